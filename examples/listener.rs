@@ -1,7 +1,7 @@
 #![allow(warnings)]
 
-use kmhook_rs::{
-    types::{EventListener, EventType, KeyMappingId, KeyId, MouseButton},
+use kmhook::{
+    types::{Event, EventFilter, EventListener, KeyId, KeyMappingId, MouseButton},
     Listener,
 };
 use std::sync::{Arc, Mutex};
@@ -11,13 +11,10 @@ fn main() {
 
     let l = listener.clone();
     let result = listener.add_event_listener(
-        move |event_type: EventType| match event_type {
-            EventType::KeyboardEvent(Some(info)) => {
+        move |event: Event| match event {
+            Event::KeyboardEvent(info) => {
                 println!("KeyboardEvent {:?}", info);
-                println!(
-                    "KeyboardState {:?}",
-                    info.keyboard_state.unwrap().usb_input_report()
-                );
+                println!("KeyboardState {:?}", info.keyboard_state.unwrap().keys);
                 if info.key_id == KeyId::from(KeyMappingId::UsA) {
                     println!("Pressed A");
                 } else if info.key_id == KeyId::from(KeyMappingId::Escape) {
@@ -27,23 +24,26 @@ fn main() {
             }
             _ => {}
         },
-        Some(EventType::KeyboardEvent(None)),
+        Some(EventFilter::Keyboard),
     );
     println!("{:?}", result);
 
     listener.add_event_listener(
-        move |event_type| match event_type {
-            EventType::MouseEvent(Some(info)) => {
+        move |event| match event {
+            Event::MouseEvent(info) => {
                 println!("Mouse Button {:?}", info.button);
                 println!("Mouse Position {:?}", info.pos);
                 println!("Mouse State {:?}", info.relative_pos);
             }
             _ => {}
         },
-        Some(EventType::MouseEvent(None)),
+        Some(EventFilter::Mouse),
     );
 
-    if let Some(join) = listener.startup(Some(true)) {
+    if let Some(join) = listener
+        .startup(Some(true))
+        .expect("failed to start listener")
+    {
         join.join().unwrap();
     }
     // listener.startup(None);
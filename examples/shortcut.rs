@@ -46,6 +46,18 @@ fn main() {
         Some(400),
     );
 
+    listener::add_global_shortcut(
+        "Ctrl+MouseLeft",
+        || println!("Ctrl + Click"),
+    );
+
+    listener::add_global_shortcut_trigger(
+        "MouseX1",
+        || println!("Double click X1"),
+        2,
+        None,
+    );
+
     // Illegal shortcut key
     // listener.add_global_shortcut(
     //     Shortcut::new(vec![KeyMappingId::ControlLeft, KeyMappingId::UsV, KeyMappingId::UsC, KeyMappingId::UsC]).expect("Failed to create shortcut"),
@@ -54,7 +66,7 @@ fn main() {
 
     // listener.startup(None);
     // work on thread
-    if let Some(join) = listener::startup(Some(true)) {
+    if let Some(join) = listener::startup(Some(true)).expect("failed to start listener") {
         join.join().unwrap();
     }
 }
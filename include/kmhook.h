@@ -0,0 +1,70 @@
+/* C API of kmhook, built with the `cabi` feature:
+   cargo rustc --lib --release --features cabi --crate-type cdylib
+   See src/ffi.rs. */
+
+#ifndef KMHOOK_H
+#define KMHOOK_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define KMHOOK_FILTER_ALL 0
+#define KMHOOK_FILTER_KEYBOARD 1
+#define KMHOOK_FILTER_MOUSE 2
+
+#define KMHOOK_EVENT_KEY 1
+#define KMHOOK_EVENT_MOUSE 2
+
+#define KMHOOK_BUTTON_NONE 0
+#define KMHOOK_BUTTON_LEFT 1
+#define KMHOOK_BUTTON_RIGHT 2
+#define KMHOOK_BUTTON_MIDDLE 3
+#define KMHOOK_BUTTON_X1 4
+#define KMHOOK_BUTTON_X2 5
+
+typedef struct kmhook_event {
+    uint32_t kind;
+    /* Key name as in shortcut strings, e.g. "A" or "CtrlLeft". Only valid
+       during the callback; NULL for mouse events. */
+    const char *key;
+    uint32_t vk;
+    uint32_t scancode;
+    bool pressed;
+    bool repeat;
+    bool injected;
+    int32_t x;
+    int32_t y;
+    uint32_t button;
+    int32_t wheel;
+    int32_t hwheel;
+} kmhook_event;
+
+typedef void (*kmhook_shortcut_callback)(void *user_data);
+typedef void (*kmhook_event_callback)(const kmhook_event *event, void *user_data);
+
+const char *kmhook_last_error(void);
+
+/* Return the registration id, or 0 on failure. */
+size_t kmhook_add_global_shortcut(const char *shortcut, kmhook_shortcut_callback cb,
+                                  void *user_data);
+size_t kmhook_add_global_shortcut_trigger(const char *shortcut, kmhook_shortcut_callback cb,
+                                          void *user_data, uint32_t trigger,
+                                          uint32_t interval);
+size_t kmhook_add_event_listener(kmhook_event_callback cb, void *user_data, uint32_t filter);
+void kmhook_del_event_by_id(size_t id);
+
+/* Returns 0, or -1 on failure, including when already started and not
+   shut down since. */
+int32_t kmhook_startup(void);
+void kmhook_shutdown(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KMHOOK_H */
@@ -0,0 +1,66 @@
+//! Procedural macros for kmhook. Use through the `macros` feature of `kmhook`.
+
+use proc_macro::{TokenStream, TokenTree};
+
+#[path = "../../src/keyname.rs"]
+mod keyname;
+
+fn compile_error(msg: &str) -> TokenStream {
+    format!("compile_error!({:?})", msg).parse().unwrap()
+}
+
+/// The string literal passed to the macro, without quotes.
+fn string_literal(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+        _ => return Err("expected a string literal, e.g. shortcut!(\"Ctrl+Alt+T\")".to_string()),
+    };
+    literal
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .filter(|s| !s.contains('\\'))
+        .map(str::to_string)
+        .ok_or_else(|| "expected a plain string literal".to_string())
+}
+
+/// A `kmhook::types::Shortcut` whose keys are checked at compile time:
+///
+/// ```ignore
+/// let shortcut = kmhook::shortcut!("Ctrl+Alt+T");
+/// ```
+#[proc_macro]
+pub fn shortcut(input: TokenStream) -> TokenStream {
+    let keys = match string_literal(input) {
+        Ok(keys) => keys,
+        Err(e) => return compile_error(&e),
+    };
+    let keys = match keyname::split_keys(&keys) {
+        Ok(keys) => keys,
+        Err(e) => return compile_error(&e),
+    };
+    let mut setters = Vec::new();
+    for key in keys {
+        if let Some(i) = keyname::mouse_key_index(key) {
+            setters.push(format!(
+                "shortcut.set_mouse_key(::kmhook::types::MouseKey::from_name({:?}).unwrap());",
+                keyname::MOUSE_KEY_NAMES[i]
+            ));
+            continue;
+        }
+        match keyname::normalize_key(key) {
+            Ok(key) => setters.push(format!(
+                "shortcut.set_key(::kmhook::types::VirtualKeyId::{:?});",
+                key
+            )),
+            Err(e) => return compile_error(&e),
+        }
+    }
+
+    format!(
+        "{{ let mut shortcut = ::kmhook::types::Shortcut::default(); {} shortcut }}",
+        setters.join(" ")
+    )
+    .parse()
+    .unwrap()
+}
@@ -0,0 +1,5 @@
+const COMMANDS: &[&str] = &["register", "unregister", "subscribe", "shortcuts"];
+
+fn main() {
+    tauri_plugin::Builder::new(COMMANDS).build();
+}
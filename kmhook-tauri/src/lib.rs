@@ -0,0 +1,202 @@
+//! Tauri plugin for kmhook: global shortcuts with everything kmhook parses,
+//! sequences like "Ctrl+K, Ctrl+C", multi-press triggers and mouse keys, and
+//! input events streamed to the webview. Built on `kmhook::enginer`, so it
+//! shares the process-wide listener with the rest of the application.
+//!
+//! ```ignore
+//! tauri::Builder::default()
+//!     .plugin(kmhook_tauri::init())
+//!     .run(tauri::generate_context!())
+//!     .unwrap();
+//! ```
+//!
+//! From the webview, after granting `kmhook:default`:
+//!
+//! ```js
+//! import { invoke, Channel } from "@tauri-apps/api/core";
+//! import { listen } from "@tauri-apps/api/event";
+//!
+//! await listen("kmhook://shortcut", (e) => console.log(e.payload.shortcut));
+//! const id = await invoke("plugin:kmhook|register", { shortcut: "Ctrl+K, Ctrl+C" });
+//! const onEvent = new Channel();
+//! onEvent.onmessage = (event) => console.log(event.kind, event);
+//! await invoke("plugin:kmhook|subscribe", { filter: "Keyboard", onEvent });
+//! ```
+//!
+//! The plugin owns the shared listener's lifecycle: it calls
+//! `enginer::startup` when set up and `enginer::shutdown` when dropped, which
+//! removes every registration, the application's own included. Do not start
+//! or shut down `enginer` yourself while using the plugin. Its commands only
+//! see what was registered through them.
+//!
+//! `enginer` exists on Windows, and on Linux with the `evdev` feature; on
+//! other targets the crate is empty.
+
+#![cfg(any(windows, all(target_os = "linux", feature = "evdev")))]
+
+use kmhook::enginer;
+use kmhook::types::{Event, EventFilter, KeyId, KeyState, MouseKey, ID};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::ipc::Channel;
+use tauri::plugin::{Builder, TauriPlugin};
+use tauri::{AppHandle, Emitter, Manager, Runtime, State};
+
+/// App event emitted with a `ShortcutFired` when a shortcut registered
+/// through the plugin fires.
+pub const SHORTCUT_EVENT: &str = "kmhook://shortcut";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutFired {
+    pub id: ID,
+    pub shortcut: String,
+}
+
+/// IDs registered through the plugin's commands, so the webview cannot
+/// list or remove the application's own registrations.
+#[derive(Default)]
+struct Owned(Mutex<HashSet<ID>>);
+
+/// What `subscribe` sends down its channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum InputEvent {
+    Key {
+        /// The key as written in shortcut strings, e.g. "A" or "CtrlLeft".
+        key: KeyId,
+        pressed: bool,
+        repeat: bool,
+    },
+    Mouse {
+        x: i32,
+        y: i32,
+        /// Mouse key name as in shortcut strings; `None` for moves and wheel
+        /// turns.
+        button: Option<String>,
+        pressed: bool,
+        /// Wheel rotation, 120 per notch.
+        wheel: i32,
+        hwheel: i32,
+    },
+}
+
+impl InputEvent {
+    fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::KeyboardEvent(key_info) => Some(Self::Key {
+                key: key_info.key_id,
+                pressed: key_info.state == KeyState::Pressed,
+                repeat: key_info.is_repeat,
+            }),
+            Event::MouseEvent(mouse_info) => Some(Self::Mouse {
+                x: mouse_info.pos.x,
+                y: mouse_info.pos.y,
+                button: mouse_info
+                    .button
+                    .as_ref()
+                    .map(|button| MouseKey::from(button).name().to_string()),
+                pressed: mouse_info
+                    .button
+                    .as_ref()
+                    .map_or(false, |button| button.state() == KeyState::Pressed),
+                wheel: mouse_info.wheel.y,
+                hwheel: mouse_info.wheel.x,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Registers `shortcut` and emits `SHORTCUT_EVENT` each time it fires. With
+/// `trigger`, it must be completed that many times, each within `interval`
+/// ms of the previous, as for `add_global_shortcut_trigger`.
+#[tauri::command]
+fn register<R: Runtime>(
+    app: AppHandle<R>,
+    owned: State<'_, Owned>,
+    shortcut: String,
+    trigger: Option<u32>,
+    interval: Option<u32>,
+) -> Result<ID, String> {
+    // The callback is registered before its ID is known.
+    let registered: Arc<OnceLock<ID>> = Arc::new(OnceLock::new());
+    let fired = {
+        let registered = registered.clone();
+        let shortcut = shortcut.clone();
+        move || {
+            if let Some(&id) = registered.get() {
+                let _ = app.emit(
+                    SHORTCUT_EVENT,
+                    ShortcutFired {
+                        id,
+                        shortcut: shortcut.clone(),
+                    },
+                );
+            }
+        }
+    };
+    let id = match trigger {
+        Some(trigger) => enginer::add_global_shortcut_trigger(&shortcut, fired, trigger, interval)?,
+        None => enginer::add_global_shortcut(&shortcut, fired)?,
+    };
+    let _ = registered.set(id);
+    owned.0.lock().unwrap().insert(id);
+    Ok(id)
+}
+
+/// Removes a shortcut or subscription made through the plugin; other IDs
+/// are ignored.
+#[tauri::command]
+fn unregister(owned: State<'_, Owned>, id: ID) {
+    if owned.0.lock().unwrap().remove(&id) {
+        enginer::del_event_by_id(id);
+    }
+}
+
+/// Sends the input events matching `filter` down `on_event` until
+/// unregistered.
+#[tauri::command]
+fn subscribe(
+    owned: State<'_, Owned>,
+    filter: Option<EventFilter>,
+    on_event: Channel<InputEvent>,
+) -> Result<ID, String> {
+    let id = enginer::add_event_listener(
+        move |event| {
+            if let Some(event) = InputEvent::from_event(&event) {
+                let _ = on_event.send(event);
+            }
+        },
+        filter,
+    )?;
+    owned.0.lock().unwrap().insert(id);
+    Ok(id)
+}
+
+/// The shortcuts registered through the plugin.
+#[tauri::command]
+fn shortcuts(owned: State<'_, Owned>) -> Vec<(ID, String)> {
+    let owned = owned.0.lock().unwrap();
+    enginer::shortcuts()
+        .into_iter()
+        .filter(|(id, _)| owned.contains(id))
+        .map(|(id, sequence)| (id, sequence.to_string()))
+        .collect()
+}
+
+/// Starts the shared listener with the app and shuts it down with it; see
+/// the crate docs.
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new("kmhook")
+        .invoke_handler(tauri::generate_handler![
+            register, unregister, subscribe, shortcuts
+        ])
+        .setup(|app, _api| {
+            app.manage(Owned::default());
+            enginer::startup(Some(true))?;
+            Ok(())
+        })
+        .on_drop(|_app| enginer::shutdown())
+        .build()
+}
@@ -0,0 +1,90 @@
+//! Hook DLL for the `dll-hook` backend of kmhook. `Backend::DllHook` loads it
+//! and sets its procs as `WH_KEYBOARD`/`WH_MOUSE` hooks on a thread of
+//! another process; Windows then maps it into that process, where the procs
+//! post what they see back to the listener's window.
+//!
+//! Build it for the bitness of the processes it is meant to hook.
+#![cfg(windows)]
+
+#[allow(dead_code)]
+#[path = "../../src/windows/dll_hook_abi.rs"]
+mod abi;
+
+use std::cell::Cell;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, FindWindowExW, PostMessageW, HC_ACTION, HWND_MESSAGE, MOUSEHOOKSTRUCTEX,
+};
+
+thread_local! {
+    /// The listener window of the hooked thread, once found.
+    static SINK: Cell<Option<HWND>> = const { Cell::new(None) };
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+fn find_sink() -> Option<HWND> {
+    let class = wide(abi::SINK_CLASS);
+    let name = wide(&abi::sink_name(unsafe { GetCurrentThreadId() }));
+    unsafe {
+        FindWindowExW(
+            HWND_MESSAGE,
+            None,
+            PCWSTR(class.as_ptr()),
+            PCWSTR(name.as_ptr()),
+        )
+    }
+    .ok()
+}
+
+/// Posts to the listener window, looking it up again if the one cached was
+/// destroyed (the listener restarted).
+fn post(msg: u32, wparam: usize, lparam: isize) {
+    for _ in 0..2 {
+        let Some(sink) = SINK.get().or_else(find_sink) else {
+            return;
+        };
+        if unsafe { PostMessageW(sink, msg, WPARAM(wparam), LPARAM(lparam)) }.is_ok() {
+            SINK.set(Some(sink));
+            return;
+        }
+        SINK.set(None);
+    }
+}
+
+/// `KeyboardProc` forwarding each keystroke the thread takes off its queue.
+#[no_mangle]
+pub unsafe extern "system" fn kmhook_keyboard_proc(
+    code: i32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        post(abi::WM_DLL_HOOK_KEYBOARD, wparam.0, lparam.0);
+    }
+    CallNextHookEx(None, code, wparam, lparam)
+}
+
+/// `MouseProc` forwarding each mouse message the thread takes off its queue.
+#[no_mangle]
+pub unsafe extern "system" fn kmhook_mouse_proc(
+    code: i32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const MOUSEHOOKSTRUCTEX);
+        let (msg_wparam, msg_lparam) = abi::pack_mouse(
+            wparam.0 as u32,
+            info.mouseData,
+            info.Base.pt.x,
+            info.Base.pt.y,
+        );
+        post(abi::WM_DLL_HOOK_MOUSE, msg_wparam, msg_lparam);
+    }
+    CallNextHookEx(None, code, wparam, lparam)
+}
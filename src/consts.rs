@@ -1,2 +1,29 @@
 // pub const MAX_KEYS: usize = 10;
-pub const DEFAULT_SHORTCUT_TRIGGER_INTERVAL: u32 = 400;
\ No newline at end of file
+pub const DEFAULT_SHORTCUT_TRIGGER_INTERVAL: u32 = 400;
+pub const DEFAULT_SEQUENCE_TIMEOUT: u64 = 1000;
+pub const DEFAULT_DOUBLE_CLICK_INTERVAL: u32 = 500;
+/// Side of the area a double click must land in, in pixels; the Windows default.
+pub const DEFAULT_DOUBLE_CLICK_SIZE: i32 = 4;
+/// Span mouse velocity is averaged over, in ms.
+pub const DEFAULT_VELOCITY_WINDOW: u64 = 50;
+pub const DEFAULT_SHAKE_REVERSALS: u32 = 4;
+pub const DEFAULT_SHAKE_AMPLITUDE: i32 = 50;
+pub const DEFAULT_SHAKE_WINDOW: u64 = 800;
+/// How far the cursor must move back from the end of a swing to count as
+/// turning, in pixels.
+pub const SHAKE_TURN_SLACK: i32 = 8;
+pub const DEFAULT_TAP_TIMEOUT: u64 = 300;
+/// Input older than this that no hook saw counts as missed by the watchdog, in ms.
+pub const HOOK_WATCHDOG_GRACE: u32 = 500;
+/// Events `Listener::subscribe` queues before dropping new ones.
+pub const SUBSCRIBE_QUEUE_CAPACITY: usize = 4096;
+/// Latencies kept for the percentiles in `Listener::metrics`.
+pub const METRICS_LATENCY_SAMPLES: usize = 1024;
+/// Bytes read per `GetRawInputBuffer` call; about 100 mouse events.
+pub const RAW_INPUT_BUFFER_SIZE: usize = 4096;
+/// How often the event loops look for a keyboard layout change, in ms.
+pub const LAYOUT_POLL_INTERVAL: u32 = 250;
+/// Side of the square in a monitor corner that counts as the hot corner, in pixels.
+pub const HOT_CORNER_SIZE: i32 = 2;
+/// How far the cursor must leave a hot corner before it can fire again, in pixels.
+pub const HOT_CORNER_REARM_DISTANCE: i32 = 24;
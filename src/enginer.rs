@@ -1,5 +1,10 @@
-use crate::types::{EventListener, EventType, JoinHandleType, ID};
-use crate::Listener;
+use crate::types::{
+    CallbackPanic, ConflictError, Corner, Event, EventFilter, EventListener, GroupId,
+    JoinHandleType, KeyId, KeyInfo, KmHookError, ListenerStateReport, Modifiers, MouseKey, Pos,
+    Propagation, Rect, RegisterError, ShakeOptions, Shortcut, ShortcutOptions, ShortcutSequence,
+    SlowCallback, ID,
+};
+use crate::{EventReceiver, Listener, Subscription};
 use lazy_static::lazy_static;
 use std::sync::Arc;
 
@@ -7,26 +12,172 @@ lazy_static! {
     static ref LISTENER: Arc<Listener> = Listener::new();
 }
 
-pub fn add_global_shortcut<F>(shortcut: &str, cb: F) -> std::result::Result<ID, String>
+pub fn add_global_shortcut<F>(shortcut: &str, cb: F) -> std::result::Result<ID, RegisterError>
 where
     F: Fn() + Send + Sync + 'static,
 {
     LISTENER.add_global_shortcut(shortcut, cb)
 }
 
+pub fn add_global_shortcut_with_options<F>(
+    shortcut: &str,
+    cb: F,
+    options: ShortcutOptions,
+) -> std::result::Result<ID, RegisterError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_global_shortcut_with_options(shortcut, cb, options)
+}
 
 pub fn add_global_shortcut_trigger<F>(
     shortcut: &str,
     cb: F,
     trigger: u32,
     internal: Option<u32>,
-) -> std::result::Result<ID, String>
+) -> std::result::Result<ID, RegisterError>
 where
     F: Fn() + Send + Sync + 'static,
 {
     LISTENER.add_global_shortcut_trigger(shortcut, cb, trigger, internal)
 }
 
+pub fn add_global_shortcut_trigger_with_options<F>(
+    shortcut: &str,
+    cb: F,
+    trigger: u32,
+    internal: Option<u32>,
+    options: ShortcutOptions,
+) -> std::result::Result<ID, RegisterError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_global_shortcut_trigger_with_options(shortcut, cb, trigger, internal, options)
+}
+
+pub fn add_global_shortcut_scoped<F>(
+    shortcut: &str,
+    cb: F,
+) -> std::result::Result<Subscription, RegisterError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_global_shortcut_scoped(shortcut, cb)
+}
+
+pub fn add_event_listener_queued<F>(
+    cb: F,
+    filter: Option<EventFilter>,
+    capacity: usize,
+) -> std::result::Result<ID, String>
+where
+    F: Fn(Event) + Send + 'static,
+{
+    LISTENER.add_event_listener_queued(cb, filter, capacity)
+}
+
+pub fn add_event_listener_with_priority<F>(
+    cb: F,
+    filter: Option<EventFilter>,
+    priority: i32,
+) -> std::result::Result<ID, String>
+where
+    F: Fn(Event) -> Propagation + Send + Sync + 'static,
+{
+    LISTENER.add_event_listener_with_priority(cb, filter, priority)
+}
+
+pub fn add_event_listener_times<F>(
+    cb: F,
+    filter: Option<EventFilter>,
+    times: u64,
+) -> std::result::Result<ID, String>
+where
+    F: Fn(Event) + Send + Sync + 'static,
+{
+    LISTENER.add_event_listener_times(cb, filter, times)
+}
+
+pub fn add_event_listener_once<F>(
+    cb: F,
+    filter: Option<EventFilter>,
+) -> std::result::Result<ID, String>
+where
+    F: Fn(Event) + Send + Sync + 'static,
+{
+    LISTENER.add_event_listener_once(cb, filter)
+}
+
+pub fn add_global_shortcut_once<F>(shortcut: &str, cb: F) -> std::result::Result<ID, RegisterError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_global_shortcut_once(shortcut, cb)
+}
+
+pub fn add_event_listener_scoped<F>(
+    cb: F,
+    filter: Option<EventFilter>,
+) -> std::result::Result<Subscription, String>
+where
+    F: Fn(Event) + Send + Sync + 'static,
+{
+    LISTENER.add_event_listener_scoped(cb, filter)
+}
+
+#[cfg(feature = "suppression")]
+pub fn block_input_for(duration: std::time::Duration) -> std::result::Result<(), String> {
+    LISTENER.block_input_for(duration)
+}
+
+#[cfg(feature = "suppression")]
+pub fn unblock_input() {
+    LISTENER.unblock_input()
+}
+
+pub fn set_shortcut_enabled(id: ID, enabled: bool) -> std::result::Result<(), String> {
+    LISTENER.set_shortcut_enabled(id, enabled)
+}
+
+pub fn shortcuts() -> Vec<(ID, ShortcutSequence)> {
+    LISTENER.shortcuts()
+}
+
+pub fn event_listeners() -> Vec<(ID, EventFilter)> {
+    LISTENER.event_listeners()
+}
+
+pub fn dump_state() -> ListenerStateReport {
+    LISTENER.dump_state()
+}
+
+pub fn find_conflicts(shortcut: &ShortcutSequence) -> std::result::Result<(), ConflictError> {
+    LISTENER.find_conflicts(shortcut)
+}
+
+pub fn create_group() -> GroupId {
+    LISTENER.create_group()
+}
+
+pub fn add_global_shortcut_to_group<F>(
+    group: GroupId,
+    shortcut: &str,
+    cb: F,
+) -> std::result::Result<ID, RegisterError>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_global_shortcut_to_group(group, shortcut, cb)
+}
+
+pub fn set_group_enabled(group: GroupId, enabled: bool) -> std::result::Result<(), String> {
+    LISTENER.set_group_enabled(group, enabled)
+}
+
+pub fn del_group(group: GroupId) -> std::result::Result<(), String> {
+    LISTENER.del_group(group)
+}
+
 pub fn del_event_by_id(id: ID) {
     LISTENER.del_event_by_id(id);
 }
@@ -35,20 +186,117 @@ pub fn del_all_events() {
     LISTENER.del_all_events();
 }
 
-pub fn add_event_listener<F>(
-    cb: F,
-    event_type: Option<EventType>,
-) -> std::result::Result<ID, String>
+pub fn add_event_listener<F>(cb: F, filter: Option<EventFilter>) -> std::result::Result<ID, String>
+where
+    F: Fn(Event) + Send + Sync + 'static,
+{
+    LISTENER.add_event_listener(cb, filter)
+}
+
+pub fn add_event_listener_filtered<P, F>(predicate: P, cb: F) -> std::result::Result<ID, String>
+where
+    P: Fn(&Event) -> bool + Send + Sync + 'static,
+    F: Fn(Event) + Send + Sync + 'static,
+{
+    LISTENER.add_event_listener_filtered(predicate, cb)
+}
+
+pub fn add_hot_corner<F>(corner: Corner, dwell_ms: u32, cb: F) -> std::result::Result<ID, String>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_hot_corner(corner, dwell_ms, cb)
+}
+
+pub fn add_shake_trigger<F>(options: ShakeOptions, cb: F) -> std::result::Result<ID, String>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    LISTENER.add_shake_trigger(options, cb)
+}
+
+pub fn update_event_filter(id: ID, filter: EventFilter) -> std::result::Result<(), String> {
+    LISTENER.update_event_filter(id, filter)
+}
+
+pub fn confine_cursor(rect: Rect) {
+    LISTENER.confine_cursor(rect);
+}
+
+pub fn release_cursor() {
+    LISTENER.release_cursor();
+}
+
+pub fn subscribe(filter: Option<EventFilter>) -> std::result::Result<EventReceiver, String> {
+    LISTENER.subscribe(filter)
+}
+
+pub fn subscribe_with_capacity(
+    filter: Option<EventFilter>,
+    capacity: usize,
+) -> std::result::Result<EventReceiver, String> {
+    LISTENER.subscribe_with_capacity(filter, capacity)
+}
+
+pub fn next_event(
+    filter: Option<EventFilter>,
+    timeout: Option<std::time::Duration>,
+) -> Option<Event> {
+    LISTENER.next_event(filter, timeout)
+}
+
+pub fn wait_for_key(timeout: Option<std::time::Duration>) -> Option<KeyInfo> {
+    LISTENER.wait_for_key(timeout)
+}
+
+pub fn capture_shortcut(
+    timeout: Option<std::time::Duration>,
+) -> std::result::Result<Shortcut, String> {
+    LISTENER.capture_shortcut(timeout)
+}
+
+pub fn is_key_pressed(key: KeyId) -> bool {
+    LISTENER.is_key_pressed(key)
+}
+
+pub fn pressed_keys() -> Vec<KeyId> {
+    LISTENER.pressed_keys()
+}
+
+pub fn modifiers() -> Modifiers {
+    LISTENER.modifiers()
+}
+
+pub fn mouse_position() -> Pos {
+    LISTENER.mouse_position()
+}
+
+pub fn pressed_mouse_buttons() -> Vec<MouseKey> {
+    LISTENER.pressed_mouse_buttons()
+}
+
+pub fn set_error_handler<F>(handler: F)
+where
+    F: Fn(CallbackPanic) + Send + Sync + 'static,
+{
+    LISTENER.set_error_handler(handler)
+}
+
+pub fn set_slow_callback_handler<F>(threshold: std::time::Duration, handler: F)
 where
-    F: Fn(EventType) + Send + Sync + 'static,
+    F: Fn(SlowCallback) + Send + Sync + 'static,
 {
-    LISTENER.add_event_listener(cb, event_type)
+    LISTENER.set_slow_callback_handler(threshold, handler)
 }
 
-pub fn startup(work_thread: Option<bool>) -> Option<JoinHandleType> {
+pub fn startup(work_thread: Option<bool>) -> Result<Option<JoinHandleType>, KmHookError> {
     LISTENER.startup(work_thread)
 }
 
+pub fn run_blocking() -> Result<(), KmHookError> {
+    LISTENER.run_blocking()
+}
+
 pub fn shutdown() {
     LISTENER.shutdown();
 }
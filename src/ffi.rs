@@ -0,0 +1,392 @@
+//! C ABI over `enginer`, for hosts that embed the hook engine without
+//! writing Rust. Declarations are in `include/kmhook.h`.
+//!
+//! Strings are NUL-terminated UTF-8. Functions that fail return 0 or -1 and
+//! leave a message for `kmhook_last_error` on the calling thread. Callbacks
+//! run on the worker thread, never on the thread that registered them.
+
+use crate::enginer;
+use crate::types::{Event, EventFilter, JoinHandleType, KeyState, MouseButton, Shortcut, ID};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+pub const KMHOOK_FILTER_ALL: u32 = 0;
+pub const KMHOOK_FILTER_KEYBOARD: u32 = 1;
+pub const KMHOOK_FILTER_MOUSE: u32 = 2;
+
+pub const KMHOOK_EVENT_KEY: u32 = 1;
+pub const KMHOOK_EVENT_MOUSE: u32 = 2;
+
+pub const KMHOOK_BUTTON_NONE: u32 = 0;
+pub const KMHOOK_BUTTON_LEFT: u32 = 1;
+pub const KMHOOK_BUTTON_RIGHT: u32 = 2;
+pub const KMHOOK_BUTTON_MIDDLE: u32 = 3;
+pub const KMHOOK_BUTTON_X1: u32 = 4;
+pub const KMHOOK_BUTTON_X2: u32 = 5;
+
+pub type KmhookShortcutCallback = extern "C" fn(user_data: *mut c_void);
+pub type KmhookEventCallback = extern "C" fn(event: *const KmhookEvent, user_data: *mut c_void);
+
+/// A keyboard or mouse event; fields that do not apply to `kind` are zero.
+#[repr(C)]
+#[derive(Debug)]
+pub struct KmhookEvent {
+    /// `KMHOOK_EVENT_KEY` or `KMHOOK_EVENT_MOUSE`.
+    pub kind: u32,
+    /// Key name as in shortcut strings, e.g. "A" or "CtrlLeft". Only valid
+    /// during the callback; null for mouse events.
+    pub key: *const c_char,
+    /// Windows virtual-key code; zero on Linux.
+    pub vk: u32,
+    pub scancode: u32,
+    /// A key or button went down rather than up.
+    pub pressed: bool,
+    pub repeat: bool,
+    pub injected: bool,
+    pub x: i32,
+    pub y: i32,
+    /// One of the `KMHOOK_BUTTON_*` values.
+    pub button: u32,
+    /// Wheel rotation, 120 per notch.
+    pub wheel: i32,
+    pub hwheel: i32,
+}
+
+/// The host's context pointer, handed back to its callbacks untouched.
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+// The host is responsible for what it passes to callbacks on the worker.
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    // A method rather than `.0`, so closures capture the `Send` wrapper and
+    // not the bare pointer.
+    fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// The worker thread started by `kmhook_startup`, joined on shutdown.
+static WORK_THREAD: Mutex<Option<JoinHandleType>> = Mutex::new(None);
+
+fn set_last_error(err: impl ToString) {
+    let msg = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with_borrow_mut(|last| *last = msg);
+}
+
+fn filter_from(filter: u32) -> Result<EventFilter, String> {
+    match filter {
+        KMHOOK_FILTER_ALL => Ok(EventFilter::All),
+        KMHOOK_FILTER_KEYBOARD => Ok(EventFilter::Keyboard),
+        KMHOOK_FILTER_MOUSE => Ok(EventFilter::Mouse),
+        _ => Err(format!("unknown filter {}", filter)),
+    }
+}
+
+fn button_code(button: &MouseButton) -> u32 {
+    match button {
+        MouseButton::Left(_) => KMHOOK_BUTTON_LEFT,
+        MouseButton::Right(_) => KMHOOK_BUTTON_RIGHT,
+        MouseButton::Middle(_) => KMHOOK_BUTTON_MIDDLE,
+        MouseButton::X1(_) => KMHOOK_BUTTON_X1,
+        MouseButton::X2(_) => KMHOOK_BUTTON_X2,
+    }
+}
+
+/// Hands `event` to `cb` if it is a keyboard or mouse event.
+fn deliver(cb: KmhookEventCallback, user_data: UserData, event: &Event) {
+    let blank = KmhookEvent {
+        kind: 0,
+        key: std::ptr::null(),
+        vk: 0,
+        scancode: 0,
+        pressed: false,
+        repeat: false,
+        injected: false,
+        x: 0,
+        y: 0,
+        button: KMHOOK_BUTTON_NONE,
+        wheel: 0,
+        hwheel: 0,
+    };
+    match event {
+        Event::KeyboardEvent(key_info) => {
+            let name =
+                CString::new(Shortcut::canonical_key_name(&key_info.key_id.0)).unwrap_or_default();
+            let c_event = KmhookEvent {
+                kind: KMHOOK_EVENT_KEY,
+                key: name.as_ptr(),
+                vk: key_info.vk,
+                scancode: key_info.scancode,
+                pressed: key_info.state == KeyState::Pressed,
+                repeat: key_info.is_repeat,
+                injected: key_info.injected,
+                ..blank
+            };
+            cb(&c_event, user_data.get());
+        }
+        Event::MouseEvent(mouse_info) => {
+            let c_event = KmhookEvent {
+                kind: KMHOOK_EVENT_MOUSE,
+                x: mouse_info.pos.x,
+                y: mouse_info.pos.y,
+                button: mouse_info
+                    .button
+                    .as_ref()
+                    .map_or(KMHOOK_BUTTON_NONE, button_code),
+                pressed: mouse_info
+                    .button
+                    .as_ref()
+                    .map_or(false, |button| button.state() == KeyState::Pressed),
+                wheel: mouse_info.wheel.y,
+                hwheel: mouse_info.wheel.x,
+                ..blank
+            };
+            cb(&c_event, user_data.get());
+        }
+        _ => {}
+    }
+}
+
+/// # Safety
+/// `s` must be null or a NUL-terminated string.
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err("null string".to_string());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| "string is not UTF-8".to_string())
+}
+
+fn id_or_zero(result: Result<ID, String>) -> ID {
+    result.unwrap_or_else(|err| {
+        set_last_error(err);
+        0
+    })
+}
+
+/// Message of the last failed call on this thread; empty if none failed.
+/// Valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn kmhook_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|last| last.as_ptr())
+}
+
+/// Registers `shortcut`, e.g. "Ctrl+Alt+T", calling `cb(user_data)` when it
+/// fires. Returns its id, or 0 on failure.
+///
+/// # Safety
+/// `shortcut` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kmhook_add_global_shortcut(
+    shortcut: *const c_char,
+    cb: Option<KmhookShortcutCallback>,
+    user_data: *mut c_void,
+) -> ID {
+    kmhook_add_global_shortcut_trigger(shortcut, cb, user_data, 1, 0)
+}
+
+/// As `kmhook_add_global_shortcut`, firing once `shortcut` was completed
+/// `trigger` times, each within `interval` ms of the previous; 0 for the
+/// default interval.
+///
+/// # Safety
+/// `shortcut` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn kmhook_add_global_shortcut_trigger(
+    shortcut: *const c_char,
+    cb: Option<KmhookShortcutCallback>,
+    user_data: *mut c_void,
+    trigger: u32,
+    interval: u32,
+) -> ID {
+    let user_data = UserData(user_data);
+    id_or_zero((|| -> Result<ID, String> {
+        let shortcut = str_arg(shortcut)?;
+        let cb = cb.ok_or("null callback")?;
+        let fired = move || cb(user_data.get());
+        let id = if trigger > 1 {
+            enginer::add_global_shortcut_trigger(
+                shortcut,
+                fired,
+                trigger,
+                (interval > 0).then_some(interval),
+            )?
+        } else {
+            enginer::add_global_shortcut(shortcut, fired)?
+        };
+        Ok(id)
+    })())
+}
+
+/// Calls `cb(event, user_data)` for the keyboard and/or mouse events
+/// selected by `filter`, one of the `KMHOOK_FILTER_*` values. Returns the
+/// listener's id, or 0 on failure.
+#[no_mangle]
+pub extern "C" fn kmhook_add_event_listener(
+    cb: Option<KmhookEventCallback>,
+    user_data: *mut c_void,
+    filter: u32,
+) -> ID {
+    let user_data = UserData(user_data);
+    id_or_zero((|| {
+        let cb = cb.ok_or("null callback")?;
+        enginer::add_event_listener(
+            move |event| deliver(cb, user_data, &event),
+            Some(filter_from(filter)?),
+        )
+    })())
+}
+
+/// Removes a shortcut or event listener. Its callback may still be running
+/// when this returns.
+#[no_mangle]
+pub extern "C" fn kmhook_del_event_by_id(id: ID) {
+    enginer::del_event_by_id(id);
+}
+
+/// Installs the hooks and starts delivering callbacks on a worker thread.
+/// Returns 0, or -1 on failure, including when already started and not
+/// shut down since.
+#[no_mangle]
+pub extern "C" fn kmhook_startup() -> i32 {
+    // A panic elsewhere must not unwind across the C boundary here.
+    let mut work_thread = WORK_THREAD.lock().unwrap_or_else(PoisonError::into_inner);
+    if work_thread.is_some() {
+        set_last_error("already started; call kmhook_shutdown first");
+        return -1;
+    }
+    match enginer::startup(Some(true)) {
+        Ok(handle) => {
+            *work_thread = handle;
+            0
+        }
+        Err(err) => {
+            set_last_error(err);
+            -1
+        }
+    }
+}
+
+/// Removes every registration, uninstalls the hooks and, unless called from
+/// a callback, waits for the worker thread to finish, so no callback runs
+/// after it returns.
+#[no_mangle]
+pub extern "C" fn kmhook_shutdown() {
+    enginer::shutdown();
+    let handle = WORK_THREAD
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(handle) = handle {
+        if handle.thread().id() != thread::current().id() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{KeyId, KeyInfo, MouseInfo, Pos, VirtualKeyId};
+
+    /// `(kind, key, button, pressed)` of each delivered event.
+    type Seen = Vec<(u32, Option<String>, u32, bool)>;
+
+    extern "C" fn record(event: *const KmhookEvent, user_data: *mut c_void) {
+        let (event, seen) = unsafe { (&*event, &mut *(user_data as *mut Seen)) };
+        let key = (!event.key.is_null()).then(|| {
+            unsafe { CStr::from_ptr(event.key) }
+                .to_string_lossy()
+                .into_owned()
+        });
+        seen.push((event.kind, key, event.button, event.pressed));
+    }
+
+    #[test]
+    fn test_filter_from() {
+        assert_eq!(filter_from(KMHOOK_FILTER_ALL), Ok(EventFilter::All));
+        assert_eq!(
+            filter_from(KMHOOK_FILTER_KEYBOARD),
+            Ok(EventFilter::Keyboard)
+        );
+        assert_eq!(filter_from(KMHOOK_FILTER_MOUSE), Ok(EventFilter::Mouse));
+        assert!(filter_from(3).is_err());
+    }
+
+    #[test]
+    fn test_deliver() {
+        let mut seen = Seen::new();
+        let user_data = UserData(&mut seen as *mut Seen as *mut c_void);
+        let key =
+            |id: VirtualKeyId, state| Event::KeyboardEvent(KeyInfo::new(KeyId::from(id), state));
+        deliver(
+            record,
+            user_data,
+            &key(VirtualKeyId::UsA, KeyState::Pressed),
+        );
+        deliver(
+            record,
+            user_data,
+            &key(VirtualKeyId::ControlLeft, KeyState::Released),
+        );
+        deliver(
+            record,
+            user_data,
+            &Event::MouseEvent(MouseInfo {
+                button: Some(MouseButton::X2(KeyState::Pressed)),
+                ..Default::default()
+            }),
+        );
+        deliver(
+            record,
+            user_data,
+            &Event::MouseEvent(MouseInfo {
+                wheel: Pos { x: 0, y: 120 },
+                ..Default::default()
+            }),
+        );
+        assert_eq!(
+            seen,
+            vec![
+                (
+                    KMHOOK_EVENT_KEY,
+                    Some("A".to_string()),
+                    KMHOOK_BUTTON_NONE,
+                    true
+                ),
+                (
+                    KMHOOK_EVENT_KEY,
+                    Some("CtrlLeft".to_string()),
+                    KMHOOK_BUTTON_NONE,
+                    false
+                ),
+                (KMHOOK_EVENT_MOUSE, None, KMHOOK_BUTTON_X2, true),
+                (KMHOOK_EVENT_MOUSE, None, KMHOOK_BUTTON_NONE, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_last_error() {
+        let last_error = || unsafe { CStr::from_ptr(kmhook_last_error()) }.to_owned();
+        assert_eq!(id_or_zero(Ok(7)), 7);
+        assert_eq!(last_error(), CString::default());
+        assert_eq!(id_or_zero(Err("no\0pe".to_string())), 0);
+        assert_eq!(last_error().to_str(), Ok("no pe"));
+        // Per thread.
+        let other = thread::spawn(move || last_error().into_bytes())
+            .join()
+            .unwrap();
+        assert!(other.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Hot corners: a callback fired when the cursor rests in a monitor corner.
+//! Fed from the mouse events of a `Listener`; see `Listener::add_hot_corner`.
+
+use crate::consts;
+#[cfg(target_os = "linux")]
+use crate::linux::worker::ThreadSpec;
+use crate::types::{Corner, Pos, Rect};
+#[cfg(target_os = "windows")]
+use crate::windows::worker::ThreadSpec;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug)]
+struct CornerState {
+    /// Cleared when the corner fires, set again once the cursor is
+    /// `HOT_CORNER_REARM_DISTANCE` away from it.
+    armed: bool,
+    /// Dwell under way, by the generation that started it.
+    entered: Option<u64>,
+    generation: u64,
+}
+
+pub(crate) struct HotCorner {
+    corner: Corner,
+    dwell: Duration,
+    /// The threads timing the dwell.
+    thread: ThreadSpec,
+    state: Mutex<CornerState>,
+    cb: Box<dyn Fn() + Send + Sync + 'static>,
+}
+
+impl HotCorner {
+    pub(crate) fn new<F>(corner: Corner, dwell: Duration, thread: ThreadSpec, cb: F) -> Arc<Self>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            corner,
+            dwell,
+            thread,
+            state: Mutex::new(CornerState {
+                armed: true,
+                entered: None,
+                generation: 0,
+            }),
+            cb: Box::new(cb),
+        })
+    }
+
+    /// Takes a mouse event at `pos` on the monitor with `bounds`. Clicks and
+    /// wheel turns (`is_move` false) in the corner cancel the dwell, so
+    /// clicking a taskbar button there does not fire.
+    pub(crate) fn on_mouse(self: &Arc<Self>, pos: &Pos, bounds: &Rect, is_move: bool) {
+        let mut state = self.state.lock().unwrap();
+        let rearm = self.corner.area(bounds, consts::HOT_CORNER_REARM_DISTANCE);
+        if !state.armed {
+            state.armed = !rearm.contains(pos);
+            return;
+        }
+        let inside = self
+            .corner
+            .area(bounds, consts::HOT_CORNER_SIZE)
+            .contains(pos);
+        if !inside || !is_move {
+            state.entered = None;
+            return;
+        }
+        if state.entered.is_some() {
+            return;
+        }
+        if self.dwell.is_zero() {
+            state.armed = false;
+            drop(state);
+            (self.cb)();
+            return;
+        }
+        state.generation += 1;
+        let generation = state.generation;
+        state.entered = Some(generation);
+        // Nothing arrives while the cursor rests, so the dwell is timed on
+        // its own thread. It holds a weak reference: a removed hot corner
+        // does not fire.
+        let this = Arc::downgrade(self);
+        let dwell = self.dwell;
+        let spawned = self.thread.clone().spawn(move || {
+            std::thread::sleep(dwell);
+            let Some(this) = this.upgrade() else {
+                return;
+            };
+            {
+                let mut state = this.state.lock().unwrap();
+                if state.entered != Some(generation) {
+                    return;
+                }
+                state.entered = None;
+                state.armed = false;
+            }
+            (this.cb)();
+        });
+        if spawned.is_err() {
+            state.entered = None;
+        }
+    }
+}
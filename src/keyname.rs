@@ -0,0 +1,261 @@
+//! Key name normalization shared with the `kmhook-macros` crate, which
+//! includes this file through `#[path]` so `shortcut!` accepts exactly what
+//! `Shortcut::from_str` does.
+
+use keycode::{KeyMap, KeyMapping, VirtualKeyId};
+use std::str::FromStr;
+
+/// Modifier spellings, compared ignoring case. Each takes a "Left"/"Right"
+/// suffix or an "L"/"R" prefix for one side: "CtrlLeft", "RShift".
+const MODIFIER_ALIASES: [(&[&str], &str); 4] = [
+    (&["ctrl", "control"], "Control"),
+    (&["shift"], "Shift"),
+    (&["alt", "menu", "option", "opt"], "Alt"),
+    (&["meta", "win", "cmd", "command", "super"], "Meta"),
+];
+
+/// Other spellings, compared ignoring case, and the names the key table
+/// uses for them. Media keys are spelled there as in the UI Events spec
+/// ("AudioVolumeUp", "MediaTrackNext", ...). Keys are named by position on
+/// a US layout, so "Plus" is the key that types '=' and '+' there.
+const KEY_ALIASES: [(&str, &str); 57] = [
+    ("altgr", "AltRight"),
+    ("esc", "Escape"),
+    ("escape", "Escape"),
+    ("return", "Enter"),
+    ("enter", "Enter"),
+    ("del", "Delete"),
+    ("delete", "Delete"),
+    ("ins", "Insert"),
+    ("insert", "Insert"),
+    ("pgup", "PageUp"),
+    ("pageup", "PageUp"),
+    ("pgdn", "PageDown"),
+    ("pgdown", "PageDown"),
+    ("pagedown", "PageDown"),
+    ("home", "Home"),
+    ("end", "End"),
+    ("space", "Space"),
+    ("spacebar", "Space"),
+    ("tab", "Tab"),
+    ("backspace", "Backspace"),
+    ("bksp", "Backspace"),
+    ("up", "ArrowUp"),
+    ("down", "ArrowDown"),
+    ("left", "ArrowLeft"),
+    ("right", "ArrowRight"),
+    ("capslock", "CapsLock"),
+    ("numlock", "NumLock"),
+    ("scrolllock", "ScrollLock"),
+    ("printscreen", "PrintScreen"),
+    ("prtsc", "PrintScreen"),
+    ("pause", "Pause"),
+    ("apps", "ContextMenu"),
+    ("contextmenu", "ContextMenu"),
+    ("plus", "Equal"),
+    ("+", "Equal"),
+    ("=", "Equal"),
+    ("minus", "Minus"),
+    ("-", "Minus"),
+    (",", "Comma"),
+    (".", "Period"),
+    ("/", "Slash"),
+    ("\\", "Backslash"),
+    (";", "Semicolon"),
+    ("'", "Quote"),
+    ("`", "Backquote"),
+    ("[", "BracketLeft"),
+    ("]", "BracketRight"),
+    ("volumeup", "AudioVolumeUp"),
+    ("volumedown", "AudioVolumeDown"),
+    ("volumemute", "AudioVolumeMute"),
+    ("mute", "AudioVolumeMute"),
+    ("medianext", "MediaTrackNext"),
+    ("medianexttrack", "MediaTrackNext"),
+    ("mediaprev", "MediaTrackPrevious"),
+    ("mediaprevtrack", "MediaTrackPrevious"),
+    ("playpause", "MediaPlayPause"),
+    ("mail", "LaunchMail"),
+];
+
+/// The key at the position of the Windows (set 1) scan code `code`, with
+/// 0xE0 in the high byte for extended keys.
+pub(crate) fn scancode_key(code: u16) -> Option<VirtualKeyId> {
+    let keymap = KeyMap::from_key_mapping(KeyMapping::Win(code)).ok()?;
+    VirtualKeyId::try_from(keymap.id).ok()
+}
+
+/// "sc:0x1E" or "sc:30".
+fn parse_scancode(key: &str) -> Option<VirtualKeyId> {
+    let code = key.strip_prefix("sc:")?;
+    let code = match code.strip_prefix("0x").or_else(|| code.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok()?,
+        None => code.parse().ok()?,
+    };
+    scancode_key(code)
+}
+
+/// "Control", "ControlLeft", ... for a modifier spelled as in
+/// `MODIFIER_ALIASES`; `lower` is lowercase.
+fn modifier_name(lower: &str) -> Option<String> {
+    let (base, side) = if let Some(base) = lower.strip_suffix("left") {
+        (base, "Left")
+    } else if let Some(base) = lower.strip_suffix("right") {
+        (base, "Right")
+    } else {
+        (lower, "")
+    };
+    let find = |base: &str| {
+        MODIFIER_ALIASES
+            .iter()
+            .find(|(spellings, _)| spellings.contains(&base))
+            .map(|(_, name)| *name)
+    };
+    if let Some(name) = find(base) {
+        return Some(format!("{}{}", name, side));
+    }
+    if side.is_empty() {
+        let (side, base) = if let Some(base) = lower.strip_prefix('l') {
+            ("Left", base)
+        } else if let Some(base) = lower.strip_prefix('r') {
+            ("Right", base)
+        } else {
+            return None;
+        };
+        return find(base).map(|name| format!("{}{}", name, side));
+    }
+    None
+}
+
+/// `rest` with its first letter in upper case.
+fn capitalize(rest: &str) -> String {
+    let mut chars = rest.chars();
+    chars.next().map_or(String::new(), |first| {
+        first.to_ascii_uppercase().to_string() + chars.as_str()
+    })
+}
+
+/// The key table name for `key` written in any case, for the spellings
+/// this module knows about.
+fn table_name(key: &str) -> Option<String> {
+    let lower = key.to_ascii_lowercase();
+    if let Some((_, name)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == lower) {
+        return Some(name.to_string());
+    }
+    if let Some(name) = modifier_name(&lower) {
+        return Some(name);
+    }
+    let mut chars = lower.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            return Some(format!("Us{}", c.to_ascii_uppercase()))
+        }
+        (Some(c), None) if c.is_ascii_digit() => return Some(format!("Digit{}", c)),
+        _ => {}
+    }
+    if let Some(n) = lower.strip_prefix('f') {
+        if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) {
+            return Some(format!("F{}", n));
+        }
+    }
+    let keypad = lower
+        .strip_prefix("numpad")
+        .or_else(|| lower.strip_prefix("num"))
+        .filter(|rest| !rest.is_empty());
+    keypad.map(|rest| {
+        // The spellings of the numpad keys follow those of the main keys.
+        let rest = match rest {
+            "plus" | "+" => "add",
+            "minus" | "-" => "subtract",
+            "*" => "multiply",
+            "/" => "divide",
+            "." => "decimal",
+            "return" => "enter",
+            rest => rest,
+        };
+        format!("Numpad{}", capitalize(rest))
+    })
+}
+
+/// Resolves a key name as written in a shortcut string: a name from the key
+/// table ("ControlLeft", "UsA", "BrowserBack"), or ignoring case, a single
+/// character ("a", "1", "/"), an alias ("Ctrl", "Esc", "PgUp", "Return",
+/// "VolumeUp", ...), "F13", or a keypad key ("Numpad5", "NumEnter").
+/// "sc:0x1E" names a key by scan code.
+pub(crate) fn normalize_key(key: &str) -> Result<VirtualKeyId, String> {
+    let key = key.trim();
+    if key.starts_with("sc:") {
+        return parse_scancode(key).ok_or_else(|| format!("Invalid scan code: {}", key));
+    }
+    table_name(key)
+        .and_then(|name| VirtualKeyId::from_str(&name).ok())
+        .or_else(|| VirtualKeyId::from_str(key).ok())
+        .ok_or_else(|| format!("Invalid key: {}", key))
+}
+
+/// Splits "Ctrl+Shift+A" into its keys. A '+' where a key is expected is
+/// the key itself, so "Ctrl++" is the same as "Ctrl+Plus".
+pub(crate) fn split_keys(keys: &str) -> Result<Vec<&str>, String> {
+    let mut parts = Vec::new();
+    let mut rest = keys.trim();
+    loop {
+        let (key, after) = match rest.strip_prefix('+') {
+            Some(after) => ("+", after),
+            None => rest.split_at(rest.find('+').unwrap_or(rest.len())),
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("Missing key in: {}", keys));
+        }
+        parts.push(key);
+        let after = after.trim_start();
+        if after.is_empty() {
+            return Ok(parts);
+        }
+        rest = after
+            .strip_prefix('+')
+            .ok_or_else(|| format!("Expected '+' before: {}", after))?
+            .trim_start();
+    }
+}
+
+/// Splits "Ctrl+K, Ctrl+C" into its chords. As in `split_keys`, a ',' where
+/// a key is expected is the key itself, so "Ctrl+," is one chord.
+pub(crate) fn split_steps(keys: &str) -> Vec<&str> {
+    let mut steps = Vec::new();
+    let mut start = 0;
+    let mut key_expected = true;
+    for (i, c) in keys.char_indices() {
+        match c {
+            ',' if !key_expected => {
+                steps.push(&keys[start..i]);
+                start = i + 1;
+                key_expected = true;
+            }
+            '+' => key_expected = !key_expected,
+            c if c.is_whitespace() => {}
+            _ => key_expected = false,
+        }
+    }
+    steps.push(&keys[start..]);
+    steps
+}
+
+/// Names of the mouse keys usable in shortcuts, indexed like `MouseKey`.
+pub(crate) const MOUSE_KEY_NAMES: [&str; 9] = [
+    "MouseLeft",
+    "MouseRight",
+    "MouseMiddle",
+    "MouseX1",
+    "MouseX2",
+    "WheelUp",
+    "WheelDown",
+    "WheelLeft",
+    "WheelRight",
+];
+
+pub(crate) fn mouse_key_index(name: &str) -> Option<usize> {
+    MOUSE_KEY_NAMES
+        .iter()
+        .position(|n| n.eq_ignore_ascii_case(name.trim()))
+}
@@ -1,11 +1,102 @@
+//! Global keyboard and mouse hooks.
+//!
+//! # Cargo features
+//!
+//! Features only ever add capabilities; enabling one never changes the
+//! behavior of code that does not use it.
+//!
+//! - `raw-input` (default): capture input with Raw Input on a hidden window.
+//! - `ll-hook`: capture input with `WH_KEYBOARD_LL`/`WH_MOUSE_LL` hooks.
+//!   With both enabled, each listener picks one with
+//!   `ListenerBuilder::backend`; raw input is the default.
+//! - `dll-hook`: capture the input of one thread of another process with
+//!   `WH_KEYBOARD`/`WH_MOUSE` hooks set from the `kmhook-winhook` DLL; see
+//!   `ListenerBuilder::dll_hook`.
+//! - `suppression`: let shortcuts swallow the keystrokes that trigger them.
+//!   Implies `ll-hook` and makes it the default backend, since raw input
+//!   cannot block input.
+//! - `simulate`: send synthesized keyboard and mouse input (`kmhook::simulate`)
+//!   and replay `recorder::Recording`s.
+//! - `serde`: `Serialize`/`Deserialize` for plain data types.
+//! - `async`: `Listener::event_stream` and `Listener::shortcut_stream`,
+//!   `futures_core::Stream`s on top of the callback interface.
+//! - `tokio`: `Listener::add_global_shortcut_async`, shortcut callbacks that
+//!   return a future spawned on a tokio runtime. Implies `async`.
+//! - `tracing`: emit `tracing` events and spans for hook installation, event
+//!   receipt, callback dispatch and shutdown; verbosity and destination are
+//!   up to the application's subscriber.
+//! - `debug-log`: former name of `tracing`, kept as an alias.
+//! - `winit`: `winit_ext`, forwarding events and shortcuts to a winit event
+//!   loop as user events, and conversions to winit's key and button types.
+//! - `cabi`: `ffi`, C functions over `enginer` (`kmhook_startup`,
+//!   `kmhook_add_global_shortcut`, ...) for C, C++ and C# hosts. The crate
+//!   builds as an rlib only; build the DLL or shared object with
+//!   `cargo rustc --lib --release --features cabi --crate-type cdylib` and
+//!   include `include/kmhook.h`.
+//! - `macros`: `shortcut!("Ctrl+Alt+T")`, a `Shortcut` validated at compile
+//!   time.
+//! - `evdev`: Linux backend reading `/dev/input/event*`, which also works under
+//!   Wayland. Needs read access to the input device nodes.
+//!
+//! On Windows at least one of `raw-input` or `ll-hook` must be enabled.
+
 pub(crate) mod consts;
+#[cfg(any(target_os = "windows", all(target_os = "linux", feature = "evdev")))]
+pub(crate) mod hotcorner;
+pub(crate) mod keyname;
+#[cfg(any(target_os = "windows", all(target_os = "linux", feature = "evdev")))]
+pub(crate) mod shake;
 pub(crate) mod utils;
 
+#[cfg(any(target_os = "windows", all(target_os = "linux", feature = "evdev")))]
 pub mod enginer;
+#[cfg(all(
+    any(target_os = "windows", all(target_os = "linux", feature = "evdev")),
+    feature = "cabi"
+))]
+pub mod ffi;
+#[cfg(any(target_os = "windows", all(target_os = "linux", feature = "evdev")))]
+pub mod recorder;
+#[cfg(any(target_os = "windows", all(target_os = "linux", feature = "evdev")))]
+pub mod stats;
+#[cfg(all(
+    any(target_os = "windows", all(target_os = "linux", feature = "evdev")),
+    feature = "async"
+))]
+pub mod stream;
 pub mod types;
+#[cfg(all(
+    any(target_os = "windows", all(target_os = "linux", feature = "evdev")),
+    feature = "winit"
+))]
+pub mod winit_ext;
+
+#[cfg(feature = "macros")]
+pub use kmhook_macros::shortcut;
 
 #[cfg(target_os = "windows")]
 pub(crate) mod windows;
 
 #[cfg(target_os = "windows")]
-pub use windows::listener::Listener;
+pub use windows::listener::{EventReceiver, Listener, ListenerBuilder, Subscription};
+
+#[cfg(all(target_os = "windows", feature = "suppression"))]
+pub use windows::listener::InputBlockGuard;
+
+#[cfg(target_os = "windows")]
+pub use windows::device::devices;
+
+#[cfg(target_os = "windows")]
+pub use windows::layout::current_layout;
+
+#[cfg(all(target_os = "windows", feature = "simulate"))]
+pub use windows::simulate;
+
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub(crate) mod linux;
+
+#[cfg(all(target_os = "linux", feature = "evdev"))]
+pub use linux::listener::{EventReceiver, Listener, ListenerBuilder, Subscription};
+
+#[cfg(all(target_os = "linux", feature = "evdev", feature = "suppression"))]
+pub use linux::listener::InputBlockGuard;
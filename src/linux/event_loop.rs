@@ -0,0 +1,315 @@
+//! evdev backend. Reads `/dev/input/event*` directly, so global input is seen
+//! under Wayland where X11 grabs receive nothing. The process needs read access
+//! to the device nodes (root, or membership in the `input` group).
+
+use super::backend::InputBackend;
+use super::update_key_snapshot;
+use super::worker::{KeyboardSysMsg, MouseSysMsg, ThreadSpec, WorkerMsg};
+use crate::types::{
+    ClickState, HookStatus, KeyId, KeyInfo, KeyMap, KeySnapshot, KeyState, KmHookError,
+    MouseButton, MouseInfo, MouseStateFlags, Pos, VirtualKeyId, ID,
+};
+use crate::utils::gen_id;
+use crate::Listener;
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::os::raw::c_long;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0x00;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+/// evdev reports wheel notches; `MouseInfo::wheel` uses Windows' units.
+const WHEEL_DELTA: i32 = 120;
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BTN_SIDE: u16 = 0x113;
+const BTN_EXTRA: u16 = 0x114;
+
+/// `struct input_event` from `linux/input.h`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct InputEvent {
+    tv_sec: c_long,
+    tv_usec: c_long,
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+impl InputEvent {
+    /// The kernel time stamp in ms, truncated like a Windows tick count.
+    fn time(&self) -> u32 {
+        (self.tv_sec as i64 * 1000 + self.tv_usec as i64 / 1000) as u32
+    }
+}
+
+#[derive(Debug, Default)]
+struct InputState {
+    keys: KeySnapshot,
+    /// Accumulated pointer motion. evdev has no notion of the cursor, so this
+    /// is relative to where the pointer was when the listener started.
+    pos: Pos,
+}
+
+#[derive(Debug)]
+pub(crate) struct EventLoop {
+    id: ID,
+    running: Arc<AtomicBool>,
+    /// The thread inside `run_blocking`, woken by `stop`.
+    blocked: Mutex<Option<thread::Thread>>,
+    state: Mutex<InputState>,
+    listener: Weak<Listener>,
+}
+
+impl Drop for EventLoop {
+    fn drop(&mut self) {
+        EVENT_LOOP_MANAGER.lock().unwrap().del_event_loop(self.id);
+    }
+}
+
+impl EventLoop {
+    fn new(listener: &Arc<Listener>) -> Self {
+        Self {
+            id: gen_id(),
+            running: Arc::new(AtomicBool::new(false)),
+            blocked: Mutex::new(None),
+            state: Mutex::new(InputState::default()),
+            listener: Arc::downgrade(listener),
+        }
+    }
+
+    fn key_id(code: u16) -> Option<KeyId> {
+        let keymap = KeyMap::from_key_mapping(keycode::KeyMapping::Evdev(code)).ok()?;
+        VirtualKeyId::try_from(keymap.id).ok().map(KeyId)
+    }
+
+    fn mouse_button(code: u16, state: ClickState) -> Option<MouseButton> {
+        match code {
+            BTN_LEFT => Some(MouseButton::Left(state)),
+            BTN_RIGHT => Some(MouseButton::Right(state)),
+            BTN_MIDDLE => Some(MouseButton::Middle(state)),
+            BTN_SIDE => Some(MouseButton::X1(state)),
+            BTN_EXTRA => Some(MouseButton::X2(state)),
+            _ => None,
+        }
+    }
+
+    fn key_event(&self, code: u16, value: i32, time: u32) {
+        // 0 = release, 1 = press, 2 = auto-repeat.
+        let state = match value {
+            0 => KeyState::Released,
+            1 | 2 => KeyState::Pressed,
+            _ => return,
+        };
+
+        if let Some(button) = Self::mouse_button(code, state) {
+            let flags = if state == ClickState::Pressed {
+                MouseStateFlags::PRESSED
+            } else {
+                MouseStateFlags::RELEASED
+            };
+            let pos = self.state.lock().unwrap().pos.clone();
+            self.post_msg_to_worker(WorkerMsg::MouseEvent(MouseSysMsg::new(MouseInfo {
+                button: Some(button),
+                pos,
+                relative_pos: Pos::default(),
+                wheel: Pos::default(),
+                flags,
+                time,
+                ..Default::default()
+            })));
+            return;
+        }
+
+        let Some(key_id) = Self::key_id(code) else {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(?code, "Get KeyID failed for evdev code");
+            return;
+        };
+        let mut key_info = KeyInfo::new(key_id, state);
+        key_info.scancode = code as u32;
+        key_info.time = time;
+        if !update_key_snapshot(&mut self.state.lock().unwrap().keys, &mut key_info) {
+            if value != 2 {
+                return;
+            }
+            key_info.is_repeat = true;
+        }
+        self.post_msg_to_worker(WorkerMsg::KeyboardEvent(KeyboardSysMsg::new(key_info)));
+    }
+
+    fn mouse_move(&self, delta: Pos, wheel: Pos, time: u32) {
+        let pos = {
+            let mut state = self.state.lock().unwrap();
+            state.pos.x += delta.x;
+            state.pos.y += delta.y;
+            state.pos.clone()
+        };
+        self.post_msg_to_worker(WorkerMsg::MouseEvent(MouseSysMsg::new(MouseInfo {
+            button: None,
+            pos,
+            relative_pos: delta.clone(),
+            raw_delta: delta,
+            wheel,
+            flags: MouseStateFlags::empty(),
+            time,
+            ..Default::default()
+        })));
+    }
+
+    fn read_device(&self, mut device: File) {
+        let mut buf = [0u8; std::mem::size_of::<InputEvent>()];
+        let mut delta = Pos::default();
+        let mut wheel = Pos::default();
+        while self.running.load(Ordering::Relaxed) {
+            if device.read_exact(&mut buf).is_err() {
+                break;
+            }
+            let event = unsafe { std::ptr::read_unaligned(buf.as_ptr() as *const InputEvent) };
+            match (event.type_, event.code) {
+                (EV_KEY, code) => self.key_event(code, event.value, event.time()),
+                (EV_REL, REL_X) => delta.x += event.value,
+                (EV_REL, REL_Y) => delta.y += event.value,
+                (EV_REL, REL_WHEEL) => wheel.y += event.value * WHEEL_DELTA,
+                (EV_REL, REL_HWHEEL) => wheel.x += event.value * WHEEL_DELTA,
+                (EV_SYN, SYN_REPORT) if delta != Pos::default() || wheel != Pos::default() => {
+                    self.mouse_move(
+                        std::mem::take(&mut delta),
+                        std::mem::take(&mut wheel),
+                        event.time(),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn post_msg_to_worker(&self, msg: WorkerMsg) {
+        if let Some(listener) = self.listener.upgrade() {
+            listener.post_worker_msg(msg);
+        }
+    }
+
+    pub fn post_msg_to_loop(&self, _msg_type: u32) {}
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(blocked) = self.blocked.lock().unwrap().take() {
+            blocked.unpark();
+        }
+    }
+
+    /// Starts one reader thread per readable `/dev/input/event*` node. Readers
+    /// notice `stop()` after the next event from their device. Fails if no
+    /// device could be opened.
+    pub fn run_with_thread(self: &Arc<Self>) -> Result<(), KmHookError> {
+        self.running.store(true, Ordering::Relaxed);
+        let thread = ThreadSpec::event_loop(&self.listener);
+        let entries = std::fs::read_dir("/dev/input")
+            .map_err(|e| KmHookError::DeviceAccess(e.to_string()))?;
+        let mut readers = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_event_node = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with("event"));
+            if !is_event_node {
+                continue;
+            }
+            let Ok(device) = File::open(&path) else {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?path, "Open failed");
+                continue;
+            };
+            let event_loop = Arc::clone(self);
+            thread
+                .clone()
+                .spawn(move || event_loop.read_device(device))
+                .map_err(|e| KmHookError::Thread(e.to_string()))?;
+            readers += 1;
+        }
+        if readers == 0 {
+            self.running.store(false, Ordering::Relaxed);
+            return Err(KmHookError::DeviceAccess(
+                "no readable /dev/input/event* node".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl InputBackend for EventLoop {
+    fn start(self: Arc<Self>) -> Result<(), KmHookError> {
+        self.run_with_thread()
+    }
+
+    fn stop(&self) {
+        EventLoop::stop(self)
+    }
+
+    /// evdev needs a reader per device, so those still get threads; the
+    /// calling thread waits for `stop`.
+    fn run_blocking(self: Arc<Self>) -> Result<(), KmHookError> {
+        self.run_with_thread()?;
+        *self.blocked.lock().unwrap() = Some(thread::current());
+        while self.running.load(Ordering::Relaxed) {
+            thread::park();
+        }
+        Ok(())
+    }
+
+    fn post_msg_to_loop(&self, msg_type: u32) {
+        EventLoop::post_msg_to_loop(self, msg_type)
+    }
+
+    fn status(&self) -> HookStatus {
+        let running = self.running.load(Ordering::Relaxed);
+        HookStatus {
+            running,
+            keyboard: running,
+            mouse: running,
+            loop_thread_id: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct EventLoopManager {
+    event_loops: HashMap<ID, Arc<EventLoop>>,
+}
+
+impl EventLoopManager {
+    fn new() -> Self {
+        Self {
+            event_loops: HashMap::new(),
+        }
+    }
+
+    pub fn new_event_loop(&mut self, listener: &Arc<Listener>) -> Arc<EventLoop> {
+        let event_loop = Arc::new(EventLoop::new(listener));
+        self.event_loops.insert(event_loop.id, event_loop.clone());
+        event_loop
+    }
+
+    fn del_event_loop(&mut self, id: ID) {
+        self.event_loops.remove(&id);
+    }
+}
+
+lazy_static! {
+    pub(crate) static ref EVENT_LOOP_MANAGER: Mutex<EventLoopManager> =
+        Mutex::new(EventLoopManager::new());
+}
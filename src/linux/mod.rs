@@ -1 +1,100 @@
-// todo
\ No newline at end of file
+//! Linux support. The listener and worker are shared with the Windows
+//! implementation; only the event source differs.
+
+use crate::consts;
+use crate::types::{
+    Backend, KeyInfo, KeySnapshot, KeyState, MonitorInfo, Pos, ThreadPriority, VirtualKeyId,
+    WindowInfo,
+};
+use backend::InputBackend;
+use listener::Listener;
+use std::sync::Arc;
+
+#[path = "../windows/listener.rs"]
+pub mod listener;
+
+#[path = "../windows/worker.rs"]
+pub(crate) mod worker;
+
+#[path = "../windows/backend.rs"]
+pub(crate) mod backend;
+
+mod event_loop;
+
+/// The event loop for `backend`; only evdev exists on Linux.
+pub(crate) fn new_backend(
+    backend: Backend,
+    listener: &Arc<Listener>,
+) -> Option<Arc<dyn InputBackend>> {
+    match backend {
+        Backend::Evdev => Some(
+            event_loop::EVENT_LOOP_MANAGER
+                .lock()
+                .unwrap()
+                .new_event_loop(listener),
+        ),
+        _ => None,
+    }
+}
+
+// Loop message IDs used by the shared listener. The evdev loop has no message
+// queue, so these are accepted and ignored.
+pub(crate) const WM_USER_RECHECK_HOOK: u32 = 1;
+pub(crate) const WM_USER_APPLY_CURSOR_CLIP: u32 = 2;
+
+/// Applies `key_info` to `snapshot` and attaches the result to it. Returns
+/// `false` if nothing changed. Lock keys are tracked by counting presses since
+/// evdev does not report LED state with key events.
+pub(crate) fn update_key_snapshot(snapshot: &mut KeySnapshot, key_info: &mut KeyInfo) -> bool {
+    let key: VirtualKeyId = key_info.key_id.into();
+    let changed = if key_info.state == KeyState::Pressed {
+        let changed = snapshot.press(key);
+        if changed {
+            snapshot.locks.toggle(key);
+        }
+        changed
+    } else {
+        snapshot.release(key)
+    };
+    snapshot.timestamp = std::time::Instant::now();
+    key_info.keyboard_state.replace(snapshot.clone());
+    changed
+}
+
+/// Thread priority and CPU affinity are only applied on Windows.
+pub(crate) fn configure_thread(_priority: ThreadPriority, _cpu_affinity: Option<usize>) {}
+
+/// evdev reports key positions only; there is no layout to type through.
+pub(crate) fn key_text(_key_info: &KeyInfo) -> Option<String> {
+    None
+}
+
+/// Window information needs a display server; evdev has none.
+pub(crate) fn foreground_window() -> Option<WindowInfo> {
+    None
+}
+
+pub(crate) fn monitor_at(_pos: &Pos) -> Option<MonitorInfo> {
+    None
+}
+
+pub(crate) fn window_at(_pos: &Pos) -> Option<WindowInfo> {
+    None
+}
+
+pub(crate) fn is_foreground_fullscreen() -> bool {
+    false
+}
+
+/// Milliseconds between clicks that still count as a double click. evdev
+/// has no desktop settings to ask.
+pub(crate) fn double_click_interval() -> u32 {
+    consts::DEFAULT_DOUBLE_CLICK_INTERVAL
+}
+
+pub(crate) fn double_click_size() -> Pos {
+    Pos {
+        x: consts::DEFAULT_DOUBLE_CLICK_SIZE,
+        y: consts::DEFAULT_DOUBLE_CLICK_SIZE,
+    }
+}
@@ -0,0 +1,173 @@
+//! Record the input seen by a `Listener` and play it back.
+//!
+//! ```no_run
+//! use kmhook::recorder::Recorder;
+//! use kmhook::types::EventListener;
+//! use kmhook::Listener;
+//!
+//! let listener = Listener::new();
+//! let _handle = listener.startup(Some(true)).unwrap();
+//! let recorder = Recorder::start(&listener, None).unwrap();
+//! std::thread::sleep(std::time::Duration::from_secs(5));
+//! let recording = recorder.stop();
+//! # #[cfg(all(target_os = "windows", feature = "simulate"))]
+//! recording.play(1.0).unwrap();
+//! ```
+
+use crate::types::{ClickState, Event, EventFilter, KeyState, MouseKey, Pos};
+use crate::{Listener, Subscription};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// One recorded input. Keys are stored by name so recordings stay readable
+/// and independent of the keycode crate's representation.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordedInput {
+    Key {
+        key: String,
+        pressed: bool,
+    },
+    MouseMove {
+        pos: Pos,
+    },
+    MouseButton {
+        button: MouseKey,
+        pressed: bool,
+        pos: Pos,
+    },
+    /// `delta` as in `MouseInfo::wheel`.
+    Wheel {
+        delta: Pos,
+    },
+}
+
+impl RecordedInput {
+    fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::KeyboardEvent(key_info) => Some(Self::Key {
+                key: key_info.key_id.0.to_string(),
+                pressed: key_info.state == KeyState::Pressed,
+            }),
+            Event::MouseEvent(mouse_info) => Some(match &mouse_info.button {
+                Some(button) => Self::MouseButton {
+                    button: button.into(),
+                    pressed: button.state() == ClickState::Pressed,
+                    pos: mouse_info.pos.clone(),
+                },
+                None if mouse_info.wheel != Pos::default() => Self::Wheel {
+                    delta: mouse_info.wheel.clone(),
+                },
+                None => Self::MouseMove {
+                    pos: mouse_info.pos.clone(),
+                },
+            }),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedEvent {
+    /// Time since the recording started.
+    pub offset: Duration,
+    pub input: RecordedInput,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Recording {
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map_or(Duration::ZERO, |event| event.offset)
+    }
+
+    /// Replays the recording on the calling thread, blocking until done.
+    /// `speed` scales the timing: `2.0` plays twice as fast, `0.0` sends
+    /// everything without waiting.
+    #[cfg(all(target_os = "windows", feature = "simulate"))]
+    pub fn play(&self, speed: f32) -> Result<(), String> {
+        use crate::simulate;
+        use crate::types::{KeyId, VirtualKeyId};
+        use std::str::FromStr;
+
+        let start = Instant::now();
+        for event in self.events.iter() {
+            if speed > 0.0 {
+                let due = event.offset.div_f32(speed);
+                if let Some(wait) = due.checked_sub(start.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+            let state = |pressed| {
+                if pressed {
+                    KeyState::Pressed
+                } else {
+                    KeyState::Released
+                }
+            };
+            match &event.input {
+                RecordedInput::Key { key, pressed } => {
+                    let key =
+                        VirtualKeyId::from_str(key).map_err(|_| format!("Invalid key: {}", key))?;
+                    simulate::send_key(KeyId(key), state(*pressed))?;
+                }
+                RecordedInput::MouseMove { pos } => simulate::move_mouse(pos.clone())?,
+                RecordedInput::MouseButton {
+                    button,
+                    pressed,
+                    pos,
+                } => {
+                    simulate::move_mouse(pos.clone())?;
+                    if let Some(button) = button.with_state(state(*pressed)) {
+                        simulate::send_mouse_button(button)?;
+                    }
+                }
+                RecordedInput::Wheel { delta } => simulate::scroll_wheel(delta.clone())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Captures events from a running `Listener` until `stop` is called.
+pub struct Recorder {
+    subscription: Subscription,
+    events: Arc<Mutex<Vec<RecordedEvent>>>,
+}
+
+impl Recorder {
+    pub fn start(listener: &Arc<Listener>, filter: Option<EventFilter>) -> Result<Self, String> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let start = Instant::now();
+        let sink = events.clone();
+        let subscription = listener.add_event_listener_scoped(
+            move |event| {
+                if let Some(input) = RecordedInput::from_event(&event) {
+                    let offset = event
+                        .timestamp()
+                        .map_or_else(|| start.elapsed(), |at| at.saturating_duration_since(start));
+                    sink.lock().unwrap().push(RecordedEvent { offset, input });
+                }
+            },
+            filter,
+        )?;
+        Ok(Self {
+            subscription,
+            events,
+        })
+    }
+
+    pub fn stop(self) -> Recording {
+        drop(self.subscription);
+        let events = std::mem::take(&mut *self.events.lock().unwrap());
+        Recording { events }
+    }
+}
@@ -0,0 +1,216 @@
+//! Detects the cursor being shaken back and forth; see
+//! `Listener::add_shake_trigger`.
+
+use crate::consts;
+use crate::types::{Pos, ShakeOptions};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Swings of the cursor along one axis.
+#[derive(Debug, Default)]
+struct Axis {
+    /// Sign of the current swing; 0 before the first movement.
+    dir: i32,
+    /// Where the current swing started.
+    start: i32,
+    /// Farthest point reached in the current swing.
+    extreme: i32,
+}
+
+impl Axis {
+    fn reset(&mut self, at: i32) {
+        *self = Self {
+            dir: 0,
+            start: at,
+            extreme: at,
+        };
+    }
+
+    /// Takes the next coordinate and returns the length of the swing that
+    /// just ended, if the cursor turned.
+    fn feed(&mut self, at: i32) -> Option<i32> {
+        if self.dir == 0 {
+            if (at - self.start).abs() >= consts::SHAKE_TURN_SLACK {
+                self.dir = (at - self.start).signum();
+                self.extreme = at;
+            }
+            return None;
+        }
+        if (at - self.extreme) * self.dir > 0 {
+            self.extreme = at;
+            return None;
+        }
+        if (self.extreme - at) * self.dir < consts::SHAKE_TURN_SLACK {
+            return None;
+        }
+        let swing = (self.extreme - self.start).abs();
+        self.start = self.extreme;
+        self.extreme = at;
+        self.dir = -self.dir;
+        Some(swing)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ShakeDetector {
+    reversals: usize,
+    min_amplitude: i32,
+    within: Duration,
+    x: Axis,
+    y: Axis,
+    /// When recent wide enough turns happened.
+    turns: VecDeque<Instant>,
+    /// No shake is reported before this, so one shake fires once.
+    quiet_until: Option<Instant>,
+    started: bool,
+}
+
+impl ShakeDetector {
+    pub(crate) fn new(options: &ShakeOptions) -> Self {
+        Self {
+            reversals: options
+                .reversals
+                .unwrap_or(consts::DEFAULT_SHAKE_REVERSALS)
+                .max(1) as usize,
+            min_amplitude: options
+                .min_amplitude
+                .unwrap_or(consts::DEFAULT_SHAKE_AMPLITUDE),
+            within: options
+                .within
+                .unwrap_or(Duration::from_millis(consts::DEFAULT_SHAKE_WINDOW)),
+            x: Axis::default(),
+            y: Axis::default(),
+            turns: VecDeque::new(),
+            quiet_until: None,
+            started: false,
+        }
+    }
+
+    /// Takes the cursor position of a move and returns whether it completed
+    /// a shake.
+    pub(crate) fn feed(&mut self, pos: &Pos, at: Instant) -> bool {
+        if !self.started {
+            self.x.reset(pos.x);
+            self.y.reset(pos.y);
+            self.started = true;
+        }
+        let turned = [self.x.feed(pos.x), self.y.feed(pos.y)]
+            .into_iter()
+            .flatten()
+            .any(|swing| swing >= self.min_amplitude);
+        if !turned {
+            return false;
+        }
+        if matches!(self.quiet_until, Some(until) if at < until) {
+            return false;
+        }
+        self.turns.push_back(at);
+        while let Some(&first) = self.turns.front() {
+            if at.saturating_duration_since(first) <= self.within {
+                break;
+            }
+            self.turns.pop_front();
+        }
+        if self.turns.len() < self.reversals {
+            return false;
+        }
+        self.turns.clear();
+        self.quiet_until = Some(at + self.within);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> ShakeDetector {
+        ShakeDetector::new(&ShakeOptions {
+            reversals: Some(3),
+            min_amplitude: Some(50),
+            within: Some(Duration::from_millis(500)),
+        })
+    }
+
+    /// Feeds horizontal moves as (x, ms since `base`) and returns which of
+    /// them completed a shake.
+    fn feed(detector: &mut ShakeDetector, base: Instant, moves: &[(i32, u64)]) -> Vec<bool> {
+        moves
+            .iter()
+            .map(|&(x, ms)| detector.feed(&Pos { x, y: 0 }, base + Duration::from_millis(ms)))
+            .collect()
+    }
+
+    #[test]
+    fn test_axis_turns() {
+        let mut axis = Axis::default();
+        axis.reset(0);
+        assert_eq!(axis.feed(5), None);
+        assert_eq!(axis.feed(100), None);
+        assert_eq!(axis.feed(120), None);
+        // Backing off by less than the slack is not a turn.
+        assert_eq!(axis.feed(115), None);
+        assert_eq!(axis.feed(0), Some(120));
+        assert_eq!(axis.feed(-30), None);
+        assert_eq!(axis.feed(40), Some(150));
+    }
+
+    #[test]
+    fn test_shake_reversals() {
+        let base = Instant::now();
+        let mut shake = detector();
+        let fired = feed(
+            &mut shake,
+            base,
+            &[(0, 0), (100, 50), (0, 100), (100, 150), (0, 200)],
+        );
+        assert_eq!(fired, [false, false, false, false, true]);
+    }
+
+    #[test]
+    fn test_shake_below_amplitude() {
+        let base = Instant::now();
+        let mut shake = detector();
+        let moves: Vec<(i32, u64)> = (0..20).map(|i| ((i % 2) * 30, i as u64 * 20)).collect();
+        assert!(!feed(&mut shake, base, &moves).contains(&true));
+    }
+
+    #[test]
+    fn test_shake_window_expires() {
+        let base = Instant::now();
+        let mut shake = detector();
+        let fired = feed(
+            &mut shake,
+            base,
+            &[(0, 0), (100, 300), (0, 600), (100, 900), (0, 1200)],
+        );
+        assert!(!fired.contains(&true));
+    }
+
+    #[test]
+    fn test_shake_quiet_after_firing() {
+        let base = Instant::now();
+        let mut shake = detector();
+        let fired = feed(
+            &mut shake,
+            base,
+            &[
+                (0, 0),
+                (100, 50),
+                (0, 100),
+                (100, 150),
+                (0, 200),
+                // Turns within `within` of the shake are ignored...
+                (100, 250),
+                (0, 300),
+                (100, 350),
+                // ...and a new shake needs all its turns afterwards.
+                (0, 800),
+                (100, 850),
+                (0, 900),
+            ],
+        );
+        let shakes: Vec<usize> = (0..fired.len()).filter(|&i| fired[i]).collect();
+        assert_eq!(shakes, [4, 10]);
+    }
+}
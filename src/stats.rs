@@ -0,0 +1,232 @@
+//! Count keystrokes, clicks, mouse travel and scrolling seen by a `Listener`.
+//!
+//! ```no_run
+//! use kmhook::stats::UsageStats;
+//! use kmhook::types::EventListener;
+//! use kmhook::Listener;
+//!
+//! let listener = Listener::new();
+//! let _handle = listener.startup(Some(true)).unwrap();
+//! let stats = UsageStats::start(&listener).unwrap();
+//! std::thread::sleep(std::time::Duration::from_secs(60));
+//! println!("{:.0} actions per minute", stats.snapshot().actions_per_minute);
+//! ```
+
+use crate::types::{ClickState, Event, EventFilter, KeyId, KeyState, MouseKey};
+use crate::{Listener, Subscription};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Span `Usage::actions_per_minute` is measured over.
+const APM_WINDOW: Duration = Duration::from_secs(60);
+
+/// Totals since `UsageStats::start` or the last `reset`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Usage {
+    /// Presses per key; auto-repeats are not counted.
+    pub keys: HashMap<KeyId, u64>,
+    /// Presses per mouse button.
+    pub buttons: HashMap<MouseKey, u64>,
+    /// Cursor travel in pixels.
+    pub distance: f64,
+    /// Wheel rotation in both directions and on both axes, in units of 120
+    /// per notch as in `MouseInfo::wheel`.
+    pub scrolled: u64,
+    /// Key and button presses in the last minute.
+    pub actions_per_minute: f64,
+    /// When counting started.
+    pub since: SystemTime,
+}
+
+impl Usage {
+    pub fn key_presses(&self) -> u64 {
+        self.keys.values().sum()
+    }
+
+    pub fn clicks(&self) -> u64 {
+        self.buttons.values().sum()
+    }
+}
+
+#[derive(Debug)]
+struct Tally {
+    keys: HashMap<KeyId, u64>,
+    buttons: HashMap<MouseKey, u64>,
+    distance: f64,
+    scrolled: u64,
+    /// Presses within `APM_WINDOW`.
+    actions: VecDeque<Instant>,
+    started: Instant,
+    since: SystemTime,
+}
+
+impl Tally {
+    fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+            buttons: HashMap::new(),
+            distance: 0.0,
+            scrolled: 0,
+            actions: VecDeque::new(),
+            started: Instant::now(),
+            since: SystemTime::now(),
+        }
+    }
+
+    fn add(&mut self, event: &Event) {
+        let at = match event {
+            Event::KeyboardEvent(key_info) => {
+                if key_info.state != KeyState::Pressed || key_info.is_repeat {
+                    return;
+                }
+                *self.keys.entry(key_info.key_id).or_default() += 1;
+                key_info.timestamp
+            }
+            Event::MouseEvent(mouse_info) => {
+                let delta = &mouse_info.relative_pos;
+                self.distance += (delta.x as f64).hypot(delta.y as f64);
+                self.scrolled += mouse_info.wheel.x.unsigned_abs() as u64
+                    + mouse_info.wheel.y.unsigned_abs() as u64;
+                match &mouse_info.button {
+                    Some(button) if button.state() == ClickState::Pressed => {
+                        *self.buttons.entry(button.into()).or_default() += 1;
+                    }
+                    _ => return,
+                }
+                mouse_info.timestamp
+            }
+            _ => return,
+        };
+        self.actions.push_back(at);
+        self.prune(at);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(&first) = self.actions.front() {
+            if now.saturating_duration_since(first) <= APM_WINDOW {
+                break;
+            }
+            self.actions.pop_front();
+        }
+    }
+
+    fn usage(&mut self) -> Usage {
+        self.usage_at(Instant::now())
+    }
+
+    fn usage_at(&mut self, now: Instant) -> Usage {
+        self.prune(now);
+        // Until a full minute has passed, extrapolate from the time so far.
+        let span = now
+            .saturating_duration_since(self.started)
+            .clamp(Duration::from_secs(1), APM_WINDOW);
+        Usage {
+            keys: self.keys.clone(),
+            buttons: self.buttons.clone(),
+            distance: self.distance,
+            scrolled: self.scrolled,
+            actions_per_minute: self.actions.len() as f64 * 60.0 / span.as_secs_f64(),
+            since: self.since,
+        }
+    }
+}
+
+/// Counts the input of a running `Listener` until dropped or stopped.
+pub struct UsageStats {
+    subscription: Subscription,
+    tally: Arc<Mutex<Tally>>,
+}
+
+impl UsageStats {
+    pub fn start(listener: &Arc<Listener>) -> Result<Self, String> {
+        let tally = Arc::new(Mutex::new(Tally::new()));
+        let sink = tally.clone();
+        let subscription = listener.add_event_listener_scoped(
+            move |event| sink.lock().unwrap().add(&event),
+            Some(EventFilter::AnyOf(vec![
+                EventFilter::Keyboard,
+                EventFilter::Mouse,
+            ])),
+        )?;
+        Ok(Self {
+            subscription,
+            tally,
+        })
+    }
+
+    pub fn snapshot(&self) -> Usage {
+        self.tally.lock().unwrap().usage()
+    }
+
+    /// Starts counting again from zero.
+    pub fn reset(&self) {
+        *self.tally.lock().unwrap() = Tally::new();
+    }
+
+    pub fn stop(self) -> Usage {
+        drop(self.subscription);
+        let usage = self.tally.lock().unwrap().usage();
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{KeyInfo, MouseButton, MouseInfo, Pos, VirtualKeyId};
+
+    fn key(id: VirtualKeyId, state: KeyState, at: Instant, is_repeat: bool) -> Event {
+        let mut key_info = KeyInfo::new(KeyId::from(id), state);
+        key_info.timestamp = at;
+        key_info.is_repeat = is_repeat;
+        Event::KeyboardEvent(key_info)
+    }
+
+    #[test]
+    fn test_tally() {
+        let base = Instant::now();
+        let secs = |s| base + Duration::from_secs(s);
+        let mut tally = Tally::new();
+        tally.started = base;
+
+        tally.add(&key(VirtualKeyId::UsA, KeyState::Pressed, secs(0), false));
+        tally.add(&key(VirtualKeyId::UsA, KeyState::Pressed, secs(1), true));
+        tally.add(&key(VirtualKeyId::UsA, KeyState::Released, secs(1), false));
+        tally.add(&key(VirtualKeyId::UsB, KeyState::Pressed, secs(2), false));
+        tally.add(&key(VirtualKeyId::UsA, KeyState::Pressed, secs(3), false));
+        tally.add(&Event::MouseEvent(MouseInfo {
+            relative_pos: Pos { x: 3, y: 4 },
+            wheel: Pos { x: 0, y: -120 },
+            timestamp: secs(3),
+            ..Default::default()
+        }));
+        tally.add(&Event::MouseEvent(MouseInfo {
+            button: Some(MouseButton::Left(ClickState::Pressed)),
+            timestamp: secs(4),
+            ..Default::default()
+        }));
+        tally.add(&Event::MouseEvent(MouseInfo {
+            button: Some(MouseButton::Left(ClickState::Released)),
+            timestamp: secs(4),
+            ..Default::default()
+        }));
+
+        let usage = tally.usage_at(secs(30));
+        assert_eq!(usage.keys[&KeyId::from(VirtualKeyId::UsA)], 2);
+        assert_eq!(usage.keys[&KeyId::from(VirtualKeyId::UsB)], 1);
+        assert_eq!(usage.key_presses(), 3);
+        assert_eq!(usage.buttons[&MouseKey::Left], 1);
+        assert_eq!(usage.clicks(), 1);
+        assert_eq!(usage.distance, 5.0);
+        assert_eq!(usage.scrolled, 120);
+        // 4 presses in the first 30 seconds.
+        assert_eq!(usage.actions_per_minute, 8.0);
+
+        // The presses at 0 and 2 seconds have left the window.
+        let usage = tally.usage_at(secs(63));
+        assert_eq!(usage.actions_per_minute, 2.0);
+        assert_eq!(usage.key_presses(), 3);
+    }
+}
@@ -0,0 +1,176 @@
+//! Events and shortcuts as `futures_core::Stream`s, for async applications;
+//! see `Listener::event_stream` and `Listener::shortcut_stream`.
+//!
+//! ```no_run
+//! # async fn run() {
+//! use futures::StreamExt;
+//! use kmhook::types::EventListener;
+//! use kmhook::Listener;
+//!
+//! let listener = Listener::new();
+//! let _handle = listener.startup(Some(true)).unwrap();
+//! let mut presses = listener.shortcut_stream("Ctrl+Alt+T").unwrap();
+//! while let Some(fired) = presses.next().await {
+//!     println!("{} fired", fired.shortcut);
+//! }
+//! # }
+//! ```
+
+use crate::types::{Event, ShortcutEvent};
+use crate::Subscription;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug)]
+struct Queue<T> {
+    items: VecDeque<T>,
+    /// Items kept while the stream is not polled; newer ones are dropped.
+    capacity: usize,
+    waker: Option<Waker>,
+    /// The callback feeding the queue was unregistered.
+    closed: bool,
+}
+
+/// Feeds a stream from a listener callback.
+#[derive(Debug)]
+pub(crate) struct Sender<T>(Arc<Mutex<Queue<T>>>);
+
+impl<T> Sender<T> {
+    /// Queues `item`, or drops it and returns false if the queue is full.
+    pub(crate) fn send(&self, item: T) -> bool {
+        let mut queue = self.0.lock().unwrap();
+        if queue.items.len() >= queue.capacity {
+            return false;
+        }
+        queue.items.push_back(item);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut queue = self.0.lock().unwrap();
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Receiver<T>(Arc<Mutex<Queue<T>>>);
+
+impl<T> Receiver<T> {
+    fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queue = self.0.lock().unwrap();
+        if let Some(item) = queue.items.pop_front() {
+            return Poll::Ready(Some(item));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let queue = Arc::new(Mutex::new(Queue {
+        items: VecDeque::new(),
+        capacity,
+        waker: None,
+        closed: false,
+    }));
+    (Sender(queue.clone()), Receiver(queue))
+}
+
+/// Events from `Listener::event_stream`. Unregisters when dropped and ends
+/// when the listener is dropped.
+#[derive(Debug)]
+pub struct EventStream {
+    rx: Receiver<Event>,
+    subscription: Subscription,
+}
+
+impl EventStream {
+    /// Registers through `register`, which is handed the sender to call from
+    /// the callback. Up to `capacity` events wait for the stream to be
+    /// polled.
+    pub(crate) fn new<R>(register: R, capacity: usize) -> Result<Self, String>
+    where
+        R: FnOnce(Sender<Event>) -> Result<Subscription, String>,
+    {
+        let (tx, rx) = channel(capacity);
+        Ok(Self {
+            rx,
+            subscription: register(tx)?,
+        })
+    }
+
+    pub fn id(&self) -> crate::types::ID {
+        self.subscription.id()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.rx.poll_next(cx)
+    }
+}
+
+/// Firings of the shortcut of `Listener::shortcut_stream`. Unregisters when
+/// dropped and ends when the listener is dropped.
+#[derive(Debug)]
+pub struct ShortcutStream {
+    rx: Receiver<ShortcutEvent>,
+    subscription: Subscription,
+}
+
+impl ShortcutStream {
+    pub(crate) fn new<R>(register: R, capacity: usize) -> Result<Self, String>
+    where
+        R: FnOnce(Sender<ShortcutEvent>) -> Result<Subscription, String>,
+    {
+        let (tx, rx) = channel(capacity);
+        Ok(Self {
+            rx,
+            subscription: register(tx)?,
+        })
+    }
+
+    pub fn id(&self) -> crate::types::ID {
+        self.subscription.id()
+    }
+}
+
+impl Stream for ShortcutStream {
+    type Item = ShortcutEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ShortcutEvent>> {
+        self.rx.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity() {
+        let (tx, rx) = channel(2);
+        assert!(tx.send(1));
+        assert!(tx.send(2));
+        assert!(!tx.send(3));
+        assert_eq!(rx.0.lock().unwrap().items, [1, 2]);
+        rx.0.lock().unwrap().items.pop_front();
+        assert!(tx.send(4));
+    }
+}
@@ -1,6 +1,8 @@
 #![allow(unused)]
 use bitflags::bitflags;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
 use std::{sync::Arc, thread::JoinHandle};
 
 pub use keycode::VirtualKeyId;
@@ -8,6 +10,9 @@ pub use keycode::{KeyMap, KeyMappingId, KeyState, KeyboardState};
 
 pub type ID = usize;
 
+/// Identifies a group of shortcuts created with `Listener::create_group`.
+pub type GroupId = ID;
+
 pub type ClickState = KeyState;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
@@ -17,6 +22,17 @@ impl KeyId {
     pub fn is_modifier(&self) -> bool {
         self.0.modifier().is_some()
     }
+
+    /// A name for display in the language of the keyboard layout of the
+    /// calling thread, e.g. "Strg" for Ctrl on a German layout. Falls back to
+    /// the layout-independent name, as in `Shortcut::canonical_string`.
+    pub fn display_name(&self) -> String {
+        #[cfg(target_os = "windows")]
+        if let Some(name) = crate::windows::key_name_text(self.0) {
+            return name;
+        }
+        Shortcut::canonical_key_name(&self.0)
+    }
 }
 
 impl From<VirtualKeyId> for KeyId {
@@ -25,6 +41,30 @@ impl From<VirtualKeyId> for KeyId {
     }
 }
 
+/// Parses one key name as written in shortcuts, e.g. "A" or "Enter".
+impl std::str::FromStr for KeyId {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        Shortcut::normalize_key(key).map(Self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for KeyId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&Shortcut::canonical_key_name(&self.0))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KeyId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Into<VirtualKeyId> for KeyId {
     fn into(self) -> VirtualKeyId {
         self.0
@@ -52,13 +92,186 @@ pub enum MouseButton {
     X2(ClickState),
 }
 
+impl MouseButton {
+    pub fn state(&self) -> ClickState {
+        match self {
+            Self::Left(state)
+            | Self::Right(state)
+            | Self::Middle(state)
+            | Self::X1(state)
+            | Self::X2(state) => *state,
+        }
+    }
+}
+
+bitflags! {
+    /// Modifier keys held down, one bit per physical side.
+    #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Modifiers: u8 {
+        const CONTROL_LEFT = 1 << 0;
+        const SHIFT_LEFT = 1 << 1;
+        const ALT_LEFT = 1 << 2;
+        const META_LEFT = 1 << 3;
+        const CONTROL_RIGHT = 1 << 4;
+        const SHIFT_RIGHT = 1 << 5;
+        const ALT_RIGHT = 1 << 6;
+        const META_RIGHT = 1 << 7;
+
+        const CONTROL = Self::CONTROL_LEFT.bits() | Self::CONTROL_RIGHT.bits();
+        const SHIFT = Self::SHIFT_LEFT.bits() | Self::SHIFT_RIGHT.bits();
+        const ALT = Self::ALT_LEFT.bits() | Self::ALT_RIGHT.bits();
+        const META = Self::META_LEFT.bits() | Self::META_RIGHT.bits();
+    }
+}
+
+impl Modifiers {
+    pub fn from_key(key: VirtualKeyId) -> Self {
+        match key {
+            VirtualKeyId::ControlLeft => Self::CONTROL_LEFT,
+            VirtualKeyId::ControlRight => Self::CONTROL_RIGHT,
+            VirtualKeyId::Control => Self::CONTROL,
+            VirtualKeyId::ShiftLeft => Self::SHIFT_LEFT,
+            VirtualKeyId::ShiftRight => Self::SHIFT_RIGHT,
+            VirtualKeyId::Shift => Self::SHIFT,
+            VirtualKeyId::AltLeft => Self::ALT_LEFT,
+            VirtualKeyId::AltRight => Self::ALT_RIGHT,
+            VirtualKeyId::Alt => Self::ALT,
+            VirtualKeyId::MetaLeft => Self::META_LEFT,
+            VirtualKeyId::MetaRight => Self::META_RIGHT,
+            VirtualKeyId::Meta => Self::META,
+            _ => Self::empty(),
+        }
+    }
+}
+
+/// Toggle state of the lock keys.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LockKeyState {
+    pub caps: bool,
+    pub num: bool,
+    pub scroll: bool,
+}
+
+impl LockKeyState {
+    /// Flips the lock toggled by `key`, if any.
+    pub fn toggle(&mut self, key: VirtualKeyId) {
+        match key {
+            VirtualKeyId::CapsLock => self.caps = !self.caps,
+            VirtualKeyId::NumLock => self.num = !self.num,
+            VirtualKeyId::ScrollLock => self.scroll = !self.scroll,
+            _ => {}
+        }
+    }
+}
+
+/// The set of keys held down at the moment a keyboard event was captured.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct KeySnapshot {
+    /// Pressed keys, in the order they went down.
+    pub keys: Vec<VirtualKeyId>,
+    pub modifiers: Modifiers,
+    pub locks: LockKeyState,
+    /// AltGr is held. The left Ctrl press Windows sends along with it is
+    /// left out of `keys` and `modifiers`, so "AltGr+Q" is not seen as
+    /// "Ctrl+AltRight+Q".
+    pub alt_gr: bool,
+    pub timestamp: Instant,
+}
+
+impl Default for KeySnapshot {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            modifiers: Modifiers::empty(),
+            locks: LockKeyState::default(),
+            alt_gr: false,
+            timestamp: Instant::now(),
+        }
+    }
+}
+
+impl KeySnapshot {
+    /// Returns `true` if the key was not already held.
+    pub fn press(&mut self, key: VirtualKeyId) -> bool {
+        if self.keys.contains(&key) {
+            return false;
+        }
+        self.keys.push(key);
+        self.modifiers |= Modifiers::from_key(key);
+        true
+    }
+
+    /// Returns `true` if the key was held.
+    pub fn release(&mut self, key: VirtualKeyId) -> bool {
+        let len = self.keys.len();
+        self.keys.retain(|&k| k != key);
+        self.modifiers.remove(Modifiers::from_key(key));
+        self.keys.len() != len
+    }
+
+    pub fn is_pressed(&self, key: VirtualKeyId) -> bool {
+        self.keys.contains(&key)
+    }
+
+    pub fn to_shortcut(&self) -> Shortcut {
+        let mut shortcut = Shortcut::default();
+        for key in self.keys.iter() {
+            shortcut.set_key(*key);
+        }
+        shortcut
+    }
+}
+
+impl From<&KeySnapshot> for Shortcut {
+    fn from(snapshot: &KeySnapshot) -> Self {
+        snapshot.to_shortcut()
+    }
+}
+
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct KeyInfo {
     pub key_id: KeyId,
     pub state: KeyState,
+    /// Scan code as reported by the system, without the E0/E1 prefix on
+    /// Windows (see `is_extended`); the evdev key code on Linux.
+    pub scancode: u32,
+    /// Windows virtual-key code as reported by the system. Zero on Linux.
+    pub vk: u32,
+    /// The key sends an E0-prefixed scan code, e.g. right Ctrl or the
+    /// arrow keys outside the numpad.
+    pub is_extended: bool,
 
     /// All keys state
-    pub keyboard_state: Option<Shortcut>,
+    pub keyboard_state: Option<KeySnapshot>,
+    /// Synthesized by `SendInput` or similar rather than a physical keyboard.
+    pub injected: bool,
+    /// The keyboard that sent the key. Raw-input backend only; `None` for
+    /// injected input.
+    pub device: Option<DeviceId>,
+    /// An auto-repeated press of a key that is already held. Only delivered
+    /// when enabled with `ListenerBuilder::key_repeats`.
+    pub is_repeat: bool,
+    /// How long the key was held, on releases of presses seen by the listener.
+    pub held_for: Option<Duration>,
+    /// Text a press types under the foreground window's keyboard layout,
+    /// e.g. "A" for Shift+A. `None` for releases, dead keys and keys that
+    /// type nothing printable. Not available on Linux.
+    pub text: Option<String>,
+    /// The foreground window, if enabled with `ListenerBuilder::window_info`.
+    pub window: Option<WindowInfo>,
+    /// When the event was captured.
+    pub timestamp: Instant,
+    /// Wall-clock time of the capture, for logs and recordings.
+    pub system_time: SystemTime,
+    /// The system's own time stamp for the event in ms: the message time
+    /// (`GetTickCount` based) on Windows, the evdev time truncated to `u32`
+    /// on Linux.
+    pub time: u32,
+    /// Position in the listener's event stream, counting from 1. Gaps mean
+    /// events were dropped or merged, e.g. by `max_mouse_moves`.
+    pub seq: u64,
 }
 
 impl KeyInfo {
@@ -66,29 +279,686 @@ impl KeyInfo {
         Self {
             key_id,
             state,
+            scancode: 0,
+            vk: 0,
+            is_extended: false,
             keyboard_state: None,
+            injected: false,
+            device: None,
+            is_repeat: false,
+            held_for: None,
+            text: None,
+            window: None,
+            timestamp: Instant::now(),
+            system_time: SystemTime::now(),
+            time: 0,
+            seq: 0,
         }
     }
+
+    /// The pressed keys as a `Shortcut`, for code written against the old
+    /// `keyboard_state: Option<Shortcut>` field.
+    pub fn shortcut(&self) -> Option<Shortcut> {
+        self.keyboard_state.as_ref().map(Shortcut::from)
+    }
+
+    /// Caps/Num/Scroll Lock as they are once this event has been processed,
+    /// captured together with the event.
+    pub fn locks(&self) -> LockKeyState {
+        self.keyboard_state
+            .as_ref()
+            .map_or(LockKeyState::default(), |snapshot| snapshot.locks)
+    }
 }
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pos {
     pub x: i32,
     pub y: i32,
 }
 
+bitflags! {
+    /// Extra state attached to a mouse event. Every backend fills in the bits it
+    /// can detect and leaves the rest clear.
+    #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct MouseStateFlags: u32 {
+        const PRESSED = 1 << 0;
+        const RELEASED = 1 << 1;
+        /// A button event with `MouseInfo::click_count` of 2 or more, so
+        /// triple and later clicks of a run too.
+        const DOUBLE = 1 << 2;
+        /// Synthesized by `SendInput` or similar rather than a physical device.
+        const INJECTED = 1 << 3;
+        /// Promoted from touch input.
+        const FROM_TOUCH = 1 << 4;
+        /// Promoted from pen input.
+        const FROM_PEN = 1 << 5;
+    }
+}
+
+/// A screen rectangle in physical pixels; `right`/`bottom` are exclusive.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn contains(&self, pos: &Pos) -> bool {
+        pos.x >= self.left && pos.x < self.right && pos.y >= self.top && pos.y < self.bottom
+    }
+}
+
+/// A display, in physical pixels of the virtual desktop.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MonitorInfo {
+    /// Position in the system's monitor enumeration; not necessarily 0 for
+    /// the primary monitor.
+    pub index: usize,
+    pub bounds: Rect,
+    /// Effective DPI; 96 is 100% scaling.
+    pub dpi: u32,
+}
+
+impl MonitorInfo {
+    /// A distance or speed on this monitor in logical pixels.
+    pub(crate) fn logical_length(&self, v: i32) -> i32 {
+        v * 96 / self.dpi.max(1) as i32
+    }
+
+    /// A point on this monitor in logical pixels: its offset from the
+    /// monitor's top-left corner is scaled, the corner stays where it is.
+    pub(crate) fn logical_point(&self, pos: &Pos) -> Pos {
+        Pos {
+            x: self.bounds.left + self.logical_length(pos.x - self.bounds.left),
+            y: self.bounds.top + self.logical_length(pos.y - self.bounds.top),
+        }
+    }
+
+    /// The monitor's area in the logical pixels of `logical_point`.
+    pub(crate) fn logical_bounds(&self) -> Rect {
+        let bottom_right = self.logical_point(&Pos {
+            x: self.bounds.right,
+            y: self.bounds.bottom,
+        });
+        Rect::new(
+            self.bounds.left,
+            self.bounds.top,
+            bottom_right.x,
+            bottom_right.y,
+        )
+    }
+}
+
+/// Units of `MouseInfo::pos` and `MouseInfo::relative_pos`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CoordinateSpace {
+    /// Physical pixels, independent of display scaling.
+    #[default]
+    Physical,
+    /// Physical pixels divided by the scale factor of the monitor the cursor
+    /// is on (`dpi / 96`), as used by GUI toolkits, measured from that
+    /// monitor's top-left corner, which keeps its physical position. Monitor
+    /// bounds stay physical.
+    Logical,
+}
+
+/// A corner of a monitor; see `Listener::add_hot_corner`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    /// The `size` by `size` square in this corner of `bounds`.
+    pub fn area(&self, bounds: &Rect, size: i32) -> Rect {
+        let (left, top) = match self {
+            Self::TopLeft => (bounds.left, bounds.top),
+            Self::TopRight => (bounds.right - size, bounds.top),
+            Self::BottomLeft => (bounds.left, bounds.bottom - size),
+            Self::BottomRight => (bounds.right - size, bounds.bottom - size),
+        };
+        Rect::new(left, top, left + size, top + size)
+    }
+}
+
+/// A top-level window and the process that owns it.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowInfo {
+    /// The `HWND` value.
+    pub hwnd: isize,
+    pub title: String,
+    pub class: String,
+    pub process_id: u32,
+    /// Executable file name such as "notepad.exe"; empty if the process
+    /// could not be opened.
+    pub process_name: String,
+}
+
+/// An input device, valid while it stays connected: its raw input device
+/// handle (`RAWINPUTHEADER.hDevice`) on Windows.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceId(pub isize);
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceKind {
+    Keyboard,
+    Mouse,
+    /// Any other HID device, e.g. a gamepad.
+    Hid,
+}
+
+/// A keyboard layout, as returned by `kmhook::current_layout()`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayoutInfo {
+    /// The `HKL`.
+    pub id: isize,
+    /// Language identifier of the input language, e.g. 0x0407 for German.
+    pub language: u16,
+    /// The language as a locale name such as "de-DE".
+    pub locale: Option<String>,
+}
+
+/// A connected input device, as listed by `kmhook::devices()`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    pub id: DeviceId,
+    pub kind: DeviceKind,
+    /// Device interface path such as `\\?\HID#VID_046D&PID_C52B&MI_00#...`.
+    /// Unlike `id` it stays the same when the device is reconnected.
+    pub name: String,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+}
+
+/// A value field of a HID report, such as an axis or hat switch.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HidValue {
+    pub usage_page: u16,
+    /// E.g. 0x30 for X or 0x39 for a hat switch on the generic desktop page.
+    pub usage: u16,
+    /// In the device's logical range.
+    pub value: u32,
+}
+
+/// A report from a game controller or other HID device.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct HidInfo {
+    pub device: DeviceId,
+    /// Top-level collection of the device, e.g. 0x01/0x05 for a gamepad.
+    pub usage_page: u16,
+    pub usage: u16,
+    /// Buttons held, numbered from 1 as on the HID button page.
+    pub buttons: Vec<u16>,
+    pub values: Vec<HidValue>,
+    /// The unparsed input reports; when several arrived at once, `buttons`
+    /// and `values` describe the last.
+    pub report: Vec<u8>,
+    /// When the report was captured.
+    pub timestamp: Instant,
+    /// Wall-clock time of the capture.
+    pub system_time: SystemTime,
+    /// As in `KeyInfo::time`.
+    pub time: u32,
+}
+
+/// Restricts a shortcut to a foreground application.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AppContext {
+    /// Executable file name, compared case-insensitively, e.g. "code.exe".
+    Process(String),
+    /// Window class name, e.g. "Notepad".
+    WindowClass(String),
+    /// Text contained in the window title.
+    TitleContains(String),
+}
+
+impl AppContext {
+    pub fn matches(&self, window: &WindowInfo) -> bool {
+        match self {
+            Self::Process(name) => window.process_name.eq_ignore_ascii_case(name),
+            Self::WindowClass(class) => window.class == *class,
+            Self::TitleContains(text) => window.title.contains(text.as_str()),
+        }
+    }
+}
+
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct MouseInfo {
     pub button: Option<MouseButton>,
     pub pos: Pos,
     pub relative_pos: Pos,
+    /// Movement as reported by the mouse sensor, before pointer acceleration
+    /// and unaffected by the cursor being clipped, hidden or at a screen
+    /// edge. Raw-input and evdev backends only; zero with `ll-hook` and for
+    /// absolute devices such as tablets.
+    pub raw_delta: Pos,
+    /// Wheel rotation in units of 120 per notch; `y > 0` scrolls up (away
+    /// from the user) and `x > 0` to the right. Zero for non-wheel events.
+    pub wheel: Pos,
+    pub flags: MouseStateFlags,
+    /// The mouse the event came from, as in `KeyInfo::device`.
+    pub device: Option<DeviceId>,
+    /// The foreground window, if enabled with `ListenerBuilder::window_info`.
+    pub window: Option<WindowInfo>,
+    /// The top-level window under the cursor for button and wheel events, if
+    /// enabled with `ListenerBuilder::cursor_window_info`.
+    pub target: Option<WindowInfo>,
+    /// The monitor `pos` is on. Not available on Linux.
+    pub monitor: Option<MonitorInfo>,
+    /// For button events, 1 for a single click, 2 for a double click, 3 for
+    /// a triple click and so on, by the system's double-click time and
+    /// distance. A release carries the count of its press. 0 otherwise.
+    pub click_count: u32,
+    /// Cursor velocity of move events in pixels per second, averaged over
+    /// `ListenerBuilder::velocity_window`. Zero for other events.
+    pub velocity: Pos,
+    /// Change of `velocity` since the previous move, in pixels per second
+    /// squared.
+    pub acceleration: Pos,
+    /// When the event was captured.
+    pub timestamp: Instant,
+    /// Wall-clock time of the capture, for logs and recordings.
+    pub system_time: SystemTime,
+    /// The system's own time stamp for the event in ms, as in `KeyInfo::time`.
+    pub time: u32,
+    /// As in `KeyInfo::seq`; a merged move keeps the last one's number.
+    pub seq: u64,
 }
 
+impl Default for MouseInfo {
+    fn default() -> Self {
+        Self {
+            button: None,
+            pos: Pos::default(),
+            relative_pos: Pos::default(),
+            raw_delta: Pos::default(),
+            wheel: Pos::default(),
+            flags: MouseStateFlags::empty(),
+            device: None,
+            window: None,
+            target: None,
+            monitor: None,
+            click_count: 0,
+            velocity: Pos::default(),
+            acceleration: Pos::default(),
+            timestamp: Instant::now(),
+            system_time: SystemTime::now(),
+            time: 0,
+            seq: 0,
+        }
+    }
+}
+
+impl MouseInfo {
+    /// A plain move: no button changed and the wheel did not turn.
+    pub fn is_move(&self) -> bool {
+        self.button.is_none() && self.wheel == Pos::default()
+    }
+
+    /// Length of `velocity`, in pixels per second.
+    pub fn speed(&self) -> f64 {
+        (self.velocity.x as f64).hypot(self.velocity.y as f64)
+    }
+
+    /// Direction of `velocity` in radians: 0 to the right, π/2 downwards.
+    pub fn direction(&self) -> f64 {
+        (self.velocity.y as f64).atan2(self.velocity.x as f64)
+    }
+
+    /// Folds a later move into this one: position and details from `later`,
+    /// deltas summed.
+    pub(crate) fn absorb(&mut self, later: MouseInfo) {
+        let relative_pos = Pos {
+            x: self.relative_pos.x + later.relative_pos.x,
+            y: self.relative_pos.y + later.relative_pos.y,
+        };
+        let raw_delta = Pos {
+            x: self.raw_delta.x + later.raw_delta.x,
+            y: self.raw_delta.y + later.raw_delta.y,
+        };
+        *self = MouseInfo {
+            relative_pos,
+            raw_delta,
+            ..later
+        };
+    }
+}
+
+/// A mouse button or wheel direction that can be part of a `Shortcut`, e.g.
+/// "Ctrl+MouseLeft" or "Shift+WheelUp".
+#[derive(Debug, Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MouseKey {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl MouseKey {
+    /// In the order of `keyname::MOUSE_KEY_NAMES`.
+    const ALL: [MouseKey; 9] = [
+        MouseKey::Left,
+        MouseKey::Right,
+        MouseKey::Middle,
+        MouseKey::X1,
+        MouseKey::X2,
+        MouseKey::WheelUp,
+        MouseKey::WheelDown,
+        MouseKey::WheelLeft,
+        MouseKey::WheelRight,
+    ];
+
+    /// The name used in shortcut strings.
+    pub fn name(&self) -> &'static str {
+        let i = Self::ALL.iter().position(|key| key == self).unwrap();
+        crate::keyname::MOUSE_KEY_NAMES[i]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        crate::keyname::mouse_key_index(name).map(|i| Self::ALL[i])
+    }
+
+    /// Wheel directions are momentary and never held down.
+    pub fn is_wheel(&self) -> bool {
+        matches!(
+            self,
+            Self::WheelUp | Self::WheelDown | Self::WheelLeft | Self::WheelRight
+        )
+    }
+
+    /// The wheel direction of a `MouseInfo::wheel` delta, if any.
+    pub fn from_wheel(wheel: &Pos) -> Option<Self> {
+        match (wheel.x.signum(), wheel.y.signum()) {
+            (_, 1) => Some(Self::WheelUp),
+            (_, -1) => Some(Self::WheelDown),
+            (1, _) => Some(Self::WheelRight),
+            (-1, _) => Some(Self::WheelLeft),
+            _ => None,
+        }
+    }
+
+    /// The `MouseButton` for this key in `state`; `None` for wheel directions.
+    pub fn with_state(self, state: ClickState) -> Option<MouseButton> {
+        match self {
+            Self::Left => Some(MouseButton::Left(state)),
+            Self::Right => Some(MouseButton::Right(state)),
+            Self::Middle => Some(MouseButton::Middle(state)),
+            Self::X1 => Some(MouseButton::X1(state)),
+            Self::X2 => Some(MouseButton::X2(state)),
+            _ => None,
+        }
+    }
+}
+
+impl From<&MouseButton> for MouseKey {
+    fn from(button: &MouseButton) -> Self {
+        match button {
+            MouseButton::Left(_) => Self::Left,
+            MouseButton::Right(_) => Self::Right,
+            MouseButton::Middle(_) => Self::Middle,
+            MouseButton::X1(_) => Self::X1,
+            MouseButton::X2(_) => Self::X2,
+        }
+    }
+}
+
+/// Which low-level hook a `HookLost`/`HookReinstalled` event is about.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HookKind {
+    Keyboard,
+    Mouse,
+}
+
+/// An input event delivered to listener callbacks.
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
-pub enum EventType {
-    KeyboardEvent(Option<KeyInfo>),
-    MouseEvent(Option<MouseInfo>),
+#[non_exhaustive]
+pub enum Event {
+    KeyboardEvent(KeyInfo),
+    MouseEvent(MouseInfo),
+    /// Windows stopped calling the hook, usually because it timed out.
+    HookLost(HookKind),
+    /// The hook was installed again after `HookLost`.
+    HookReinstalled(HookKind),
+    /// A device was connected; also sent for every connected device when the
+    /// listener starts. Raw-input backend only.
+    DeviceArrived(DeviceInfo),
+    /// A device was disconnected. Its `id` may be reused afterwards.
+    DeviceRemoved(DeviceInfo),
+    /// A report from a game controller; see `ListenerBuilder::game_controllers`.
+    HidEvent(HidInfo),
+    /// The foreground window's keyboard layout changed, either by switching
+    /// layouts or by focusing a window that uses another. Hook and raw-input
+    /// backends only.
+    LayoutChanged(LayoutInfo),
+    /// A shortcut fired, sent after its callback ran or, with
+    /// `ListenerBuilder::callback_threads`, once the callback was handed to
+    /// the pool, so it may still be running. Autofire repeats are not
+    /// reported.
+    ShortcutEvent(ShortcutEvent),
+    /// The system woke from sleep and the hooks or raw input registrations
+    /// were set up again. Hook and raw-input backends only.
+    Resumed,
+    /// Monitors were added, removed, moved or resized; carries the monitors
+    /// now present. Hook and raw-input backends only.
+    DisplayChanged(Vec<MonitorInfo>),
+    /// Another window came to the foreground; see
+    /// `ListenerBuilder::focus_events`.
+    FocusChanged(WindowInfo),
+}
+
+impl Event {
+    /// Whether the event was synthesized rather than produced by a device.
+    pub fn is_injected(&self) -> bool {
+        match self {
+            Self::KeyboardEvent(key_info) => key_info.injected,
+            Self::MouseEvent(mouse_info) => mouse_info.flags.contains(MouseStateFlags::INJECTED),
+            _ => false,
+        }
+    }
+
+    /// When an input event was captured; `None` for notifications.
+    pub fn timestamp(&self) -> Option<Instant> {
+        match self {
+            Self::KeyboardEvent(key_info) => Some(key_info.timestamp),
+            Self::MouseEvent(mouse_info) => Some(mouse_info.timestamp),
+            Self::HidEvent(info) => Some(info.timestamp),
+            Self::ShortcutEvent(fired) => Some(fired.timestamp),
+            _ => None,
+        }
+    }
+
+    /// See `KeyInfo::seq`; `None` for notifications.
+    pub fn seq(&self) -> Option<u64> {
+        match self {
+            Self::KeyboardEvent(key_info) => Some(key_info.seq),
+            Self::MouseEvent(mouse_info) => Some(mouse_info.seq),
+            _ => None,
+        }
+    }
+
+    /// See `KeyInfo::device`.
+    pub fn device(&self) -> Option<DeviceId> {
+        match self {
+            Self::KeyboardEvent(key_info) => key_info.device,
+            Self::MouseEvent(mouse_info) => mouse_info.device,
+            Self::DeviceArrived(info) | Self::DeviceRemoved(info) => Some(info.id),
+            Self::HidEvent(info) => Some(info.device),
+            _ => None,
+        }
+    }
+
+    fn device_kind(&self) -> Option<DeviceKind> {
+        match self {
+            Self::DeviceArrived(info) | Self::DeviceRemoved(info) => Some(info.kind),
+            _ => None,
+        }
+    }
+
+    fn hook_kind(&self) -> Option<HookKind> {
+        match self {
+            Self::HookLost(kind) | Self::HookReinstalled(kind) => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
+#[deprecated(note = "use `Event` for delivered events and `EventFilter` for registration")]
+pub type EventType = Event;
+
+/// Selects which events a listener registration receives.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventFilter {
+    Keyboard,
+    Mouse,
+    #[default]
     All,
+    /// Keyboard and mouse events from one device (raw-input backend).
+    Device(DeviceId),
+    /// `Event::HidEvent`s.
+    Hid,
+    /// `Event::ShortcutEvent`s.
+    Shortcut,
+    /// Key presses, auto-repeats included.
+    KeyDown,
+    /// Key releases.
+    KeyUp,
+    /// Mouse events without a button or wheel turn.
+    MouseMove,
+    /// Presses and releases of one mouse button; wheel directions never match.
+    MouseButton(MouseKey),
+    /// Wheel turns in any direction.
+    Wheel,
+    /// Events matched by any of the filters.
+    AnyOf(Vec<EventFilter>),
+    /// Keyboard events of the given keys only.
+    Keys(Vec<KeyId>),
+    /// Keyboard events of every key but the given ones.
+    ExceptKeys(Vec<KeyId>),
+    /// Mouse events with the cursor in any of the areas, in the coordinates
+    /// events are reported in.
+    Inside(Vec<Rect>),
+    /// Mouse events with the cursor in none of the areas.
+    Outside(Vec<Rect>),
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        match self {
+            Self::All => true,
+            Self::Keyboard => {
+                matches!(event, Event::KeyboardEvent(_))
+                    || event.hook_kind() == Some(HookKind::Keyboard)
+                    || event.device_kind() == Some(DeviceKind::Keyboard)
+            }
+            Self::Mouse => {
+                matches!(event, Event::MouseEvent(_))
+                    || event.hook_kind() == Some(HookKind::Mouse)
+                    || event.device_kind() == Some(DeviceKind::Mouse)
+            }
+            Self::Device(device) => event.device() == Some(*device),
+            Self::Hid => matches!(event, Event::HidEvent(_)),
+            Self::Shortcut => matches!(event, Event::ShortcutEvent(_)),
+            Self::KeyDown => {
+                matches!(event, Event::KeyboardEvent(key_info) if key_info.state == KeyState::Pressed)
+            }
+            Self::KeyUp => {
+                matches!(event, Event::KeyboardEvent(key_info) if key_info.state == KeyState::Released)
+            }
+            Self::MouseMove => {
+                matches!(event, Event::MouseEvent(mouse_info) if mouse_info.is_move())
+            }
+            Self::MouseButton(key) => matches!(
+                event,
+                Event::MouseEvent(MouseInfo { button: Some(button), .. })
+                    if MouseKey::from(button) == *key
+            ),
+            Self::Wheel => {
+                matches!(event, Event::MouseEvent(mouse_info) if mouse_info.wheel != Pos::default())
+            }
+            Self::AnyOf(filters) => filters.iter().any(|filter| filter.matches(event)),
+            Self::Keys(keys) => {
+                matches!(event, Event::KeyboardEvent(key_info) if keys.contains(&key_info.key_id))
+            }
+            Self::ExceptKeys(keys) => {
+                matches!(event, Event::KeyboardEvent(key_info) if !keys.contains(&key_info.key_id))
+            }
+            Self::Inside(areas) => matches!(
+                event,
+                Event::MouseEvent(mouse_info)
+                    if areas.iter().any(|area| area.contains(&mouse_info.pos))
+            ),
+            Self::Outside(areas) => matches!(
+                event,
+                Event::MouseEvent(mouse_info)
+                    if !areas.iter().any(|area| area.contains(&mouse_info.pos))
+            ),
+        }
+    }
+
+    pub fn wants_keyboard(&self) -> bool {
+        match self {
+            Self::Keyboard
+            | Self::All
+            | Self::Device(_)
+            | Self::KeyDown
+            | Self::KeyUp
+            | Self::Keys(_)
+            | Self::ExceptKeys(_) => true,
+            Self::AnyOf(filters) => filters.iter().any(Self::wants_keyboard),
+            _ => false,
+        }
+    }
+
+    pub fn wants_mouse(&self) -> bool {
+        match self {
+            Self::Mouse
+            | Self::All
+            | Self::Device(_)
+            | Self::MouseMove
+            | Self::MouseButton(_)
+            | Self::Wheel
+            | Self::Inside(_)
+            | Self::Outside(_) => true,
+            Self::AnyOf(filters) => filters.iter().any(Self::wants_mouse),
+            _ => false,
+        }
+    }
 }
 
 // #[derive(Debug)]
@@ -133,10 +1003,12 @@ pub enum EventType {
 //         return self._keyboard_state_usb_input.len() > 2 && self._keyboard_state_usb_input[2] != 0;
 //     }
 // }
-#[derive(Debug, Clone, Eq, Hash)]
+#[derive(Debug, Clone, Eq)]
 pub struct Shortcut {
     modifiers: Vec<VirtualKeyId>,
     normal_keys: Vec<VirtualKeyId>,
+    /// Kept sorted; the order mouse keys are pressed in is not significant.
+    mouse_keys: Vec<MouseKey>,
 }
 
 impl PartialEq for Shortcut {
@@ -152,20 +1024,47 @@ impl PartialEq for Shortcut {
             }
         }
 
-        self.normal_keys == other.normal_keys
+        self.normal_keys == other.normal_keys && self.mouse_keys == other.mouse_keys
+    }
+}
+
+// Consistent with `PartialEq`, which ignores the order of the modifiers.
+impl std::hash::Hash for Shortcut {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        let canonical = self.canonicalize();
+        canonical.modifiers.hash(state);
+        canonical.normal_keys.hash(state);
+        canonical.mouse_keys.hash(state);
+    }
+}
+
+impl FromStr for Shortcut {
+    type Err = String;
+
+    fn from_str(keys: &str) -> Result<Self, Self::Err> {
+        Shortcut::from_str(keys)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Shortcut {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.canonical_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Shortcut {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Shortcut::from_str(&s).map_err(serde::de::Error::custom)
     }
 }
 
+/// Writes `canonical_string`, which `Shortcut::from_str` parses back.
 impl std::fmt::Display for Shortcut {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let keys = self
-            .modifiers
-            .iter()
-            .chain(self.normal_keys.iter())
-            .map(|key| key.to_string())
-            .collect::<Vec<String>>()
-            .join("+");
-        write!(f, "{}", keys)
+        write!(f, "{}", self.canonical_string())
     }
 }
 
@@ -174,6 +1073,7 @@ impl Shortcut {
         Self {
             modifiers: Vec::new(),
             normal_keys: Vec::new(),
+            mouse_keys: Vec::new(),
         }
     }
 
@@ -191,33 +1091,38 @@ impl Shortcut {
     }
 
     fn normalize_key(key: &str) -> Result<VirtualKeyId, String> {
-        let key = key.to_string();
-
-        if key.len() == 1 {
-            if let Ok(key) = VirtualKeyId::from_str(format!("Us{}", key).as_str()) {
-                return Ok(key);
-            }
-            VirtualKeyId::from_str(key.as_str()).map_err(|_| format!("Invalid key: {}", key))
-        } else {
-            let key = key
-                .replace("Ctrl", "Control")
-                .replace("Menu", "Alt")
-                .replace("Win", "Meta")
-                .replace("Option", "Alt")
-                .replace("Cmd", "Meta")
-                .replace("Command", "Meta");
-            VirtualKeyId::from_str(key.as_str()).map_err(|_| format!("Invalid key: {}", key))
-        }
+        crate::keyname::normalize_key(key)
     }
 
-    pub fn from_str(keys: &str) -> Result<Self, String> {
-        keys.trim()
-            .split("+")
-            .map(|key| Self::normalize_key(key))
+    /// Builds a shortcut from Windows scan codes (0xE0 in the high byte for
+    /// extended keys), e.g. `[0x1D, 0x10]` for left Ctrl and the key right of
+    /// Tab. Keys are matched by position, so this binds the same physical
+    /// keys on QWERTY, AZERTY and QWERTZ; "sc:0x10" does the same in strings.
+    pub fn from_scancodes(codes: &[u16]) -> Result<Self, String> {
+        codes
+            .iter()
+            .map(|&code| {
+                crate::keyname::scancode_key(code)
+                    .ok_or_else(|| format!("Invalid scan code: {:#x}", code))
+            })
             .collect::<Result<Vec<VirtualKeyId>, String>>()
             .and_then(Self::new)
     }
 
+    /// Parses "Ctrl+Shift+A"; key names are case-insensitive and "Ctrl++"
+    /// binds the '+' key. Mouse buttons and wheel directions are written
+    /// as in `MouseKey::name`, e.g. "Ctrl+MouseLeft" or "Shift+WheelUp".
+    pub fn from_str(keys: &str) -> Result<Self, String> {
+        let mut s = Self::default();
+        for key in crate::keyname::split_keys(keys)? {
+            match MouseKey::from_name(key) {
+                Some(mouse_key) => s.set_mouse_key(mouse_key),
+                None => s.set_key(Self::normalize_key(key)?),
+            }
+        }
+        Ok(s)
+    }
+
     pub fn set_key(&mut self, key: VirtualKeyId) {
         if key.modifier().is_some() {
             if !self.modifiers.contains(&key) {
@@ -230,6 +1135,32 @@ impl Shortcut {
         }
     }
 
+    pub fn set_mouse_key(&mut self, key: MouseKey) {
+        if let Err(i) = self.mouse_keys.binary_search(&key) {
+            self.mouse_keys.insert(i, key);
+        }
+    }
+
+    pub fn modifier_keys(&self) -> &[VirtualKeyId] {
+        &self.modifiers
+    }
+
+    pub fn normal_keys(&self) -> &[VirtualKeyId] {
+        &self.normal_keys
+    }
+
+    pub fn mouse_keys(&self) -> &[MouseKey] {
+        &self.mouse_keys
+    }
+
+    pub fn has_mouse_key(&self) -> bool {
+        !self.mouse_keys.is_empty()
+    }
+
+    pub fn has_wheel_key(&self) -> bool {
+        self.mouse_keys.iter().any(MouseKey::is_wheel)
+    }
+
     pub fn remove_key(&mut self, key: VirtualKeyId) {
         if key.modifier().is_some() {
             self.modifiers.retain(|&k| k != key);
@@ -247,6 +1178,11 @@ impl Shortcut {
     }
 
     pub fn is_match(&self, other: &Self) -> bool {
+        self.is_match_with(other, SidePolicy::default())
+    }
+
+    /// `is_match`, reading the sides of this shortcut's modifiers by `policy`.
+    pub fn is_match_with(&self, other: &Self, policy: SidePolicy) -> bool {
         if self.modifiers.len() != other.modifiers.len() {
             return false;
         }
@@ -255,26 +1191,12 @@ impl Shortcut {
             return false;
         }
 
-        for (i, key) in self.modifiers.iter().enumerate() {
-            // let mut count = 0;
-            // for other_key in other.modifiers.iter() {
-            //     let other_key_bits = other_key.modifier().unwrap().bits();
-            //     let key_bits = key.modifier().unwrap().bits();
-            //     if other_key_bits & !key_bits == 0 {
-            //         count += 1;
-            //     }
-            //     if count > 1 {
-            //         return false;
-            //     }
-            // }
+        for key in self.modifiers.iter() {
+            let key_bits = policy.resolve(Modifiers::from_key(*key));
             let count = other
                 .modifiers
                 .iter()
-                .filter(|&other_key| {
-                    let other_key_bits = other_key.modifier().unwrap().bits();
-                    let key_bits = key.modifier().unwrap().bits();
-                    other_key_bits & !key_bits == 0
-                })
+                .filter(|&other_key| key_bits.contains(Modifiers::from_key(*other_key)))
                 .count();
             if count != 1 {
                 return false;
@@ -286,40 +1208,703 @@ impl Shortcut {
                 return false;
             }
         }
-        true
+        self.mouse_keys == other.mouse_keys
+    }
+
+    pub fn matches(&self, snapshot: &KeySnapshot) -> bool {
+        self.is_match(&snapshot.to_shortcut())
+    }
+
+    /// Whether some key combination can trigger both shortcuts: the modifiers
+    /// pair up (a generic modifier overlaps both of its sides) and one list of
+    /// normal keys starts with the other, as in "Ctrl+C" and "Ctrl+C+V".
+    pub fn overlaps(&self, other: &Self) -> bool {
+        if self.modifiers.len() != other.modifiers.len() {
+            return false;
+        }
+        let modifiers_pair = self.modifiers.iter().all(|key| {
+            other.modifiers.iter().any(|other_key| {
+                Modifiers::from_key(*key).intersects(Modifiers::from_key(*other_key))
+            })
+        });
+        let n = self.normal_keys.len().min(other.normal_keys.len());
+        modifiers_pair
+            && self.normal_keys[..n] == other.normal_keys[..n]
+            && self.mouse_keys == other.mouse_keys
+    }
+
+    /// Sort key for modifiers: Ctrl, Shift, Alt, Meta, and within each the
+    /// generic key before its left and right variants.
+    fn modifier_order(key: &VirtualKeyId) -> (u8, u8) {
+        let m = Modifiers::from_key(*key);
+        let group = [
+            Modifiers::CONTROL,
+            Modifiers::SHIFT,
+            Modifiers::ALT,
+            Modifiers::META,
+        ]
+        .iter()
+        .position(|g| g.contains(m))
+        .unwrap_or(4) as u8;
+        let side = if m.bits().count_ones() > 1 {
+            0
+        } else if m.bits() & 0x0f != 0 {
+            1
+        } else {
+            2
+        };
+        (group, side)
+    }
+
+    pub(crate) fn canonical_key_name(key: &VirtualKeyId) -> String {
+        let name = key.to_string();
+        if let Some(rest) = name.strip_prefix("Us") {
+            if rest.len() == 1 {
+                return rest.to_string();
+            }
+        }
+        name.replacen("Control", "Ctrl", 1)
+    }
+
+    /// The same shortcut with its modifiers in a fixed order (Ctrl, Shift,
+    /// Alt, Meta; the generic key before its sides), so equal shortcuts are
+    /// also identical. Normal keys keep their order since it is significant
+    /// for matching.
+    pub fn canonicalize(&self) -> Self {
+        let mut canonical = self.clone();
+        canonical.modifiers.sort_by_key(Self::modifier_order);
+        canonical
+    }
+
+    /// A normalized form such as "Ctrl+Shift+A" that does not depend on the
+    /// order modifiers were written in, suitable as a config or map key.
+    /// It is also what `Display` writes and parses back to an equal shortcut.
+    pub fn canonical_string(&self) -> String {
+        let canonical = self.canonicalize();
+        canonical
+            .modifiers
+            .iter()
+            .chain(canonical.normal_keys.iter())
+            .map(Self::canonical_key_name)
+            .chain(self.mouse_keys.iter().map(|key| key.name().to_string()))
+            .collect::<Vec<String>>()
+            .join("+")
+    }
+
+    /// Renders the shortcut for people to read; see `ShortcutStyle`.
+    pub fn format(&self, style: ShortcutStyle) -> String {
+        let mut modifiers = self.modifiers.clone();
+        match style {
+            ShortcutStyle::Canonical => return self.canonical_string(),
+            ShortcutStyle::Localized => {
+                modifiers.sort_by_key(Self::modifier_order);
+                modifiers
+                    .iter()
+                    .chain(self.normal_keys.iter())
+                    .map(|key| KeyId(*key).display_name())
+                    .chain(self.mouse_keys.iter().map(|key| key.name().to_string()))
+                    .collect::<Vec<String>>()
+                    .join("+")
+            }
+            ShortcutStyle::MacSymbols => {
+                // Apple's order: Control, Option, Shift, Command.
+                let symbol = |key: &VirtualKeyId| {
+                    let m = Modifiers::from_key(*key);
+                    [
+                        (Modifiers::CONTROL, "⌃"),
+                        (Modifiers::ALT, "⌥"),
+                        (Modifiers::SHIFT, "⇧"),
+                        (Modifiers::META, "⌘"),
+                    ]
+                    .iter()
+                    .position(|(group, _)| group.intersects(m))
+                };
+                modifiers.sort_by_key(symbol);
+                modifiers.dedup_by_key(|key| symbol(key));
+                let mut text: String = modifiers
+                    .iter()
+                    .filter_map(symbol)
+                    .map(|i| ["⌃", "⌥", "⇧", "⌘"][i])
+                    .collect();
+                for key in self.normal_keys.iter() {
+                    text += &Self::mac_key_name(key);
+                }
+                for key in self.mouse_keys.iter() {
+                    text += key.name();
+                }
+                text
+            }
+        }
+    }
+
+    fn mac_key_name(key: &VirtualKeyId) -> String {
+        match key {
+            VirtualKeyId::Enter => "↩",
+            VirtualKeyId::Escape => "⎋",
+            VirtualKeyId::Backspace => "⌫",
+            VirtualKeyId::Delete => "⌦",
+            VirtualKeyId::Tab => "⇥",
+            VirtualKeyId::CapsLock => "⇪",
+            VirtualKeyId::ArrowLeft => "←",
+            VirtualKeyId::ArrowRight => "→",
+            VirtualKeyId::ArrowUp => "↑",
+            VirtualKeyId::ArrowDown => "↓",
+            VirtualKeyId::PageUp => "⇞",
+            VirtualKeyId::PageDown => "⇟",
+            VirtualKeyId::Home => "↖",
+            VirtualKeyId::End => "↘",
+            VirtualKeyId::Space => "Space",
+            key => return Self::canonical_key_name(key),
+        }
+        .to_string()
+    }
+}
+
+/// How `Shortcut::format` renders a shortcut.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+pub enum ShortcutStyle {
+    /// "Ctrl+Shift+A", as `Shortcut::canonical_string`; parses back.
+    #[default]
+    Canonical,
+    /// Key names of the calling thread's keyboard layout, e.g.
+    /// "Strg+Umschalt+A" on a German layout; see `KeyId::display_name`.
+    Localized,
+    /// macOS menu style, "⌃⇧A". Modifier sides are not shown.
+    MacSymbols,
+}
+
+/// Chords pressed one after another, e.g. "Ctrl+K, Ctrl+C" or "G then I".
+/// A single chord is a sequence of one step.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShortcutSequence {
+    steps: Vec<Shortcut>,
+}
+
+impl ShortcutSequence {
+    pub fn new(steps: Vec<Shortcut>) -> Result<Self, String> {
+        if steps.is_empty() {
+            return Err("Empty sequence".to_string());
+        }
+        Ok(Self { steps })
+    }
+
+    /// Steps are separated by "," or " then ". A "," where a key is expected
+    /// is the comma key, as in "Ctrl+K, Ctrl+,".
+    pub fn from_str(keys: &str) -> Result<Self, String> {
+        let keys = keys.replace(" then ", ",");
+        crate::keyname::split_steps(&keys)
+            .into_iter()
+            .map(Shortcut::from_str)
+            .collect::<Result<Vec<Shortcut>, String>>()
+            .and_then(Self::new)
+    }
+
+    pub fn steps(&self) -> &[Shortcut] {
+        &self.steps
+    }
+
+    pub fn is_chord(&self) -> bool {
+        self.steps.len() == 1
+    }
+
+    /// Whether typing one sequence can trigger the other, i.e. their common
+    /// steps overlap pairwise.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.steps
+            .iter()
+            .zip(other.steps.iter())
+            .all(|(step, other_step)| step.overlaps(other_step))
+    }
+}
+
+impl From<Shortcut> for ShortcutSequence {
+    fn from(shortcut: Shortcut) -> Self {
+        Self {
+            steps: vec![shortcut],
+        }
+    }
+}
+
+impl std::fmt::Display for ShortcutSequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let steps = self
+            .steps
+            .iter()
+            .map(|step| step.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        write!(f, "{}", steps)
     }
 }
 
 pub type JoinHandleType = JoinHandle<()>;
 
+/// How a listener captures input; see `ListenerBuilder::backend`. Each backend
+/// is only available when its cargo feature is enabled.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Backend {
+    /// Raw Input on a hidden window (`raw-input`).
+    RawInput,
+    /// `WH_KEYBOARD_LL`/`WH_MOUSE_LL` hooks (`ll-hook`). The only backend that
+    /// can swallow input.
+    LowLevelHook,
+    /// `/dev/input/event*` on Linux (`evdev`).
+    Evdev,
+    /// `WH_KEYBOARD`/`WH_MOUSE` hooks on one thread of another process, set
+    /// from the `kmhook-winhook` DLL (`dll-hook`); see `ListenerBuilder::dll_hook`.
+    DllHook,
+}
+
+impl Default for Backend {
+    /// Evdev on Linux. On Windows raw input, unless it is disabled or the
+    /// `suppression` feature asks for the hook backend.
+    fn default() -> Self {
+        if cfg!(target_os = "linux") {
+            Self::Evdev
+        } else if cfg!(all(feature = "raw-input", not(feature = "suppression"))) {
+            Self::RawInput
+        } else {
+            Self::LowLevelHook
+        }
+    }
+}
+
+/// Why a listener could not start capturing input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KmHookError {
+    /// `SetWindowsHookExW` failed.
+    HookInstall(String),
+    /// `RegisterRawInputDevices` failed.
+    RawInputRegistration(String),
+    /// The hidden window receiving raw input could not be created.
+    WindowCreation(String),
+    /// No input device could be opened (evdev).
+    DeviceAccess(String),
+    /// The event loop thread could not be started or died during setup.
+    Thread(String),
+    /// The backend's cargo feature is not enabled, or it does not exist on
+    /// this platform, or an option needs it but another one is configured.
+    BackendUnavailable(Backend),
+}
+
+impl std::fmt::Display for KmHookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::HookInstall(e) => write!(f, "failed to install hook: {}", e),
+            Self::RawInputRegistration(e) => write!(f, "failed to register raw input: {}", e),
+            Self::WindowCreation(e) => write!(f, "failed to create input window: {}", e),
+            Self::DeviceAccess(e) => write!(f, "failed to open input devices: {}", e),
+            Self::Thread(e) => write!(f, "event loop thread failed: {}", e),
+            Self::BackendUnavailable(backend) => {
+                write!(f, "{:?} backend is not available", backend)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KmHookError {}
+
+/// A shortcut overlaps already registered ones; see `Shortcut::overlaps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictError {
+    pub shortcut: ShortcutSequence,
+    /// IDs of the registered shortcuts it overlaps.
+    pub conflicts: Vec<ID>,
+}
+
+impl std::fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Shortcut {} conflicts with {:?}",
+            self.shortcut, self.conflicts
+        )
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+/// Why a shortcut could not be registered. Converts into the `String`
+/// errors of the other registration methods.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegisterError {
+    /// The shortcut string does not parse.
+    Parse(String),
+    /// The same keys are already bound for the same context and device.
+    Duplicate(ShortcutSequence),
+    /// It overlaps registered shortcuts; only with
+    /// `ListenerBuilder::detect_conflicts`.
+    Conflict(ConflictError),
+    /// No group with this ID; see `Listener::create_group`.
+    GroupNotFound(GroupId),
+}
+
+impl std::fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "{}", e),
+            Self::Duplicate(shortcut) => write!(f, "Shortcut {} already exists", shortcut),
+            Self::Conflict(e) => write!(f, "{}", e),
+            Self::GroupNotFound(group) => write!(f, "Group not found: {}", group),
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+impl From<ConflictError> for RegisterError {
+    fn from(e: ConflictError) -> Self {
+        Self::Conflict(e)
+    }
+}
+
+impl From<RegisterError> for String {
+    fn from(e: RegisterError) -> Self {
+        e.to_string()
+    }
+}
+
+/// A callback panicked; passed to `Listener::set_error_handler`. The worker
+/// goes on delivering events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallbackPanic {
+    /// The event listener or shortcut whose callback panicked.
+    pub id: ID,
+    pub message: String,
+}
+
+impl CallbackPanic {
+    pub(crate) fn new(id: ID, payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload
+                .downcast_ref::<&str>()
+                .map_or("callback panicked".to_string(), |s| s.to_string()),
+        };
+        Self { id, message }
+    }
+}
+
+impl std::fmt::Display for CallbackPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Callback {} panicked: {}", self.id, self.message)
+    }
+}
+
+impl std::error::Error for CallbackPanic {}
+
+/// A callback ran longer than the threshold given to
+/// `Listener::set_slow_callback_handler`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowCallback {
+    /// The event listener or shortcut whose callback was slow.
+    pub id: ID,
+    pub elapsed: Duration,
+}
+
+/// How the left and right keys of a modifier are told apart when matching.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SidePolicy {
+    /// "Ctrl" matches either Ctrl key, "CtrlLeft" only the left one.
+    #[default]
+    StrictSides,
+    /// Sides are ignored: "CtrlLeft" matches the right Ctrl key as well.
+    AnySide,
+    /// "Ctrl" means the left Ctrl key; the right one must be written out.
+    PreferLeft,
+}
+
+impl SidePolicy {
+    /// The modifier keys that may stand for `modifier`.
+    fn resolve(self, modifier: Modifiers) -> Modifiers {
+        let group = [
+            Modifiers::CONTROL,
+            Modifiers::SHIFT,
+            Modifiers::ALT,
+            Modifiers::META,
+        ]
+        .into_iter()
+        .find(|group| group.intersects(modifier))
+        .unwrap_or(modifier);
+        match self {
+            Self::StrictSides => modifier,
+            Self::AnySide => group,
+            Self::PreferLeft if modifier == group => {
+                modifier
+                    & (Modifiers::CONTROL_LEFT
+                        | Modifiers::SHIFT_LEFT
+                        | Modifiers::ALT_LEFT
+                        | Modifiers::META_LEFT)
+            }
+            Self::PreferLeft => modifier,
+        }
+    }
+}
+
+/// What a callback registered with `Listener::add_global_shortcut_with_event`
+/// is passed when its shortcut fires, and the payload of `Event::ShortcutEvent`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct ShortcutEvent {
+    /// The registration that fired.
+    pub id: ID,
+    pub shortcut: ShortcutSequence,
+    pub timestamp: Instant,
+    /// How many times the registration has fired, counting from 1 and
+    /// including autofire repeats.
+    pub trigger_count: u64,
+    /// The key press or release that completed the shortcut; autofire
+    /// repeats keep the original press. `None` for mouse shortcuts and
+    /// shortcuts served as hotkeys.
+    pub key_info: Option<KeyInfo>,
+}
+
+/// What counts as a shake for `Listener::add_shake_trigger`. Construct with
+/// `..Default::default()`.
+#[derive(Debug, Clone, Default)]
+pub struct ShakeOptions {
+    /// Changes of direction needed, horizontal or vertical. Defaults to 4.
+    pub reversals: Option<u32>,
+    /// Shortest swing before a change of direction counts, in pixels.
+    /// Defaults to 50.
+    pub min_amplitude: Option<i32>,
+    /// Time all the changes of direction must fall in. Defaults to 800 ms.
+    pub within: Option<Duration>,
+}
+
+/// Per-shortcut behavior. Construct with `..Default::default()` so new options
+/// can be added without breaking callers.
+#[derive(Debug, Clone, Default)]
+pub struct ShortcutOptions {
+    /// Consume the keystroke that completes the shortcut (and its repeats and
+    /// release) so it never reaches the focused application.
+    #[cfg(feature = "suppression")]
+    pub swallow: bool,
+    /// Longest pause allowed between the steps of a `ShortcutSequence`.
+    /// Defaults to 1 second.
+    pub sequence_timeout: Option<Duration>,
+    /// Fire on press (default) or on release of the completing chord.
+    pub edge: TriggerEdge,
+    /// Longest hold that still counts as a tap (`TriggerEdge::Tap`).
+    /// Defaults to 300 ms.
+    pub tap_timeout: Option<Duration>,
+    /// Minimum time between two runs of the callback; completions within
+    /// it are ignored.
+    pub cooldown: Option<Duration>,
+    /// Keep running the callback at this interval while the completing chord
+    /// stays held, until the next release or press. The repeats run on a
+    /// helper thread. Only applies to `TriggerEdge::Press`.
+    pub autofire: Option<Duration>,
+    /// Only fire while a matching window is in the foreground. A shortcut
+    /// with a context takes precedence over the same shortcut without one.
+    pub context: Option<AppContext>,
+    /// Only input from this device completes the shortcut; presses on other
+    /// devices leave its progress alone. Raw-input backend only.
+    pub device: Option<DeviceId>,
+    /// How modifier sides are matched; `None` uses the listener's
+    /// `ListenerBuilder::side_policy`.
+    pub side_policy: Option<SidePolicy>,
+    /// Shortcuts completed by the same input fire highest priority first,
+    /// then in the order they were registered. Default is 0.
+    pub priority: i32,
+    /// Unregister the shortcut once it has fired this many times. Autofire
+    /// repeats count and stop at the limit; a shortcut they used up stays
+    /// registered but no longer fires.
+    pub max_triggers: Option<u64>,
+}
+
+/// Returned by callbacks of `Listener::add_event_listener_with_priority`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+pub enum Propagation {
+    #[default]
+    Continue,
+    /// The event was handled: listeners of lower priority do not get it.
+    /// Shortcuts still see it.
+    Stop,
+}
+
+/// When a shortcut's callback runs.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriggerEdge {
+    /// As soon as the chord is complete.
+    #[default]
+    Press,
+    /// When the completed chord is let go, i.e. on the first release after
+    /// it, unless something else was pressed in between. Wheel directions
+    /// have no release and still fire on the turn.
+    Release,
+    /// Like `Release`, but only if the chord was held no longer than
+    /// `ShortcutOptions::tap_timeout`. Lets "Alt" fire on a tap of Alt alone
+    /// and not when Alt is held for Alt+Tab.
+    Tap,
+}
+
+/// Selects the worker thread a registration's callback runs on.
+///
+/// Only `Shared` exists by default. Dedicated `Keyboard`/`Mouse` workers are
+/// spawned when enabled with `Listener::add_worker`; registrations asking for a
+/// worker that was not enabled fall back to `Shared`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WorkerAffinity {
+    #[default]
+    Shared,
+    Keyboard,
+    Mouse,
+}
+
+impl WorkerAffinity {
+    /// Thread name after the `ListenerBuilder::thread_name_prefix`.
+    pub(crate) fn thread_role(&self) -> &'static str {
+        match self {
+            Self::Shared => "worker",
+            Self::Keyboard => "worker-keyboard",
+            Self::Mouse => "worker-mouse",
+        }
+    }
+}
+
+/// Scheduling priority of a thread the crate spawns; see
+/// `ListenerBuilder::hook_thread_priority`. Ignored on Linux.
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThreadPriority {
+    #[default]
+    Normal,
+    AboveNormal,
+    Highest,
+    TimeCritical,
+}
+
+/// Hook-to-callback latency percentiles.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct Latency {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// A snapshot of a listener's counters, from `Listener::metrics()`.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Metrics {
+    pub keyboard_events: u64,
+    pub mouse_events: u64,
+    /// Mouse moves merged into later ones by throttling or coalescing, and
+    /// events that did not fit a bounded worker queue or the queue of an
+    /// `add_event_listener_queued`.
+    pub dropped_events: u64,
+    /// Messages waiting in each worker's queue.
+    pub queue_depth: HashMap<WorkerAffinity, usize>,
+    /// `None` until an event reached a callback.
+    pub latency: Option<Latency>,
+}
+
+/// What a listener's backend has set up.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HookStatus {
+    /// The event loop is running.
+    pub running: bool,
+    /// Keyboard input is hooked or registered for raw input.
+    pub keyboard: bool,
+    pub mouse: bool,
+    /// OS id of the event loop thread; `None` when stopped or on Linux, where
+    /// each device has its own reader.
+    pub loop_thread_id: Option<u32>,
+}
+
+/// A registered shortcut in a `ListenerStateReport`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShortcutReport {
+    pub id: ID,
+    pub shortcut: String,
+    pub enabled: bool,
+    /// Delivered through `RegisterHotKey` rather than the hook.
+    pub hotkey: bool,
+}
+
+/// A registered event listener in a `ListenerStateReport`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventListenerReport {
+    pub id: ID,
+    pub filter: EventFilter,
+    pub affinity: WorkerAffinity,
+    pub priority: i32,
+}
+
+/// A worker thread in a `ListenerStateReport`.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorkerReport {
+    pub affinity: WorkerAffinity,
+    pub thread_name: String,
+    /// `None` until the worker runs.
+    pub thread_id: Option<String>,
+    pub queue_depth: usize,
+}
+
+/// Everything `Listener::dump_state` knows about a listener, for attaching
+/// to bug reports. Serializes to JSON with the `serde` feature.
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListenerStateReport {
+    pub backend: Backend,
+    pub hook: HookStatus,
+    pub shortcuts: Vec<ShortcutReport>,
+    pub event_listeners: Vec<EventListenerReport>,
+    pub workers: Vec<WorkerReport>,
+    pub keyboard_events: u64,
+    pub mouse_events: u64,
+    pub dropped_events: u64,
+}
+
 pub trait EventListener {
     fn new() -> Arc<Self>;
-    fn add_global_shortcut<F>(&self, shortcut: &str, cb: F) -> std::result::Result<ID, String>
+    /// `shortcut` is a chord like "Ctrl+Shift+A" or "Ctrl+MouseLeft", or a
+    /// `ShortcutSequence` like "Ctrl+K, Ctrl+C". Chords with mouse keys fire
+    /// when the mouse button goes down or the wheel turns.
+    fn add_global_shortcut<F>(
+        &self,
+        shortcut: &str,
+        cb: F,
+    ) -> std::result::Result<ID, RegisterError>
     where
         F: Fn() + Send + Sync + 'static;
 
+    /// Fires `cb` once `shortcut` was triggered `trigger` times, each within
+    /// `internal` ms of the previous, e.g. a double Ctrl+C or a double click on
+    /// "MouseX1". `internal` defaults to 400 ms, or to the system double-click
+    /// time for shortcuts made only of mouse keys.
     fn add_global_shortcut_trigger<F>(
         &self,
         shortcut: &str,
         cb: F,
         trigger: u32,
         internal: Option<u32>,
-    ) -> std::result::Result<ID, String>
+    ) -> std::result::Result<ID, RegisterError>
     where
         F: Fn() + Send + Sync + 'static;
 
     fn add_event_listener<F>(
         &self,
         cb: F,
-        event_type: Option<EventType>,
+        filter: Option<EventFilter>,
     ) -> std::result::Result<ID, String>
     where
-        F: Fn(EventType) + Send + Sync + 'static;
+        F: Fn(Event) + Send + Sync + 'static;
 
     fn del_event_by_id(&self, id: ID);
     fn del_all_events(&self);
 
-    fn startup(self: &Arc<Self>, work_thread: Option<bool>) -> Option<JoinHandleType>;
+    fn startup(
+        self: &Arc<Self>,
+        work_thread: Option<bool>,
+    ) -> Result<Option<JoinHandleType>, KmHookError>;
     fn shutdown(&self);
 }
 
@@ -336,41 +1921,153 @@ mod tests {
 
     #[test]
     fn test_event_info() {
-        let event_type = EventType::KeyboardEvent(Some(KeyInfo::new(
+        let event = Event::KeyboardEvent(KeyInfo::new(
             KeyId::from(VirtualKeyId::UsA),
             KeyState::Pressed,
-        )));
+        ));
 
-        match event_type {
-            EventType::KeyboardEvent(info) => {
-                if let Some(k) = info {
-                    println!("KeyboardEvent {:?}", k)
-                }
-            }
+        match event {
+            Event::KeyboardEvent(k) => println!("KeyboardEvent {:?}", k),
             _ => {}
         }
     }
 
+    #[test]
+    fn test_event_filter() {
+        let key_event = Event::KeyboardEvent(KeyInfo::new(
+            KeyId::from(VirtualKeyId::UsA),
+            KeyState::Pressed,
+        ));
+        let mouse_event = Event::MouseEvent(MouseInfo {
+            button: None,
+            pos: Pos::default(),
+            relative_pos: Pos::default(),
+            wheel: Pos::default(),
+            flags: MouseStateFlags::empty(),
+            ..Default::default()
+        });
+
+        assert!(EventFilter::Keyboard.matches(&key_event));
+        assert!(!EventFilter::Keyboard.matches(&mouse_event));
+        assert!(EventFilter::Mouse.matches(&mouse_event));
+        assert!(!EventFilter::Mouse.matches(&key_event));
+        assert!(EventFilter::All.matches(&key_event));
+        assert!(EventFilter::All.matches(&mouse_event));
+
+        let lost = Event::HookLost(HookKind::Mouse);
+        assert!(EventFilter::Mouse.matches(&lost));
+        assert!(!EventFilter::Keyboard.matches(&lost));
+
+        let mut pad_key = KeyInfo::new(KeyId::from(VirtualKeyId::UsA), KeyState::Pressed);
+        pad_key.device = Some(DeviceId(7));
+        let pad = EventFilter::Device(DeviceId(7));
+        assert!(pad.matches(&Event::KeyboardEvent(pad_key)));
+        assert!(!pad.matches(&key_event));
+        assert!(!pad.matches(&lost));
+
+        let fired = Event::ShortcutEvent(ShortcutEvent {
+            id: 1,
+            shortcut: ShortcutSequence::from_str("Ctrl+A").unwrap(),
+            timestamp: Instant::now(),
+            trigger_count: 1,
+            key_info: None,
+        });
+        assert!(EventFilter::Shortcut.matches(&fired));
+
+        let click = Event::MouseEvent(MouseInfo {
+            button: Some(MouseButton::Left(ClickState::Pressed)),
+            ..Default::default()
+        });
+        let clicks = EventFilter::AnyOf(vec![
+            EventFilter::MouseButton(MouseKey::Left),
+            EventFilter::MouseButton(MouseKey::Right),
+        ]);
+        assert!(clicks.matches(&click));
+        assert!(!clicks.matches(&mouse_event));
+        assert!(EventFilter::MouseMove.matches(&mouse_event));
+        assert!(!EventFilter::MouseMove.matches(&click));
+        assert!(clicks.wants_mouse() && !clicks.wants_keyboard());
+        assert!(EventFilter::KeyDown.matches(&key_event));
+        assert!(!EventFilter::KeyUp.matches(&key_event));
+
+        let a: KeyId = "a".parse().unwrap();
+        assert!(EventFilter::Keys(vec![a]).matches(&key_event));
+        assert!(!EventFilter::ExceptKeys(vec![a]).matches(&key_event));
+        assert!(!EventFilter::ExceptKeys(vec![a]).matches(&mouse_event));
+        let b = KeyId::from(VirtualKeyId::UsB);
+        assert!(!EventFilter::Keys(vec![b]).matches(&key_event));
+        assert!(EventFilter::ExceptKeys(vec![b]).matches(&key_event));
+
+        let corner = vec![Rect::new(0, 0, 10, 10)];
+        assert!(EventFilter::Inside(corner.clone()).matches(&mouse_event));
+        assert!(!EventFilter::Outside(corner.clone()).matches(&mouse_event));
+        assert!(!EventFilter::Inside(corner).matches(&key_event));
+        let far = vec![Rect::new(100, 100, 200, 200)];
+        assert!(EventFilter::Outside(far).matches(&mouse_event));
+        assert!(!EventFilter::Shortcut.matches(&key_event));
+        assert!(!EventFilter::Keyboard.matches(&fired));
+    }
+
+    #[test]
+    fn test_mouse_speed() {
+        let info = MouseInfo {
+            velocity: Pos { x: 300, y: -400 },
+            ..Default::default()
+        };
+        assert_eq!(info.speed(), 500.0);
+        assert!(info.direction() < 0.0);
+        assert_eq!(MouseInfo::default().speed(), 0.0);
+    }
+
+    #[test]
+    fn test_corner_area() {
+        let screen = Rect::new(0, 0, 1920, 1080);
+        assert_eq!(Corner::TopLeft.area(&screen, 2), Rect::new(0, 0, 2, 2));
+        let bottom_right = Corner::BottomRight.area(&screen, 2);
+        assert!(bottom_right.contains(&Pos { x: 1919, y: 1079 }));
+        assert!(!bottom_right.contains(&Pos { x: 1917, y: 1079 }));
+        assert_eq!(
+            Corner::TopRight.area(&screen, 2),
+            Rect::new(1918, 0, 1920, 2)
+        );
+    }
+
+    #[test]
+    fn test_logical_point() {
+        // A 200% monitor to the right of a 1920 wide primary.
+        let monitor = MonitorInfo {
+            index: 1,
+            bounds: Rect::new(1920, 0, 5760, 2160),
+            dpi: 192,
+        };
+        assert_eq!(
+            monitor.logical_point(&Pos { x: 1920, y: 0 }),
+            Pos { x: 1920, y: 0 }
+        );
+        assert_eq!(
+            monitor.logical_point(&Pos { x: 2920, y: 500 }),
+            Pos { x: 2420, y: 250 }
+        );
+        assert_eq!(monitor.logical_bounds(), Rect::new(1920, 0, 3840, 1080));
+        assert_eq!(monitor.logical_length(-30), -15);
+    }
+
     #[test]
     fn enumhashable() {
         use std::collections::HashMap;
-        let mut map: HashMap<Option<EventType>, (ID, ID)> = HashMap::new();
-
-        map.insert(Some(EventType::KeyboardEvent(None)), (2, 2));
-        map.insert(Some(EventType::MouseEvent(None)), (1, 2));
-        map.insert(None, (3, 3));
+        let mut map: HashMap<EventFilter, (ID, ID)> = HashMap::new();
 
-        // println!("{:?}", map);
+        map.insert(EventFilter::Keyboard, (2, 2));
+        map.insert(EventFilter::Mouse, (1, 2));
+        map.insert(EventFilter::All, (3, 3));
 
-        let et = EventType::KeyboardEvent(Some(KeyInfo::new(
+        let et = Event::KeyboardEvent(KeyInfo::new(
             KeyId::from(VirtualKeyId::UsA),
             KeyState::Pressed,
-        )));
+        ));
         for (k, v) in map.iter() {
-            if let Some(k) = k {
-                if std::mem::discriminant(k) == std::mem::discriminant(&et) {
-                    println!("{:?}", v);
-                }
+            if k.matches(&et) {
+                println!("{:?}", v);
             }
         }
 
@@ -420,6 +2117,41 @@ mod tests {
         assert_eq!(shortcut.modifiers[1], VirtualKeyId::Alt);
         assert_eq!(shortcut.normal_keys[0], VirtualKeyId::UsT);
 
+        assert_eq!(
+            Shortcut::from_str("AltGr+Q").unwrap(),
+            Shortcut::from_str("AltRight+Q").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("ctrl + shift + esc").unwrap(),
+            Shortcut::from_str("Control+Shift+Escape").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("LCtrl+Return").unwrap(),
+            Shortcut::from_str("ControlLeft+Enter").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("Super+E").unwrap(),
+            Shortcut::from_str("Meta+E").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("RSuper+E").unwrap(),
+            Shortcut::from_str("MetaRight+E").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("Del+PgUp").unwrap().normal_keys,
+            vec![VirtualKeyId::Delete, VirtualKeyId::PageUp]
+        );
+        assert_eq!(
+            Shortcut::from_str("Ctrl++").unwrap(),
+            Shortcut::from_str("Ctrl+Plus").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("+").unwrap().normal_keys,
+            vec![VirtualKeyId::Equal]
+        );
+        assert!(Shortcut::from_str("Ctrl+").is_err());
+        assert!(Shortcut::from_str("Ctrl++A").is_err());
+
         let shortcut = Shortcut::from_str("Ctrl+Alt+T+X").unwrap();
         assert_eq!(shortcut.modifiers.len(), 2);
         assert_eq!(shortcut.normal_keys.len(), 2);
@@ -427,6 +2159,40 @@ mod tests {
         assert_eq!(shortcut.modifiers[1], VirtualKeyId::Alt);
         assert_eq!(shortcut.normal_keys[0], VirtualKeyId::UsT);
         assert_eq!(shortcut.normal_keys[1], VirtualKeyId::UsX);
+
+        for (name, key) in [
+            ("VolumeUp", VirtualKeyId::AudioVolumeUp),
+            ("MediaPlayPause", VirtualKeyId::MediaPlayPause),
+            ("BrowserBack", VirtualKeyId::BrowserBack),
+            ("F19", VirtualKeyId::F19),
+        ] {
+            let shortcut = Shortcut::from_str(&format!("Ctrl+{}", name)).unwrap();
+            assert_eq!(shortcut.normal_keys, vec![key]);
+            assert_eq!(Shortcut::from_str(&shortcut.to_string()).unwrap(), shortcut);
+        }
+
+        assert_eq!(
+            Shortcut::from_str("Ctrl+sc:0x1E").unwrap(),
+            Shortcut::from_str("Ctrl+A").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_scancodes(&[0x1D, 0x1E]).unwrap(),
+            Shortcut::from_str("CtrlLeft+sc:30").unwrap()
+        );
+        assert!(Shortcut::from_str("sc:0xZZ").is_err());
+
+        let numpad = Shortcut::from_str("Ctrl+Numpad5").unwrap();
+        assert_eq!(numpad.normal_keys, vec![VirtualKeyId::Numpad5]);
+        assert_eq!(numpad, Shortcut::from_str("Ctrl+Num5").unwrap());
+        assert_ne!(numpad, Shortcut::from_str("Ctrl+Digit5").unwrap());
+        assert_ne!(
+            Shortcut::from_str("NumpadEnter").unwrap(),
+            Shortcut::from_str("Enter").unwrap()
+        );
+        assert_eq!(
+            Shortcut::from_str("NumLock").unwrap().normal_keys,
+            vec![VirtualKeyId::NumLock]
+        );
     }
 
     #[test]
@@ -446,6 +2212,16 @@ mod tests {
 
         let shortcut2 = Shortcut::from_str("Shift+Alt+T").unwrap();
         assert!(!shortcut1.is_match(&shortcut2));
+
+        let right = Shortcut::from_str("CtrlRight+T").unwrap();
+        let generic = Shortcut::from_str("Ctrl+T").unwrap();
+        let left = Shortcut::from_str("CtrlLeft+T").unwrap();
+        assert!(generic.is_match_with(&right, SidePolicy::StrictSides));
+        assert!(!left.is_match_with(&right, SidePolicy::StrictSides));
+        assert!(left.is_match_with(&right, SidePolicy::AnySide));
+        assert!(!generic.is_match_with(&right, SidePolicy::PreferLeft));
+        assert!(generic.is_match_with(&left, SidePolicy::PreferLeft));
+        assert!(right.is_match_with(&right, SidePolicy::PreferLeft));
     }
 
     #[test]
@@ -455,7 +2231,7 @@ mod tests {
         state.set_key(VirtualKeyId::Alt);
         state.set_key(VirtualKeyId::UsT);
 
-        assert_eq!(state.to_string(), "ControlLeft+Alt+UsT");
+        assert_eq!(state.to_string(), "CtrlLeft+Alt+T");
         assert_eq!(state, Shortcut::from_str("ControlLeft+Alt+UsT").unwrap());
         assert_eq!(state, Shortcut::from_str("Alt+ControlLeft+T").unwrap());
 
@@ -478,12 +2254,228 @@ mod tests {
             .is_match(&Shortcut::from_str("CtrlRight+ShiftLeft+C").unwrap()));
 
         state.remove_key(VirtualKeyId::ControlLeft);
-        assert_eq!(state.to_string(), "Alt+UsT");
+        assert_eq!(state.to_string(), "Alt+T");
 
         state.remove_key(VirtualKeyId::Alt);
-        assert_eq!(state.to_string(), "UsT");
+        assert_eq!(state.to_string(), "T");
 
         state.remove_key(VirtualKeyId::UsT);
         assert_eq!(state.to_string(), "");
     }
+
+    #[test]
+    fn test_canonical_string() {
+        assert_eq!(
+            Shortcut::from_str("Shift+Ctrl+A")
+                .unwrap()
+                .canonical_string(),
+            "Ctrl+Shift+A"
+        );
+        assert_eq!(
+            Shortcut::from_str("Ctrl+Shift+A")
+                .unwrap()
+                .canonical_string(),
+            "Ctrl+Shift+A"
+        );
+        assert_eq!(
+            Shortcut::from_str("Meta+AltRight+CtrlLeft+Ctrl+T+X")
+                .unwrap()
+                .canonical_string(),
+            "Ctrl+CtrlLeft+AltRight+Meta+T+X"
+        );
+
+        let canonical = Shortcut::from_str("Alt+Shift+Win+Z")
+            .unwrap()
+            .canonical_string();
+        assert_eq!(
+            Shortcut::from_str(&canonical).unwrap(),
+            Shortcut::from_str("Alt+Shift+Win+Z").unwrap()
+        );
+
+        for text in [
+            "Ctrl+Shift+A",
+            "CtrlLeft+AltRight+Digit5",
+            "Shift+Numpad5+F19",
+            "Ctrl+Equal",
+            "Meta+MouseLeft",
+        ] {
+            let shortcut: Shortcut = text.parse().unwrap();
+            assert_eq!(shortcut.to_string(), text);
+        }
+
+        use std::hash::{BuildHasher, RandomState};
+        let a = Shortcut::from_str("Shift+Ctrl+A").unwrap();
+        let b = Shortcut::from_str("Ctrl+Shift+A").unwrap();
+        assert_eq!(a.canonicalize().modifier_keys(), b.modifier_keys());
+        let hasher = RandomState::new();
+        assert_eq!(hasher.hash_one(&a), hasher.hash_one(&b));
+    }
+
+    #[test]
+    fn test_shortcut_format() {
+        let shortcut = Shortcut::from_str("Shift+Ctrl+A").unwrap();
+        assert_eq!(shortcut.format(ShortcutStyle::Canonical), "Ctrl+Shift+A");
+        assert_eq!(shortcut.format(ShortcutStyle::MacSymbols), "⌃⇧A");
+        assert_eq!(
+            Shortcut::from_str("Meta+AltRight+Alt+Enter")
+                .unwrap()
+                .format(ShortcutStyle::MacSymbols),
+            "⌥⌘↩"
+        );
+    }
+
+    #[test]
+    fn test_shortcut_sequence() {
+        let seq = ShortcutSequence::from_str("Ctrl+K, Ctrl+C").unwrap();
+        assert_eq!(seq.steps().len(), 2);
+        assert_eq!(seq.steps()[1], Shortcut::from_str("Ctrl+C").unwrap());
+        assert!(!seq.is_chord());
+
+        assert_eq!(
+            ShortcutSequence::from_str("G then I").unwrap(),
+            ShortcutSequence::from_str("G,I").unwrap()
+        );
+        assert!(ShortcutSequence::from_str("Ctrl+A").unwrap().is_chord());
+        assert!(ShortcutSequence::from_str("Ctrl+K,").is_err());
+
+        let comma = Shortcut::from_str("Ctrl+Comma").unwrap();
+        assert_eq!(
+            ShortcutSequence::from_str("Ctrl+,").unwrap().steps(),
+            [comma.clone()]
+        );
+        let seq = ShortcutSequence::from_str("Ctrl+K, Ctrl+,").unwrap();
+        assert_eq!(seq.steps()[1], comma);
+        assert_eq!(
+            ShortcutSequence::from_str(", then A")
+                .unwrap()
+                .steps()
+                .len(),
+            2
+        );
+        assert_eq!(
+            ShortcutSequence::from_str("Ctrl++, A")
+                .unwrap()
+                .steps()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_shortcut_overlaps() {
+        let overlaps = |a: &str, b: &str| {
+            ShortcutSequence::from_str(a)
+                .unwrap()
+                .overlaps(&ShortcutSequence::from_str(b).unwrap())
+        };
+        assert!(overlaps("Ctrl+C", "Ctrl+C+V"));
+        assert!(overlaps("Ctrl+C+V", "Ctrl+C"));
+        assert!(overlaps("Ctrl+A", "CtrlLeft+A"));
+        assert!(!overlaps("CtrlLeft+A", "CtrlRight+A"));
+        assert!(!overlaps("Ctrl+C", "Ctrl+Shift+C"));
+        assert!(!overlaps("Ctrl+C+V", "Ctrl+V+C"));
+        assert!(overlaps("Ctrl+K", "Ctrl+K, Ctrl+C"));
+        assert!(!overlaps("Ctrl+K, Ctrl+C", "Ctrl+K, Ctrl+D"));
+    }
+
+    #[test]
+    fn test_mouse_shortcut() {
+        let shortcut = Shortcut::from_str("Ctrl+MouseLeft").unwrap();
+        assert!(shortcut.has_modifier());
+        assert!(!shortcut.has_normal_key());
+        assert_eq!(shortcut.mouse_keys(), &[MouseKey::Left]);
+        assert_eq!(shortcut.canonical_string(), "Ctrl+MouseLeft");
+
+        let mut chord = Shortcut::new(vec![VirtualKeyId::ShiftLeft]).unwrap();
+        chord.set_mouse_key(MouseKey::WheelUp);
+        assert!(Shortcut::from_str("Shift+wheelup")
+            .unwrap()
+            .is_match(&chord));
+        assert!(!Shortcut::from_str("Shift+WheelDown")
+            .unwrap()
+            .is_match(&chord));
+        assert!(!Shortcut::from_str("Shift").unwrap().is_match(&chord));
+
+        assert_eq!(
+            MouseKey::from_wheel(&Pos { x: 0, y: -120 }),
+            Some(MouseKey::WheelDown)
+        );
+        assert_eq!(MouseKey::from_wheel(&Pos::default()), None);
+    }
+
+    #[test]
+    fn test_mouse_move_absorb() {
+        let mut first = MouseInfo {
+            pos: Pos { x: 10, y: 10 },
+            relative_pos: Pos { x: 2, y: 0 },
+            raw_delta: Pos { x: 4, y: 0 },
+            ..Default::default()
+        };
+        assert!(first.is_move());
+        first.absorb(MouseInfo {
+            pos: Pos { x: 13, y: 9 },
+            relative_pos: Pos { x: 3, y: -1 },
+            raw_delta: Pos { x: 6, y: -2 },
+            ..Default::default()
+        });
+        assert_eq!(first.pos, Pos { x: 13, y: 9 });
+        assert_eq!(first.relative_pos, Pos { x: 5, y: -1 });
+        assert_eq!(first.raw_delta, Pos { x: 10, y: -2 });
+
+        first.button = Some(MouseButton::Left(ClickState::Pressed));
+        assert!(!first.is_move());
+    }
+
+    #[test]
+    fn test_app_context() {
+        let window = WindowInfo {
+            title: "notes.txt - Notepad".to_string(),
+            class: "Notepad".to_string(),
+            process_name: "notepad.exe".to_string(),
+            ..Default::default()
+        };
+        assert!(AppContext::Process("Notepad.exe".to_string()).matches(&window));
+        assert!(AppContext::WindowClass("Notepad".to_string()).matches(&window));
+        assert!(!AppContext::WindowClass("notepad".to_string()).matches(&window));
+        assert!(AppContext::TitleContains("notes.txt".to_string()).matches(&window));
+        assert!(!AppContext::Process("code.exe".to_string()).matches(&window));
+    }
+
+    #[test]
+    fn test_key_snapshot() {
+        let mut snapshot = KeySnapshot::default();
+        assert!(snapshot.press(VirtualKeyId::ControlLeft));
+        assert!(snapshot.press(VirtualKeyId::UsC));
+        assert!(!snapshot.press(VirtualKeyId::UsC));
+        assert!(snapshot.press(VirtualKeyId::UsV));
+
+        assert_eq!(snapshot.modifiers, Modifiers::CONTROL_LEFT);
+        assert_eq!(snapshot.to_shortcut().to_string(), "CtrlLeft+C+V");
+        assert!(Shortcut::from_str("Ctrl+C+V").unwrap().matches(&snapshot));
+        assert!(!Shortcut::from_str("Ctrl+V+C").unwrap().matches(&snapshot));
+
+        assert!(snapshot.release(VirtualKeyId::ControlLeft));
+        assert!(!snapshot.release(VirtualKeyId::ControlLeft));
+        assert!(snapshot.modifiers.is_empty());
+        assert_eq!(snapshot.keys, vec![VirtualKeyId::UsC, VirtualKeyId::UsV]);
+
+        snapshot.locks.toggle(VirtualKeyId::CapsLock);
+        snapshot.locks.toggle(VirtualKeyId::UsC);
+        let mut key_info = KeyInfo::new(KeyId(VirtualKeyId::CapsLock), KeyState::Pressed);
+        assert_eq!(key_info.locks(), LockKeyState::default());
+        key_info.keyboard_state = Some(snapshot);
+        assert!(key_info.locks().caps);
+        assert!(!key_info.locks().num);
+    }
+
+    #[test]
+    fn test_callback_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("boom {}", 1)).unwrap_err();
+        assert_eq!(CallbackPanic::new(7, payload).message, "boom 1");
+        let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(
+            CallbackPanic::new(7, payload).to_string(),
+            "Callback 7 panicked: static"
+        );
+    }
 }
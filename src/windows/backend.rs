@@ -0,0 +1,23 @@
+//! The event source a `Listener` drives, picked at runtime with
+//! `ListenerBuilder::backend`.
+
+use crate::types::{HookStatus, KmHookError};
+use std::sync::Arc;
+
+pub(crate) trait InputBackend: Send + Sync + std::fmt::Debug {
+    /// Starts the event loop thread and returns once the hooks or devices the
+    /// listener needs are set up.
+    fn start(self: Arc<Self>) -> Result<(), KmHookError>;
+
+    fn stop(&self);
+
+    /// Runs the event loop on the calling thread until `stop`. Fails like
+    /// `start` if the hooks or devices cannot be set up.
+    fn run_blocking(self: Arc<Self>) -> Result<(), KmHookError>;
+
+    /// Posts one of the `WM_USER_*` loop messages to the event loop thread.
+    fn post_msg_to_loop(&self, msg_type: u32);
+
+    /// What is set up right now, for `Listener::dump_state`.
+    fn status(&self) -> HookStatus;
+}
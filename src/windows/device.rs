@@ -0,0 +1,95 @@
+//! Input devices as seen by raw input.
+
+use crate::types::{DeviceId, DeviceInfo, DeviceKind};
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::UI::Input::{
+    GetRawInputDeviceInfoW, GetRawInputDeviceList, RAWINPUTDEVICELIST, RIDI_DEVICEINFO,
+    RIDI_DEVICENAME, RID_DEVICE_INFO, RID_DEVICE_INFO_TYPE, RIM_TYPEKEYBOARD, RIM_TYPEMOUSE,
+};
+
+impl From<HANDLE> for DeviceId {
+    fn from(device: HANDLE) -> Self {
+        DeviceId(device.0 as isize)
+    }
+}
+
+/// The four hex digits after `prefix` ("VID_", "PID_") in a device path.
+fn hex_id(name: &str, prefix: &str) -> Option<u16> {
+    let name = name.to_ascii_uppercase();
+    let start = name.find(prefix)? + prefix.len();
+    u16::from_str_radix(name.get(start..start + 4)?, 16).ok()
+}
+
+fn device_name(device: HANDLE) -> Option<String> {
+    let mut len = 0u32;
+    unsafe { GetRawInputDeviceInfoW(device, RIDI_DEVICENAME, None, &mut len) };
+    if len == 0 {
+        return None;
+    }
+    let mut name = vec![0u16; len as usize];
+    let read = unsafe {
+        GetRawInputDeviceInfoW(
+            device,
+            RIDI_DEVICENAME,
+            Some(name.as_mut_ptr() as *mut std::ffi::c_void),
+            &mut len,
+        )
+    };
+    if read == u32::MAX {
+        return None;
+    }
+    let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    Some(String::from_utf16_lossy(&name[..end]))
+}
+
+/// Whether `device` is a keyboard, mouse or other HID device.
+pub(crate) fn device_type(device: HANDLE) -> Option<RID_DEVICE_INFO_TYPE> {
+    let mut info = RID_DEVICE_INFO {
+        cbSize: std::mem::size_of::<RID_DEVICE_INFO>() as u32,
+        ..Default::default()
+    };
+    let mut size = info.cbSize;
+    let read = unsafe {
+        GetRawInputDeviceInfoW(
+            device,
+            RIDI_DEVICEINFO,
+            Some(&mut info as *mut RID_DEVICE_INFO as *mut std::ffi::c_void),
+            &mut size,
+        )
+    };
+    (read != u32::MAX && read != 0).then_some(info.dwType)
+}
+
+pub(crate) fn device_info(device: HANDLE, kind: RID_DEVICE_INFO_TYPE) -> Option<DeviceInfo> {
+    let name = device_name(device)?;
+    Some(DeviceInfo {
+        id: device.into(),
+        kind: match kind {
+            RIM_TYPEKEYBOARD => DeviceKind::Keyboard,
+            RIM_TYPEMOUSE => DeviceKind::Mouse,
+            _ => DeviceKind::Hid,
+        },
+        vendor_id: hex_id(&name, "VID_"),
+        product_id: hex_id(&name, "PID_"),
+        name,
+    })
+}
+
+/// Keyboards, mice and other HID devices currently connected.
+pub fn devices() -> Vec<DeviceInfo> {
+    let entry_size = std::mem::size_of::<RAWINPUTDEVICELIST>() as u32;
+    let mut count = 0u32;
+    if unsafe { GetRawInputDeviceList(None, &mut count, entry_size) } == u32::MAX {
+        return Vec::new();
+    }
+    let mut list = vec![RAWINPUTDEVICELIST::default(); count as usize];
+    let read = unsafe { GetRawInputDeviceList(Some(list.as_mut_ptr()), &mut count, entry_size) };
+    // A device plugged in between the two calls fails the second.
+    if read == u32::MAX {
+        return Vec::new();
+    }
+    list.truncate(read as usize);
+    list.iter()
+        .filter_map(|device| device_info(device.hDevice, device.dwType))
+        .collect()
+}
@@ -0,0 +1,404 @@
+//! Thread-specific `WH_KEYBOARD`/`WH_MOUSE` hooks on a thread of another
+//! process, set from the `kmhook-winhook` DLL (`dll-hook`). The hook procs
+//! run inside the target and post what they see to a message-only window
+//! of the event loop thread; see `dll_hook_abi`.
+
+use super::dll_hook_abi::{
+    sink_name, unpack_mouse, KEYBOARD_PROC, MOUSE_PROC, SINK_CLASS, WM_DLL_HOOK_KEYBOARD,
+    WM_DLL_HOOK_MOUSE,
+};
+use crate::types::{
+    ClickState, HookStatus, KeyId, KeyInfo, KeyState, KmHookError, MouseButton, MouseInfo,
+    MouseStateFlags, Pos,
+};
+use crate::windows::backend::InputBackend;
+use crate::windows::worker::{KeyboardSysMsg, MouseSysMsg, Routes, ThreadSpec, WorkerMsg};
+use crate::windows::{set_thread_dpi_aware, update_key_snapshot, WM_USER_RECHECK_HOOK};
+use crate::Listener;
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::thread;
+use windows::core::{PCSTR, PCWSTR};
+use windows::Win32::Foundation::{FreeLibrary, HMODULE, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress, LoadLibraryW};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageTime, GetMessageW,
+    PostThreadMessageW, RegisterClassW, SetWindowsHookExW, TranslateMessage, UnhookWindowsHookEx,
+    HHOOK, HOOKPROC, HWND_MESSAGE, KBDLLHOOKSTRUCT, KBDLLHOOKSTRUCT_FLAGS, LLKHF_EXTENDED, MSG,
+    WH_KEYBOARD, WH_MOUSE, WINDOWS_HOOK_ID, WINDOW_EX_STYLE, WINDOW_STYLE, WM_LBUTTONDOWN,
+    WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEWHEEL, WM_NCMOUSEMOVE,
+    WM_NCXBUTTONDBLCLK, WM_QUIT, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_USER, WM_XBUTTONDOWN,
+    WM_XBUTTONUP, WNDCLASSW, XBUTTON1,
+};
+
+/// What the event loop thread owns while it runs.
+#[derive(Default)]
+struct Hooks {
+    module: Option<HMODULE>,
+    sink: Option<HWND>,
+    keyboard: Option<HHOOK>,
+    mouse: Option<HHOOK>,
+}
+
+thread_local! {
+    static LOCAL_HOOKS: RefCell<Hooks> = RefCell::new(Hooks::default());
+    static LOCAL_ROUTES: RefCell<Routes> = RefCell::new(Routes::default());
+    static LOCAL_MOUSE_LAST_POS: RefCell<Option<Pos>> = RefCell::new(None);
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+unsafe extern "system" fn sink_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+#[derive(Debug)]
+pub(crate) struct DllHook {
+    dll: PathBuf,
+    thread_id: u32,
+    loop_thread_id: Mutex<u32>,
+    thread_handle: Mutex<Option<thread::JoinHandle<()>>>,
+    listener: Weak<Listener>,
+}
+
+impl DllHook {
+    pub(crate) fn new(dll: PathBuf, thread_id: u32, listener: &Arc<Listener>) -> Self {
+        Self {
+            dll,
+            thread_id,
+            loop_thread_id: Mutex::new(0),
+            thread_handle: Mutex::new(None),
+            listener: Arc::downgrade(listener),
+        }
+    }
+
+    /// A keystroke as seen by `KeyboardProc`: the scan code and extended
+    /// flag live in the keystroke flags, bit 31 is set on release.
+    fn key_info(vk: u32, flags: isize) -> Option<KeyInfo> {
+        let flags = flags as u32;
+        let extended = flags & (1 << 24) != 0;
+        let scancode = (flags >> 16) & 0xFF;
+        let key_id = KeyId::try_from(KBDLLHOOKSTRUCT {
+            vkCode: vk,
+            scanCode: scancode,
+            flags: if extended {
+                LLKHF_EXTENDED
+            } else {
+                KBDLLHOOKSTRUCT_FLAGS(0)
+            },
+            ..Default::default()
+        })
+        .ok()?;
+        let state = if flags & (1 << 31) != 0 {
+            KeyState::Released
+        } else {
+            KeyState::Pressed
+        };
+        let mut key_info = KeyInfo::new(key_id, state);
+        key_info.scancode = scancode;
+        key_info.vk = vk;
+        key_info.is_extended = extended;
+        key_info.time = unsafe { GetMessageTime() } as u32;
+        if !update_key_snapshot(&mut key_info) {
+            if key_info.state == KeyState::Released {
+                return None;
+            }
+            key_info.is_repeat = true;
+        }
+        Some(key_info)
+    }
+
+    fn mouse_info(wparam: usize, lparam: isize) -> MouseInfo {
+        let (msg, data, x, y) = unpack_mouse(wparam, lparam);
+        // Non-client messages mirror the client ones 0x160 lower.
+        let msg = if (WM_NCMOUSEMOVE..=WM_NCXBUTTONDBLCLK).contains(&msg) {
+            msg + 0x160
+        } else {
+            msg
+        };
+        let x_button = |state| {
+            if data == XBUTTON1 {
+                MouseButton::X1(state)
+            } else {
+                MouseButton::X2(state)
+            }
+        };
+        let button = match msg {
+            WM_LBUTTONDOWN => Some(MouseButton::Left(ClickState::Pressed)),
+            WM_LBUTTONUP => Some(MouseButton::Left(ClickState::Released)),
+            WM_RBUTTONDOWN => Some(MouseButton::Right(ClickState::Pressed)),
+            WM_RBUTTONUP => Some(MouseButton::Right(ClickState::Released)),
+            WM_MBUTTONDOWN => Some(MouseButton::Middle(ClickState::Pressed)),
+            WM_MBUTTONUP => Some(MouseButton::Middle(ClickState::Released)),
+            WM_XBUTTONDOWN => Some(x_button(ClickState::Pressed)),
+            WM_XBUTTONUP => Some(x_button(ClickState::Released)),
+            _ => None,
+        };
+        let wheel = match msg {
+            WM_MOUSEWHEEL => Pos {
+                x: 0,
+                y: data as i16 as i32,
+            },
+            WM_MOUSEHWHEEL => Pos {
+                x: data as i16 as i32,
+                y: 0,
+            },
+            _ => Pos::default(),
+        };
+
+        let pos = Pos { x, y };
+        let relative_pos = LOCAL_MOUSE_LAST_POS.with_borrow_mut(|last| {
+            let rel = last.as_ref().map_or(Pos::default(), |last| Pos {
+                x: pos.x - last.x,
+                y: pos.y - last.y,
+            });
+            last.replace(pos.clone());
+            rel
+        });
+
+        let mut flags = MouseStateFlags::empty();
+        if let Some(button) = &button {
+            flags |= if button.state() == ClickState::Pressed {
+                MouseStateFlags::PRESSED
+            } else {
+                MouseStateFlags::RELEASED
+            };
+        }
+
+        MouseInfo {
+            button,
+            pos,
+            relative_pos,
+            wheel,
+            flags,
+            time: unsafe { GetMessageTime() } as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Loads the DLL and creates the window its hook procs post to.
+    fn init(&self) -> Result<(), KmHookError> {
+        let dll = wide(&self.dll.to_string_lossy());
+        let module = unsafe { LoadLibraryW(PCWSTR(dll.as_ptr())) }
+            .map_err(|e| KmHookError::HookInstall(format!("{}: {}", self.dll.display(), e)))?;
+        LOCAL_HOOKS.with_borrow_mut(|hooks| hooks.module = Some(module));
+
+        let hinstance = unsafe { GetModuleHandleW(None) }
+            .map_err(|e| KmHookError::WindowCreation(e.to_string()))?
+            .into();
+        let class_name = wide(SINK_CLASS);
+        let window_name = wide(&sink_name(self.thread_id));
+        let wnd_class = WNDCLASSW {
+            lpfnWndProc: Some(sink_proc),
+            hInstance: hinstance,
+            lpszClassName: PCWSTR(class_name.as_ptr()),
+            ..Default::default()
+        };
+        let sink = unsafe {
+            let _ = RegisterClassW(&wnd_class);
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                PCWSTR(class_name.as_ptr()),
+                PCWSTR(window_name.as_ptr()),
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                HWND_MESSAGE,
+                None,
+                hinstance,
+                None,
+            )
+        }
+        .map_err(|e| KmHookError::WindowCreation(e.to_string()))?;
+        LOCAL_HOOKS.with_borrow_mut(|hooks| hooks.sink = Some(sink));
+        Ok(())
+    }
+
+    fn uninit(&self) {
+        let hooks = LOCAL_HOOKS.take();
+        unsafe {
+            for hhook in [hooks.keyboard, hooks.mouse].into_iter().flatten() {
+                let _ = UnhookWindowsHookEx(hhook);
+            }
+            if let Some(sink) = hooks.sink {
+                let _ = DestroyWindow(sink);
+            }
+            if let Some(module) = hooks.module {
+                let _ = FreeLibrary(module);
+            }
+        }
+    }
+
+    fn set_hook(&self, kind: WINDOWS_HOOK_ID) -> Result<HHOOK, KmHookError> {
+        let name = if kind == WH_KEYBOARD {
+            KEYBOARD_PROC
+        } else {
+            MOUSE_PROC
+        };
+        let module = LOCAL_HOOKS
+            .with_borrow(|hooks| hooks.module)
+            .ok_or_else(|| KmHookError::HookInstall("DLL not loaded".to_string()))?;
+        let proc: HOOKPROC = unsafe {
+            GetProcAddress(module, PCSTR(name.as_ptr())).map(|proc| std::mem::transmute(proc))
+        };
+        if proc.is_none() {
+            return Err(KmHookError::HookInstall(format!(
+                "{} does not export {}",
+                self.dll.display(),
+                String::from_utf8_lossy(&name[..name.len() - 1])
+            )));
+        }
+        unsafe { SetWindowsHookExW(kind, proc, module, self.thread_id) }
+            .map_err(|e| KmHookError::HookInstall(e.to_string()))
+    }
+
+    fn recheck_hook(&self) -> Result<(), KmHookError> {
+        let Some(listener) = self.listener.upgrade() else {
+            return Ok(());
+        };
+        let routes = listener.routes();
+        LOCAL_ROUTES.with_borrow_mut(|local| *local = routes);
+
+        for (kind, wanted) in [
+            (WH_KEYBOARD, listener.has_keyboard_event()),
+            (WH_MOUSE, listener.has_mouse_event()),
+        ] {
+            let installed = LOCAL_HOOKS.with_borrow_mut(|hooks| {
+                if kind == WH_KEYBOARD {
+                    &mut hooks.keyboard
+                } else {
+                    &mut hooks.mouse
+                }
+                .take()
+            });
+            let hhook = match (installed, wanted) {
+                (Some(hhook), true) => Some(hhook),
+                (Some(hhook), false) => {
+                    let _ = unsafe { UnhookWindowsHookEx(hhook) };
+                    None
+                }
+                (None, true) => Some(self.set_hook(kind)?),
+                (None, false) => None,
+            };
+            LOCAL_HOOKS.with_borrow_mut(|hooks| {
+                if kind == WH_KEYBOARD {
+                    hooks.keyboard = hhook;
+                } else {
+                    hooks.mouse = hhook;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn post_msg_to_worker(msg: WorkerMsg) {
+        LOCAL_ROUTES.with_borrow_mut(|routes| routes.send(msg));
+    }
+
+    fn run(&self, ready: mpsc::Sender<Result<(), KmHookError>>) {
+        *self.loop_thread_id.lock().unwrap() = unsafe { GetCurrentThreadId() };
+        set_thread_dpi_aware();
+
+        let result = self.init().and_then(|_| self.recheck_hook());
+        let failed = result.is_err();
+        let _ = ready.send(result);
+        if failed {
+            self.uninit();
+            *self.loop_thread_id.lock().unwrap() = 0;
+            return;
+        }
+
+        let mut msg = MSG::default();
+        unsafe {
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                match msg.message {
+                    WM_USER if msg.wParam.0 as u32 == WM_USER_RECHECK_HOOK => {
+                        if let Err(_e) = self.recheck_hook() {
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(error = ?_e, "recheck_hook failed");
+                        }
+                    }
+                    WM_DLL_HOOK_KEYBOARD => {
+                        if let Some(key_info) = Self::key_info(msg.wParam.0 as u32, msg.lParam.0) {
+                            Self::post_msg_to_worker(WorkerMsg::KeyboardEvent(
+                                KeyboardSysMsg::new(key_info),
+                            ));
+                        }
+                    }
+                    WM_DLL_HOOK_MOUSE => {
+                        let mouse_info = Self::mouse_info(msg.wParam.0, msg.lParam.0);
+                        Self::post_msg_to_worker(WorkerMsg::MouseEvent(MouseSysMsg::new(
+                            mouse_info,
+                        )));
+                    }
+                    _ => {
+                        let _ = TranslateMessage(&msg);
+                        DispatchMessageW(&msg);
+                    }
+                }
+            }
+        }
+        self.uninit();
+    }
+}
+
+impl InputBackend for DllHook {
+    /// Returns once the DLL is loaded and the hooks are set in the target.
+    fn start(self: Arc<Self>) -> Result<(), KmHookError> {
+        let event_loop = Arc::clone(&self);
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let handle = ThreadSpec::event_loop(&self.listener)
+            .spawn(move || event_loop.run(ready_tx))
+            .map_err(|e| KmHookError::Thread(e.to_string()))?;
+        self.thread_handle.lock().unwrap().replace(handle);
+        ready_rx
+            .recv()
+            .map_err(|e| KmHookError::Thread(e.to_string()))?
+    }
+
+    fn stop(&self) {
+        let thread_id = std::mem::take(&mut *self.loop_thread_id.lock().unwrap());
+        if thread_id != 0 {
+            let _ = unsafe { PostThreadMessageW(thread_id, WM_QUIT, None, None) };
+        }
+    }
+
+    fn run_blocking(self: Arc<Self>) -> Result<(), KmHookError> {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        self.run(ready_tx);
+        ready_rx
+            .recv()
+            .map_err(|e| KmHookError::Thread(e.to_string()))?
+    }
+
+    fn post_msg_to_loop(&self, msg_type: u32) {
+        let thread_id = *self.loop_thread_id.lock().unwrap();
+        if thread_id != 0 {
+            let _ =
+                unsafe { PostThreadMessageW(thread_id, WM_USER, WPARAM(msg_type as usize), None) };
+        }
+    }
+
+    /// The hooks live in the target thread; they are set while the loop runs.
+    fn status(&self) -> HookStatus {
+        let thread_id = *self.loop_thread_id.lock().unwrap();
+        HookStatus {
+            running: thread_id != 0,
+            keyboard: thread_id != 0,
+            mouse: thread_id != 0,
+            loop_thread_id: (thread_id != 0).then_some(thread_id),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! What the hook procs of the `kmhook-winhook` DLL send to the `dll-hook`
+//! backend. The DLL includes this file through `#[path]`, so it may not
+//! depend on either crate.
+
+/// Hook procs the DLL exports, as passed to `GetProcAddress`.
+pub(crate) const KEYBOARD_PROC: &[u8] = b"kmhook_keyboard_proc\0";
+pub(crate) const MOUSE_PROC: &[u8] = b"kmhook_mouse_proc\0";
+
+/// Class of the message-only window the backend receives input on.
+pub(crate) const SINK_CLASS: &str = "kmhook_dll_hook_sink";
+
+/// Posted to the sink for each keystroke. wParam is the virtual key and
+/// lParam the keystroke flags, both as the `KeyboardProc` got them.
+pub(crate) const WM_DLL_HOOK_KEYBOARD: u32 = 0x8000 + 1;
+/// Posted to the sink for each mouse message; see `pack_mouse`.
+pub(crate) const WM_DLL_HOOK_MOUSE: u32 = 0x8000 + 2;
+
+/// Window name of the sink for the hooked thread `thread_id`, which is how
+/// the hook procs find it.
+pub(crate) fn sink_name(thread_id: u32) -> String {
+    format!("kmhook_dll_hook_{}", thread_id)
+}
+
+/// Mouse message and high word of `mouseData` in wParam, screen position in
+/// lParam.
+pub(crate) fn pack_mouse(msg: u32, mouse_data: u32, x: i32, y: i32) -> (usize, isize) {
+    let wparam = (msg & 0xFFFF) as usize | ((mouse_data >> 16) as usize) << 16;
+    let lparam = (x as u16 as u32 | (y as u16 as u32) << 16) as i32 as isize;
+    (wparam, lparam)
+}
+
+/// Inverse of `pack_mouse`: message, high word of `mouseData`, x and y.
+pub(crate) fn unpack_mouse(wparam: usize, lparam: isize) -> (u32, u16, i32, i32) {
+    (
+        (wparam & 0xFFFF) as u32,
+        (wparam >> 16) as u16,
+        lparam as i16 as i32,
+        (lparam >> 16) as i16 as i32,
+    )
+}
@@ -1,31 +1,63 @@
-// depreated
+//! Low-level hook backend (`WH_KEYBOARD_LL` / `WH_MOUSE_LL`).
 
-use crate::types::ID;
+use crate::consts;
+use crate::types::{
+    ClickState, Event, HookKind, HookStatus, KeyId, KeyInfo, KeyState, KmHookError, MouseButton,
+    MouseInfo, MouseStateFlags, Pos, ID,
+};
 use crate::utils::gen_id;
-use crate::windows::worker::{KeyboardSysMsg, MouseSysMsg, WorkerMsg};
-use crate::windows::WM_USER_RECHECK_HOOK;
+use crate::windows::backend::InputBackend;
+use crate::windows::worker::{KeyboardSysMsg, MouseSysMsg, Routes, ThreadSpec, WorkerMsg};
+use crate::windows::{hotkey, layout};
+use crate::windows::{
+    monitor, power, set_thread_dpi_aware, update_key_snapshot, window, WM_USER_POWER_RESUME,
+    WM_USER_RECHECK_HOOK,
+};
 use crate::Listener;
 
 use lazy_static::lazy_static;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::{mpsc, Arc, Mutex, Weak};
 use std::thread;
-use windows::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
+use windows::Win32::Foundation::{LPARAM, LRESULT, POINT, WPARAM};
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
-use windows::Win32::System::Threading::{
-    GetCurrentThread, GetCurrentThreadId, SetThreadPriority, THREAD_PRIORITY_TIME_CRITICAL,
-};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
 use windows::Win32::UI::WindowsAndMessaging::{
-    CallNextHookEx, DispatchMessageW, GetMessageW, PostThreadMessageW, SetWindowsHookExW,
-    TranslateMessage, UnhookWindowsHookEx, HC_ACTION, HHOOK, KBDLLHOOKSTRUCT, KF_REPEAT, KF_UP,
-    MSG, MSLLHOOKSTRUCT, WH_KEYBOARD_LL, WH_MOUSE_LL, WM_QUIT, WM_USER,
+    CallNextHookEx, DispatchMessageW, GetCursorPos, GetMessageW, KillTimer, PostThreadMessageW,
+    SetTimer, SetWindowsHookExW, TranslateMessage, UnhookWindowsHookEx, HC_ACTION, HHOOK,
+    KBDLLHOOKSTRUCT, LLKHF_EXTENDED, LLKHF_INJECTED, LLKHF_UP, LLMHF_INJECTED, MSG, MSLLHOOKSTRUCT,
+    WH_KEYBOARD_LL, WH_MOUSE_LL, WM_HOTKEY, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
+    WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEWHEEL, WM_QUIT, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_TIMER,
+    WM_USER, WM_XBUTTONDOWN, WM_XBUTTONUP, XBUTTON1,
 };
 
 thread_local! {
     static LOCAL_KEYBOARD_HHOOK: RefCell<HashMap<ID, HHOOK>> = RefCell::new(HashMap::new());
     static LOCAL_MOUSE_HHOOK: RefCell<HashMap<ID, HHOOK>> = RefCell::new(HashMap::new());
-    static LOCAL_KEY_LAST_TIME: RefCell<u32> = RefCell::new(0);
+    static LOCAL_MOUSE_LAST_POS: RefCell<Option<Pos>> = RefCell::new(None);
+    /// Where the hook procs on this thread deliver events, refreshed by
+    /// `recheck_hook`; keeps the global manager lock off the hot path.
+    static LOCAL_ROUTES: RefCell<Routes> = RefCell::new(Routes::default());
+    #[cfg(feature = "suppression")]
+    static LOCAL_LISTENER: RefCell<Weak<Listener>> = RefCell::new(Weak::new());
+    /// Tick count of the newest input a hook on this thread saw.
+    static LOCAL_LAST_HOOK_TIME: Cell<u32> = const { Cell::new(0) };
+    static LOCAL_WATCHDOG_CURSOR: Cell<Option<(i32, i32)>> = const { Cell::new(None) };
+    /// Keys whose press completed a swallowing shortcut; their repeats and
+    /// release are swallowed too so the application never sees half a keystroke.
+    #[cfg(feature = "suppression")]
+    static LOCAL_SWALLOWED_KEYS: RefCell<std::collections::HashSet<KeyId>> =
+        RefCell::new(std::collections::HashSet::new());
+    /// Mouse buttons pressed during a `block_input`, whose release is
+    /// swallowed too.
+    #[cfg(feature = "suppression")]
+    /// A key was swallowed while Win was held; its release needs masking.
+    #[cfg(feature = "suppression")]
+    static LOCAL_WIN_MASK: Cell<bool> = const { Cell::new(false) };
+    static LOCAL_SWALLOWED_BUTTONS: RefCell<std::collections::HashSet<crate::types::MouseKey>> =
+        RefCell::new(std::collections::HashSet::new());
 }
 
 #[derive(Debug)]
@@ -63,46 +95,88 @@ impl EventLoop {
         }
 
         let kb = &*(lparam.0 as *const usize as *const KBDLLHOOKSTRUCT);
-        let is_repeat = kb.flags.0 & KF_REPEAT;
-        let is_up = kb.flags.0 & KF_UP;
-        println!("is_repeat {:?}, is_up: {:?}", is_repeat, is_up);
-        // let mut is_repeat = false;
-        // LOCAL_KEY_LAST_TIME.with(|last_time| {
-        //     let mut last_time = last_time.borrow_mut();
-        //     let current_time = kb.time;
-        //     if *last_time == current_time {
-        //         is_repeat = true;
-        //     }
-        //     *last_time = current_time;
-        // });
-        // if is_repeat {
-        //     println!(
-        //         "{:?} keyboard_hook_proc is repeat {:?}",
-        //         std::thread::current().id(),
-        //         kb
-        //     );
-        //     return CallNextHookEx(None, ncode, wparam, lparam);
-        // }
-
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} keyboard_hook_proc trigger {:?}",
-            std::thread::current().id(),
-            kb
+        LOCAL_LAST_HOOK_TIME.set(kb.time);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?kb, "keyboard_hook_proc trigger");
+
+        let Ok(key_id) = KeyId::try_from(*kb) else {
+            return CallNextHookEx(None, ncode, wparam, lparam);
+        };
+        let mut key_info = KeyInfo::new(
+            key_id,
+            if kb.flags.0 & LLKHF_UP.0 != 0 {
+                KeyState::Released
+            } else {
+                KeyState::Pressed
+            },
         );
+        key_info.scancode = kb.scanCode;
+        key_info.vk = kb.vkCode;
+        key_info.is_extended = kb.flags.0 & LLKHF_EXTENDED.0 != 0;
+        key_info.injected = kb.flags.0 & LLKHF_INJECTED.0 != 0;
+        key_info.time = kb.time;
+        // A press that leaves the snapshot unchanged is an auto-repeat.
+        if !update_key_snapshot(&mut key_info) {
+            if key_info.state == KeyState::Released {
+                return CallNextHookEx(None, ncode, wparam, lparam);
+            }
+            key_info.is_repeat = true;
+        }
+        // The hook runs before the system toggles a lock key; report the new state.
+        if key_info.state == KeyState::Pressed && !key_info.is_repeat {
+            if let Some(snapshot) = key_info.keyboard_state.as_mut() {
+                snapshot.locks.toggle(key_id.0);
+            }
+        }
+
+        #[cfg(feature = "suppression")]
+        let swallow = match key_info.state {
+            KeyState::Pressed if key_info.is_repeat => {
+                LOCAL_SWALLOWED_KEYS.with(|keys| keys.borrow().contains(&key_id))
+            }
+            KeyState::Pressed => {
+                let swallow = LOCAL_LISTENER.with_borrow(|listener| {
+                    listener.upgrade().map_or(false, |listener| {
+                        listener.blocks_input(Some(&key_info)) || listener.swallows(&key_info)
+                    })
+                });
+                if swallow {
+                    LOCAL_SWALLOWED_KEYS.with(|keys| keys.borrow_mut().insert(key_id));
+                }
+                swallow
+            }
+            KeyState::Released => {
+                LOCAL_SWALLOWED_KEYS.with(|keys| keys.borrow_mut().remove(&key_id))
+            }
+        };
+        // With the key of a Win+<key> chord swallowed, the system sees Win
+        // pressed and released alone and opens the Start menu.
+        #[cfg(feature = "suppression")]
+        if matches!(
+            key_id.0,
+            crate::types::VirtualKeyId::MetaLeft | crate::types::VirtualKeyId::MetaRight
+        ) {
+            if key_info.state == KeyState::Released && !swallow && LOCAL_WIN_MASK.replace(false) {
+                Self::mask_win_release();
+            }
+        } else if swallow
+            && key_info.keyboard_state.as_ref().map_or(false, |snapshot| {
+                snapshot.modifiers.intersects(crate::types::Modifiers::META)
+            })
+        {
+            LOCAL_WIN_MASK.set(true);
+        }
 
-        let msg = WorkerMsg::KeyboardEvent(KeyboardSysMsg::new(wparam.0 as u32, *kb));
+        Self::post_msg_to_worker(WorkerMsg::KeyboardEvent(KeyboardSysMsg::new(key_info)));
 
-        let event_loops = { EVENT_LOOP_MANAGER.lock().unwrap().get_keyboard_event_loop() };
-        for event_loop in event_loops.iter() {
-            event_loop.post_msg_to_worker(msg.clone());
+        #[cfg(feature = "suppression")]
+        if swallow {
+            return LRESULT(1);
         }
 
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} keyboard_hook_proc trigger end call next",
-            std::thread::current().id()
-        );
+        #[cfg(feature = "tracing")]
+        tracing::trace!("keyboard_hook_proc trigger end call next");
 
         CallNextHookEx(None, ncode, wparam, lparam)
     }
@@ -113,83 +187,168 @@ impl EventLoop {
         lparam: LPARAM,
     ) -> LRESULT {
         if ncode == HC_ACTION.try_into().unwrap() {
-            let mtype = wparam.0 as u32;
             let minfo = &*(lparam.0 as *const usize as *const MSLLHOOKSTRUCT);
+            LOCAL_LAST_HOOK_TIME.set(minfo.time);
+
+            #[cfg(feature = "tracing")]
+            tracing::trace!(?minfo, "mouse_hook_proc trigger");
+
+            let mouse_info = Self::mouse_info(wparam.0 as u32, minfo);
+            // Releases of buttons held when a block starts pass so they come
+            // up; those of buttons pressed during it are swallowed, even once
+            // it has ended.
+            #[cfg(feature = "suppression")]
+            let swallow = match &mouse_info.button {
+                Some(button) if button.state() == ClickState::Released => LOCAL_SWALLOWED_BUTTONS
+                    .with_borrow_mut(|buttons| {
+                        buttons.remove(&crate::types::MouseKey::from(button))
+                    }),
+                button => {
+                    let swallow = LOCAL_LISTENER.with_borrow(|listener| {
+                        listener
+                            .upgrade()
+                            .map_or(false, |listener| listener.blocks_input(None))
+                    });
+                    if let (true, Some(button)) = (swallow, button) {
+                        LOCAL_SWALLOWED_BUTTONS.with_borrow_mut(|buttons| {
+                            buttons.insert(crate::types::MouseKey::from(button))
+                        });
+                    }
+                    swallow
+                }
+            };
+            Self::post_msg_to_worker(WorkerMsg::MouseEvent(MouseSysMsg::new(mouse_info)));
 
-            #[cfg(feature = "Debug")]
-            println!(
-                "{:?} mouse_hook_proc trigger {:?}",
-                std::thread::current().id(),
-                minfo
-            );
+            #[cfg(feature = "suppression")]
+            if swallow {
+                return LRESULT(1);
+            }
 
-            let msg = WorkerMsg::MouseEvent(MouseSysMsg::new(mtype, *minfo));
+            #[cfg(feature = "tracing")]
+            tracing::trace!("mouse_hook_proc trigger end call next");
+        }
+        CallNextHookEx(None, ncode, wparam, lparam)
+    }
 
-            let event_loops = { EVENT_LOOP_MANAGER.lock().unwrap().get_mouse_event_loop() };
-            for event_loop in event_loops.iter() {
-                event_loop.post_msg_to_worker(msg.clone());
+    fn mouse_info(mtype: u32, minfo: &MSLLHOOKSTRUCT) -> MouseInfo {
+        let x_button = |state| {
+            if (minfo.mouseData >> 16) as u16 == XBUTTON1 {
+                MouseButton::X1(state)
+            } else {
+                MouseButton::X2(state)
             }
+        };
+        let button = match mtype {
+            WM_LBUTTONDOWN => Some(MouseButton::Left(ClickState::Pressed)),
+            WM_LBUTTONUP => Some(MouseButton::Left(ClickState::Released)),
+            WM_RBUTTONDOWN => Some(MouseButton::Right(ClickState::Pressed)),
+            WM_RBUTTONUP => Some(MouseButton::Right(ClickState::Released)),
+            WM_MBUTTONDOWN => Some(MouseButton::Middle(ClickState::Pressed)),
+            WM_MBUTTONUP => Some(MouseButton::Middle(ClickState::Released)),
+            WM_XBUTTONDOWN => Some(x_button(ClickState::Pressed)),
+            WM_XBUTTONUP => Some(x_button(ClickState::Released)),
+            _ => None,
+        };
+
+        // The rotation is the signed high word of mouseData.
+        let wheel_delta = (minfo.mouseData >> 16) as i16 as i32;
+        let wheel = match mtype {
+            WM_MOUSEWHEEL => Pos {
+                x: 0,
+                y: wheel_delta,
+            },
+            WM_MOUSEHWHEEL => Pos {
+                x: wheel_delta,
+                y: 0,
+            },
+            _ => Pos::default(),
+        };
+
+        let pos = Pos {
+            x: minfo.pt.x,
+            y: minfo.pt.y,
+        };
+        let relative_pos = LOCAL_MOUSE_LAST_POS.with_borrow_mut(|last| {
+            let rel = last.as_ref().map_or(Pos::default(), |last| Pos {
+                x: pos.x - last.x,
+                y: pos.y - last.y,
+            });
+            last.replace(pos.clone());
+            rel
+        });
 
-            #[cfg(feature = "Debug")]
-            println!(
-                "{:?} mouse_hook_proc trigger end call next",
-                std::thread::current().id()
-            );
+        let mut flags = MouseStateFlags::from_extra_info(minfo.dwExtraInfo);
+        if minfo.flags & LLMHF_INJECTED != 0 {
+            flags |= MouseStateFlags::INJECTED;
+        }
+        if let Some(button) = &button {
+            flags |= if button.state() == ClickState::Pressed {
+                MouseStateFlags::PRESSED
+            } else {
+                MouseStateFlags::RELEASED
+            };
+        }
+
+        MouseInfo {
+            button,
+            pos,
+            relative_pos,
+            wheel,
+            flags,
+            time: minfo.time,
+            ..Default::default()
         }
-        CallNextHookEx(None, ncode, wparam, lparam)
     }
 
-    fn set_keyboard_hook(&self) {
+    fn set_keyboard_hook(&self) -> Result<(), KmHookError> {
         if LOCAL_KEYBOARD_HHOOK.with_borrow(|ids| ids.contains_key(&self.id)) {
-            return;
+            return Ok(());
         }
-        if let Ok(hhook) = unsafe {
-            let handle = GetModuleHandleW(None).unwrap();
+        let hhook = unsafe {
+            let handle =
+                GetModuleHandleW(None).map_err(|e| KmHookError::HookInstall(e.to_string()))?;
             SetWindowsHookExW(WH_KEYBOARD_LL, Some(Self::keyboard_hook_proc), handle, 0)
-        } {
-            #[cfg(feature = "Debug")]
-            println!(
-                "{:?} set_keyboard_hook {:?}",
-                std::thread::current().id(),
-                hhook
-            );
-
-            LOCAL_KEYBOARD_HHOOK.with_borrow_mut(|ids| {
-                ids.insert(self.id, hhook);
-            });
-            EVENT_LOOP_MANAGER
-                .lock()
-                .unwrap()
-                .add_keyboard_event(self.id);
         }
+        .map_err(|e| KmHookError::HookInstall(e.to_string()))?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?hhook, "set_keyboard_hook");
+
+        LOCAL_KEYBOARD_HHOOK.with_borrow_mut(|ids| {
+            ids.insert(self.id, hhook);
+        });
+        Self::reset_hook_time();
+        EVENT_LOOP_MANAGER
+            .lock()
+            .unwrap()
+            .add_keyboard_event(self.id);
+        Ok(())
     }
 
-    fn set_mouse_hook(&self) {
+    fn set_mouse_hook(&self) -> Result<(), KmHookError> {
         if LOCAL_MOUSE_HHOOK.with_borrow(|ids| ids.contains_key(&self.id)) {
-            return;
-        }
-        if let Ok(hhook) =
-            unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(Self::mouse_hook_proc), None, 0) }
-        {
-            #[cfg(feature = "Debug")]
-            println!(
-                "{:?} set_mouse_hook {:?}",
-                std::thread::current().id(),
-                hhook
-            );
-
-            LOCAL_MOUSE_HHOOK.with_borrow_mut(|ids| {
-                ids.insert(self.id, hhook);
-            });
-            EVENT_LOOP_MANAGER.lock().unwrap().add_mouse_event(self.id);
+            return Ok(());
         }
+        let hhook = unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(Self::mouse_hook_proc), None, 0) }
+            .map_err(|e| KmHookError::HookInstall(e.to_string()))?;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?hhook, "set_mouse_hook");
+
+        LOCAL_MOUSE_HHOOK.with_borrow_mut(|ids| {
+            ids.insert(self.id, hhook);
+        });
+        Self::reset_hook_time();
+        EVENT_LOOP_MANAGER.lock().unwrap().add_mouse_event(self.id);
+        Ok(())
     }
 
     fn unhook_keyboard(&self) {
         LOCAL_KEYBOARD_HHOOK.with_borrow_mut(|ids| {
             if let Some(hhook) = ids.remove(&self.id) {
                 unsafe {
-                    println!("unhook_keyboard {:?}", hhook);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(?hhook, "unhook_keyboard");
                     let _ = UnhookWindowsHookEx(hhook);
                     EVENT_LOOP_MANAGER
                         .lock()
@@ -204,7 +363,8 @@ impl EventLoop {
         LOCAL_MOUSE_HHOOK.with_borrow_mut(|ids| {
             if let Some(hhook) = ids.remove(&self.id) {
                 unsafe {
-                    println!("unhook_mouse {:?}", hhook);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(?hhook, "unhook_mouse");
                     let _ = UnhookWindowsHookEx(hhook);
                     EVENT_LOOP_MANAGER.lock().unwrap().del_mouse_event(self.id);
                 }
@@ -212,44 +372,152 @@ impl EventLoop {
         });
     }
 
-    fn recheck_hook(&self) {
+    fn recheck_hook(&self) -> Result<(), KmHookError> {
         if let Some(listener) = self.listener.upgrade() {
+            let routes = listener.routes();
+            LOCAL_ROUTES.with_borrow_mut(|local| *local = routes);
+            #[cfg(feature = "suppression")]
+            LOCAL_LISTENER.with_borrow_mut(|local| *local = Arc::downgrade(&listener));
+            hotkey::sync(&listener);
+
             if listener.has_keyboard_event() {
-                self.set_keyboard_hook();
+                self.set_keyboard_hook()?;
             } else {
                 self.unhook_keyboard();
             }
 
             if listener.has_mouse_event() {
-                self.set_mouse_hook();
+                self.set_mouse_hook()?;
             } else {
                 self.unhook_mouse();
             }
         }
+        Ok(())
     }
 
-    fn post_msg_to_worker(&self, msg: WorkerMsg) {
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} post_msg_to_worker {:?}",
-            std::thread::current().id(),
-            msg
-        );
+    fn last_input_time() -> Option<u32> {
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        unsafe { GetLastInputInfo(&mut info) }
+            .as_bool()
+            .then_some(info.dwTime)
+    }
 
-        if let Some(listener) = self.listener.upgrade() {
-            if let Some(worker) = listener.get_worker() {
-                worker.post_msg(msg);
+    /// Input from before a hook was installed is not the hook's to miss.
+    fn reset_hook_time() {
+        if let Some(time) = Self::last_input_time() {
+            LOCAL_LAST_HOOK_TIME.set(time);
+        }
+    }
+
+    /// The hook that missed input the system received, of those installed:
+    /// the mouse hook when the cursor moved since the last check, the
+    /// keyboard hook otherwise.
+    fn missed_hook(keyboard: bool, mouse: bool, moved: bool) -> Option<HookKind> {
+        if moved {
+            mouse.then_some(HookKind::Mouse)
+        } else {
+            keyboard.then_some(HookKind::Keyboard)
+        }
+    }
+
+    /// Reinstalls the hook that missed input; see `missed_hook`.
+    fn check_hooks(&self) {
+        let Some(last_input) = Self::last_input_time() else {
+            return;
+        };
+        let mut cursor = POINT::default();
+        let cursor = unsafe { GetCursorPos(&mut cursor) }
+            .ok()
+            .map(|_| (cursor.x, cursor.y));
+        let moved = LOCAL_WATCHDOG_CURSOR.replace(cursor) != cursor;
+
+        let unseen = last_input.wrapping_sub(LOCAL_LAST_HOOK_TIME.get()) as i32;
+        if unseen <= consts::HOOK_WATCHDOG_GRACE as i32 {
+            return;
+        }
+        LOCAL_LAST_HOOK_TIME.set(last_input);
+
+        let keyboard = LOCAL_KEYBOARD_HHOOK.with_borrow(|ids| ids.contains_key(&self.id));
+        let mouse = LOCAL_MOUSE_HHOOK.with_borrow(|ids| ids.contains_key(&self.id));
+        if let Some(kind) = Self::missed_hook(keyboard, mouse, moved) {
+            self.reinstall(kind);
+        }
+    }
+
+    fn reinstall(&self, kind: HookKind) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(?kind, "hook watchdog: hook lost");
+
+        Self::post_msg_to_worker(WorkerMsg::Notify(Event::HookLost(kind)));
+        let result = match kind {
+            HookKind::Keyboard => {
+                self.unhook_keyboard();
+                self.set_keyboard_hook()
+            }
+            HookKind::Mouse => {
+                self.unhook_mouse();
+                self.set_mouse_hook()
+            }
+        };
+        match result {
+            Ok(()) => Self::post_msg_to_worker(WorkerMsg::Notify(Event::HookReinstalled(kind))),
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = ?_e, "hook watchdog: reinstall failed");
             }
         }
     }
 
+    /// Hooks can be gone after sleep without the watchdog noticing, so they
+    /// are installed afresh.
+    fn resume(&self) {
+        self.unhook_keyboard();
+        self.unhook_mouse();
+        if let Err(_e) = self.recheck_hook() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = ?_e, "resume: recheck_hook failed");
+        }
+        Self::post_msg_to_worker(WorkerMsg::Notify(Event::Resumed));
+    }
+
+    /// Taps the unassigned VK 0xE8 ahead of a Win release, so the system
+    /// does not take it for a press of Win alone.
+    #[cfg(feature = "suppression")]
+    fn mask_win_release() {
+        use windows::Win32::UI::Input::KeyboardAndMouse::{
+            SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+            KEYEVENTF_KEYUP, VIRTUAL_KEY,
+        };
+
+        let key = |flags| INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(0xE8),
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        };
+        let inputs = [key(KEYBD_EVENT_FLAGS(0)), key(KEYEVENTF_KEYUP)];
+        unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    }
+
+    fn post_msg_to_worker(msg: WorkerMsg) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?msg, "post_msg_to_worker");
+
+        LOCAL_ROUTES.with_borrow_mut(|routes| routes.send(msg));
+    }
+
     pub fn post_msg_to_loop(&self, msg_type: u32) {
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} post_msg_to_loop {:?}",
-            std::thread::current().id(),
-            msg_type
-        );
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?msg_type, "post_msg_to_loop");
 
         let thread_id = {
             let binding = self.loop_thread_id.lock().unwrap();
@@ -263,33 +531,80 @@ impl EventLoop {
         }
     }
 
-    fn run(&self) {
+    /// Reports the result of installing the initial hooks through `ready`
+    /// before entering the message loop.
+    fn run(&self, ready: mpsc::Sender<Result<(), KmHookError>>) {
         {
             *self.loop_thread_id.lock().unwrap() = unsafe { GetCurrentThreadId() };
         }
-        unsafe {
-            let thread_handle = GetCurrentThread();
-            if SetThreadPriority(thread_handle, THREAD_PRIORITY_TIME_CRITICAL).is_err() {
-                #[cfg(feature = "Debug")]
-                println!("SetThreadPriority failed {:?}", thread_handle);
-            }
+        set_thread_dpi_aware();
+
+        let result = self.recheck_hook();
+        let failed = result.is_err();
+        let _ = ready.send(result);
+        if failed {
+            *self.loop_thread_id.lock().unwrap() = 0;
+            return;
+        }
+
+        let watchdog = self
+            .listener
+            .upgrade()
+            .and_then(|listener| listener.config().hook_watchdog)
+            .map(|interval| unsafe { SetTimer(None, 0, interval.as_millis().max(1) as u32, None) })
+            .filter(|&timer| timer != 0);
+        layout::start_polling();
+        power::watch_resume();
+        if self
+            .listener
+            .upgrade()
+            .map_or(false, |l| l.config().focus_events)
+        {
+            window::start_focus_events(|event| Self::post_msg_to_worker(WorkerMsg::Notify(event)));
         }
 
         let mut msg = MSG::default();
         unsafe {
             while GetMessageW(&mut msg, None, 0, 0).as_bool() {
-                #[cfg(feature = "Debug")]
-                println!("{:?} GetMessageW {:?}", std::thread::current().id(), msg);
+                #[cfg(feature = "tracing")]
+                tracing::trace!(?msg, "GetMessageW");
 
                 match msg.message {
-                    WM_USER if msg.wParam.0 as u32 == WM_USER_RECHECK_HOOK => self.recheck_hook(),
+                    WM_USER if msg.wParam.0 as u32 == WM_USER_RECHECK_HOOK => {
+                        if let Err(_e) = self.recheck_hook() {
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!(error = ?_e, "recheck_hook failed");
+                        }
+                    }
+                    WM_USER if msg.wParam.0 as u32 == WM_USER_POWER_RESUME => self.resume(),
+                    WM_TIMER if Some(msg.wParam.0) == watchdog => self.check_hooks(),
+                    WM_TIMER if layout::is_poll_timer(msg.wParam.0) => {
+                        if let Some(info) = layout::poll() {
+                            Self::post_msg_to_worker(WorkerMsg::Notify(Event::LayoutChanged(info)));
+                        }
+                        if let Some(event) = monitor::display_change() {
+                            Self::post_msg_to_worker(WorkerMsg::Notify(event));
+                        }
+                    }
+                    WM_HOTKEY => {
+                        if let Some(id) = hotkey::shortcut_id(msg.wParam.0) {
+                            Self::post_msg_to_worker(WorkerMsg::Hotkey(id));
+                        }
+                    }
                     _ => {
                         let _ = TranslateMessage(&msg);
                         DispatchMessageW(&msg);
                     }
                 }
             }
+            if let Some(timer) = watchdog {
+                let _ = KillTimer(None, timer);
+            }
         }
+        window::stop_focus_events();
+        power::unwatch_resume();
+        layout::stop_polling();
+        hotkey::clear(self.listener.upgrade().as_deref());
     }
 
     pub fn stop(&self) {
@@ -303,18 +618,54 @@ impl EventLoop {
         *self.loop_thread_id.lock().unwrap() = 0;
     }
 
-    pub fn run_with_thread(self: &Arc<Self>) {
+    /// Returns once the hooks needed by the listener are installed.
+    pub fn run_with_thread(self: &Arc<Self>) -> Result<(), KmHookError> {
         let event_loop = Arc::clone(self);
-        let handle = thread::spawn(move || {
-            #[cfg(feature = "Debug")]
-            println!(
-                "Event loop thread started with ID: {:?}",
-                std::thread::current().id()
-            );
-            event_loop.recheck_hook();
-            event_loop.run();
-        });
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let handle = ThreadSpec::event_loop(&self.listener)
+            .spawn(move || {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("event loop thread started");
+                event_loop.run(ready_tx);
+            })
+            .map_err(|e| KmHookError::Thread(e.to_string()))?;
         self.thread_handle.lock().unwrap().replace(Arc::new(handle));
+        ready_rx
+            .recv()
+            .map_err(|e| KmHookError::Thread(e.to_string()))?
+    }
+}
+
+impl InputBackend for EventLoop {
+    fn start(self: Arc<Self>) -> Result<(), KmHookError> {
+        self.run_with_thread()
+    }
+
+    fn stop(&self) {
+        EventLoop::stop(self)
+    }
+
+    fn run_blocking(self: Arc<Self>) -> Result<(), KmHookError> {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        self.run(ready_tx);
+        ready_rx
+            .recv()
+            .map_err(|e| KmHookError::Thread(e.to_string()))?
+    }
+
+    fn post_msg_to_loop(&self, msg_type: u32) {
+        EventLoop::post_msg_to_loop(self, msg_type)
+    }
+
+    fn status(&self) -> HookStatus {
+        let loop_thread_id = *self.loop_thread_id.lock().unwrap();
+        let manager = EVENT_LOOP_MANAGER.lock().unwrap();
+        HookStatus {
+            running: loop_thread_id != 0,
+            keyboard: manager.has_keyboard_event(&self.id),
+            mouse: manager.has_mouse_event(&self.id),
+            loop_thread_id: (loop_thread_id != 0).then_some(loop_thread_id),
+        }
     }
 }
 
@@ -344,6 +695,10 @@ impl EventLoopManager {
         self.keyboard_event_ids.push(id);
     }
 
+    fn has_keyboard_event(&self, id: &ID) -> bool {
+        self.keyboard_event_ids.contains(id)
+    }
+
     fn del_keyboard_event(&mut self, id: ID) {
         self.keyboard_event_ids.retain(|&x| x != id);
     }
@@ -352,28 +707,12 @@ impl EventLoopManager {
         self.mouse_event_ids.push(id);
     }
 
-    fn del_mouse_event(&mut self, id: ID) {
-        self.mouse_event_ids.retain(|&x| x != id);
+    fn has_mouse_event(&self, id: &ID) -> bool {
+        self.mouse_event_ids.contains(id)
     }
 
-    fn get_keyboard_event_loop(&self) -> Vec<Arc<EventLoop>> {
-        let mut event_loops = Vec::new();
-        for id in self.keyboard_event_ids.iter() {
-            if let Some(event_loop) = self.event_loops.get(id) {
-                event_loops.push(event_loop.clone());
-            }
-        }
-        event_loops
-    }
-
-    fn get_mouse_event_loop(&self) -> Vec<Arc<EventLoop>> {
-        let mut event_loops = Vec::new();
-        for id in self.mouse_event_ids.iter() {
-            if let Some(event_loop) = self.event_loops.get(id) {
-                event_loops.push(event_loop.clone());
-            }
-        }
-        event_loops
+    fn del_mouse_event(&mut self, id: ID) {
+        self.mouse_event_ids.retain(|&x| x != id);
     }
 
     fn del_event_loop(&mut self, id: ID) {
@@ -387,3 +726,24 @@ lazy_static! {
     pub(crate) static ref EVENT_LOOP_MANAGER: Mutex<EventLoopManager> =
         Mutex::new(EventLoopManager::new());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missed_hook_is_one_kind() {
+        assert_eq!(
+            EventLoop::missed_hook(true, true, true),
+            Some(HookKind::Mouse)
+        );
+        assert_eq!(
+            EventLoop::missed_hook(true, true, false),
+            Some(HookKind::Keyboard)
+        );
+        // Input of a kind no hook is installed for is not missed.
+        assert_eq!(EventLoop::missed_hook(true, false, true), None);
+        assert_eq!(EventLoop::missed_hook(false, true, false), None);
+        assert_eq!(EventLoop::missed_hook(false, false, true), None);
+    }
+}
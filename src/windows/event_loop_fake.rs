@@ -1,50 +1,67 @@
+use crate::consts;
 use crate::types::{
-    ClickState, KeyId, KeyInfo, KeyState, MouseButton, MouseInfo, Pos, Shortcut, ID,
+    ClickState, DeviceId, DeviceInfo, Event, HookStatus, KeyId, KeyInfo, KeyState, KmHookError,
+    MouseButton, MouseInfo, MouseStateFlags, Pos, ID,
 };
 use crate::utils::gen_id;
-use crate::windows::worker::{KeyboardSysMsg, MouseSysMsg, WorkerMsg};
-use crate::windows::WM_USER_RECHECK_HOOK;
+use crate::windows::backend::InputBackend;
+use crate::windows::device::{self, device_type};
+use crate::windows::worker::{KeyboardSysMsg, MouseSysMsg, Routes, ThreadSpec, WorkerMsg};
+use crate::windows::{
+    hid, hotkey, layout, monitor, power, set_thread_dpi_aware, update_key_snapshot, window,
+    WM_USER_APPLY_CURSOR_CLIP, WM_USER_POWER_RESUME, WM_USER_RECHECK_HOOK,
+};
 use crate::Listener;
 
 use lazy_static::lazy_static;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::{mpsc, Arc, Mutex, Weak};
 use std::thread;
 use windows::core::PCWSTR;
 use windows::Win32::Devices::HumanInterfaceDevice::{
-    HID_USAGE_GENERIC_KEYBOARD, HID_USAGE_GENERIC_MOUSE, HID_USAGE_PAGE_GENERIC,
-    KEYBOARD_OVERRUN_MAKE_CODE,
+    HID_USAGE_GENERIC_GAMEPAD, HID_USAGE_GENERIC_JOYSTICK, HID_USAGE_GENERIC_KEYBOARD,
+    HID_USAGE_GENERIC_MOUSE, HID_USAGE_PAGE_GENERIC, KEYBOARD_OVERRUN_MAKE_CODE,
 };
-use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, RECT, WPARAM};
+use windows::Win32::Foundation::{HANDLE, HWND, LPARAM, LRESULT, RECT, WPARAM};
 use windows::Win32::Globalization::UCHAR_MAX_VALUE;
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
-use windows::Win32::System::Threading::{
-    GetCurrentThread, GetCurrentThreadId, SetThreadPriority, THREAD_PRIORITY_TIME_CRITICAL,
-};
+use windows::Win32::System::Threading::{GetCurrentThreadId, INFINITE};
 use windows::Win32::UI::Input::{
-    GetRawInputData, RegisterRawInputDevices, HRAWINPUT, MOUSE_MOVE_ABSOLUTE,
-    MOUSE_VIRTUAL_DESKTOP, RAWINPUT, RAWINPUTDEVICE, RAWINPUTHEADER, RIDEV_INPUTSINK,
-    RID_DEVICE_INFO_TYPE, RID_INPUT, RIM_TYPEKEYBOARD, RIM_TYPEMOUSE,
+    GetRawInputBuffer, GetRawInputData, RegisterRawInputDevices, HRAWINPUT, MOUSE_MOVE_ABSOLUTE,
+    MOUSE_VIRTUAL_DESKTOP, RAWINPUT, RAWINPUTDEVICE, RAWINPUTHEADER, RIDEV_CAPTUREMOUSE,
+    RIDEV_DEVNOTIFY, RIDEV_INPUTSINK, RIDEV_NOLEGACY, RID_DEVICE_INFO_TYPE, RID_INPUT, RIM_TYPEHID,
+    RIM_TYPEKEYBOARD, RIM_TYPEMOUSE,
 };
 use windows::Win32::UI::WindowsAndMessaging::{
-    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetCursorPos, GetMessageW,
-    GetSystemMetrics, PostThreadMessageW, RegisterClassW, TranslateMessage, CW_USEDEFAULT, HHOOK,
-    MSG, RI_KEY_BREAK, RI_MOUSE_BUTTON_4_DOWN, RI_MOUSE_BUTTON_4_UP, RI_MOUSE_BUTTON_5_DOWN,
-    RI_MOUSE_BUTTON_5_UP, RI_MOUSE_LEFT_BUTTON_DOWN, RI_MOUSE_LEFT_BUTTON_UP,
+    ClipCursor, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetCursorPos,
+    GetMessageTime, GetMessageW, KillTimer, MsgWaitForMultipleObjectsEx, PeekMessageW,
+    PostThreadMessageW, RegisterClassW, SetTimer, TranslateMessage, CW_USEDEFAULT, GIDC_ARRIVAL,
+    GIDC_REMOVAL, HHOOK, HWND_MESSAGE, MSG, MWMO_INPUTAVAILABLE, PM_REMOVE, QS_ALLINPUT,
+    RI_KEY_BREAK, RI_KEY_E0, RI_MOUSE_BUTTON_4_DOWN, RI_MOUSE_BUTTON_4_UP, RI_MOUSE_BUTTON_5_DOWN,
+    RI_MOUSE_BUTTON_5_UP, RI_MOUSE_HWHEEL, RI_MOUSE_LEFT_BUTTON_DOWN, RI_MOUSE_LEFT_BUTTON_UP,
     RI_MOUSE_MIDDLE_BUTTON_DOWN, RI_MOUSE_MIDDLE_BUTTON_UP, RI_MOUSE_RIGHT_BUTTON_DOWN,
-    RI_MOUSE_RIGHT_BUTTON_UP, SM_CXSCREEN, SM_CXVIRTUALSCREEN, SM_CYSCREEN, SM_CYVIRTUALSCREEN,
-    SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, WM_INPUT, WM_QUIT, WM_USER, WNDCLASSW, WS_EX_LAYERED,
-    WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT, WS_OVERLAPPED,
+    RI_MOUSE_RIGHT_BUTTON_UP, RI_MOUSE_WHEEL, WINDOW_EX_STYLE, WINDOW_STYLE, WM_DISPLAYCHANGE,
+    WM_HOTKEY, WM_INPUT, WM_INPUT_DEVICE_CHANGE, WM_QUIT, WM_TIMER, WM_USER, WNDCLASSW,
+    WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT, WS_OVERLAPPED,
 };
 
 thread_local! {
     static LOCAL_KEYBOARD_HHOOK: RefCell<HashMap<ID, HHOOK>> = RefCell::new(HashMap::new());
     static LOCAL_MOUSE_HHOOK: RefCell<HashMap<ID, HHOOK>> = RefCell::new(HashMap::new());
-    static LOCAL_KEY_LAST_TIME: RefCell<u32> = RefCell::new(0);
     static LOCAL_HWDN: RefCell<HashMap<ID, HWND>> = RefCell::new(HashMap::new());
+    static LOCAL_CLIP_TIMER: RefCell<HashMap<ID, usize>> = RefCell::new(HashMap::new());
+    /// Where input read on this thread is delivered, refreshed by
+    /// `recheck_hook`; keeps the global manager lock off the hot path.
+    static LOCAL_ROUTES: RefCell<Routes> = RefCell::new(Routes::default());
+    static LOCAL_KEYBOARD_ON: Cell<bool> = const { Cell::new(false) };
+    static LOCAL_MOUSE_ON: Cell<bool> = const { Cell::new(false) };
+    /// Connected devices by handle, to describe them once they are removed.
+    static LOCAL_DEVICES: RefCell<HashMap<isize, DeviceInfo>> = RefCell::new(HashMap::new());
+    /// `GetRawInputBuffer` target, in u64s for the 8-byte alignment of its entries.
+    static LOCAL_RAW_INPUT_BUFFER: RefCell<Vec<u64>> =
+        RefCell::new(vec![0; consts::RAW_INPUT_BUFFER_SIZE / 8]);
     // static LOCAL_KEYBOARD_STATE: RefCell<KeyboardState> = RefCell::new(KeyboardState::new(Some(consts::MAX_KEYS)));
-    static LOCAL_KEYBOARD_STATE_S: RefCell<Shortcut> = RefCell::new(Shortcut::default());
 }
 
 #[derive(Debug)]
@@ -85,7 +102,8 @@ impl EventLoop {
 
         let key_id: Result<KeyId, _> = KeyId::try_from(*keyboard);
         if key_id.is_err() {
-            println!("Get KeyID failed {:?}", keyboard);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(?keyboard, "Get KeyID failed");
             return;
         }
         let key_id = key_id.unwrap();
@@ -97,6 +115,13 @@ impl EventLoop {
                 KeyState::Pressed
             },
         );
+        key_info.scancode = keyboard.MakeCode as u32;
+        key_info.vk = keyboard.VKey as u32;
+        key_info.is_extended = keyboard.Flags as u32 & RI_KEY_E0 != 0;
+        // Raw input carries no injected flag; synthesized input has no source device.
+        key_info.injected = rawinput.header.hDevice.0.is_null();
+        key_info.device = (!key_info.injected).then(|| rawinput.header.hDevice.into());
+        key_info.time = unsafe { GetMessageTime() } as u32;
 
         // let mut old_state: Option<KeyboardState> = None;
         // LOCAL_KEYBOARD_STATE.with(|state| {
@@ -105,31 +130,20 @@ impl EventLoop {
         //     key_info.keyboard_state.replace(state.borrow().clone())
         // });
 
-        let mut old_state: Option<Shortcut> = None;
-        LOCAL_KEYBOARD_STATE_S.with(|state| {
-            old_state.replace(state.borrow().clone());
-            if key_info.state == KeyState::Pressed {
-                state.borrow_mut().set_key(key_id.into())
-            } else {
-                state.borrow_mut().remove_key(key_id.into())
+        if !update_key_snapshot(&mut key_info) {
+            if key_info.state == KeyState::Released {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(?key_info, "Key State not changed");
+                return;
             }
-            key_info.keyboard_state.replace(state.borrow().clone());
-        });
-
-        if old_state == key_info.keyboard_state {
-            #[cfg(feature = "Debug")]
-            println!("Key State not changed {:?}", key_info);
-            return;
+            key_info.is_repeat = true;
         }
 
-        #[cfg(feature = "Debug")]
-        println!("kbd: vk_code={:?} key_info={:?}", keyboard.VKey, key_info);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(vk_code = keyboard.VKey, ?key_info, "kbd");
 
-        let msg = WorkerMsg::KeyboardEvent(KeyboardSysMsg::new(key_info));
-
-        let event_loops = { EVENT_LOOP_MANAGER.lock().unwrap().get_keyboard_event_loop() };
-        for event_loop in event_loops.iter() {
-            event_loop.post_msg_to_worker(msg.clone());
+        if LOCAL_KEYBOARD_ON.get() {
+            Self::post_msg_to_worker(WorkerMsg::KeyboardEvent(KeyboardSysMsg::new(key_info)));
         }
     }
 
@@ -190,11 +204,25 @@ impl EventLoop {
             _ => None,
         };
 
-        if btn.is_none() && button_flags != 0 {
-            #[cfg(feature = "Debug")]
-            println!(
-                "Currently, mouse button events are not supported. {:?}",
-                button_flags
+        // The rotation is a signed value in usButtonData.
+        let wheel_delta = unsafe { mouse.Anonymous.Anonymous.usButtonData } as i16 as i32;
+        let wheel = match button_flags as u32 {
+            RI_MOUSE_WHEEL => Pos {
+                x: 0,
+                y: wheel_delta,
+            },
+            RI_MOUSE_HWHEEL => Pos {
+                x: wheel_delta,
+                y: 0,
+            },
+            _ => Pos::default(),
+        };
+
+        if btn.is_none() && button_flags != 0 && wheel == Pos::default() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                ?button_flags,
+                "Currently, mouse button events are not supported."
             );
             return;
         }
@@ -204,29 +232,26 @@ impl EventLoop {
             y: lppoint.y,
         };
         let mut rel_pos = Pos::default();
+        let mut raw_delta = Pos::default();
         if pos_flags & MOUSE_MOVE_ABSOLUTE.0 > 0 {
-            let mut rect = RECT::default();
-            if (pos_flags & MOUSE_VIRTUAL_DESKTOP.0) > 0 {
-                unsafe {
-                    rect.left = GetSystemMetrics(SM_XVIRTUALSCREEN);
-                    rect.top = GetSystemMetrics(SM_YVIRTUALSCREEN);
-                    rect.right = GetSystemMetrics(SM_CXVIRTUALSCREEN);
-                    rect.bottom = GetSystemMetrics(SM_CYVIRTUALSCREEN);
-                }
+            // Area 0..=65535 is mapped onto. The virtual desktop's origin is
+            // negative when a monitor is left of or above the primary one.
+            let metrics = monitor::screen_metrics();
+            let area = if (pos_flags & MOUSE_VIRTUAL_DESKTOP.0) > 0 {
+                metrics.virtual_screen
             } else {
-                unsafe {
-                    rect.left = 0;
-                    rect.top = 0;
-                    rect.right = GetSystemMetrics(SM_CXSCREEN);
-                    rect.bottom = GetSystemMetrics(SM_CYSCREEN);
-                }
-            }
-
-            // int absoluteX = MulDiv(mouse.lLastX, rect.right, USHRT_MAX) + rect.left;
-            // int absoluteY = MulDiv(mouse.lLastY, rect.bottom, USHRT_MAX) + rect.top;
-
-            let absolute_x = (mouse.lLastX * rect.right / u16::MAX as i32) + rect.left;
-            let absolute_y = (mouse.lLastY * rect.bottom / u16::MAX as i32) + rect.top;
+                metrics.primary
+            };
+            let (left, top) = (area.left, area.top);
+            let (width, height) = (area.right - area.left, area.bottom - area.top);
+
+            // MulDiv(lLastX, width, USHRT_MAX) + left, in 64 bits so wide
+            // desktops don't overflow.
+            let scale = |v: i32, size: i32, origin: i32| {
+                (v as i64 * (size - 1).max(0) as i64 / u16::MAX as i64) as i32 + origin
+            };
+            let absolute_x = scale(mouse.lLastX, width, left);
+            let absolute_y = scale(mouse.lLastY, height, top);
 
             // println!(
             //     "Mouse move absolute x: {:?} y: {:?}",
@@ -240,6 +265,10 @@ impl EventLoop {
             pos.y += last_y;
             rel_pos.x = last_x;
             rel_pos.y = last_y;
+            raw_delta = Pos {
+                x: last_x,
+                y: last_y,
+            };
 
             // println!(
             //     "Mouse move relative x: {:?} y: {:?}, ab: x:{:?} y:{:?}",
@@ -247,20 +276,118 @@ impl EventLoop {
             // );
         }
 
+        let mut flags = MouseStateFlags::from_extra_info(mouse.ulExtraInformation as usize);
+        let device = if rawinput.header.hDevice.0.is_null() {
+            flags |= MouseStateFlags::INJECTED;
+            None
+        } else {
+            Some(rawinput.header.hDevice.into())
+        };
+        if let Some(btn) = &btn {
+            flags |= if btn.state() == ClickState::Pressed {
+                MouseStateFlags::PRESSED
+            } else {
+                MouseStateFlags::RELEASED
+            };
+        }
+
         let minfo = MouseInfo {
             button: btn,
             pos,
             relative_pos: rel_pos,
+            raw_delta,
+            wheel,
+            flags,
+            device,
+            time: unsafe { GetMessageTime() } as u32,
+            ..Default::default()
         };
 
-        let msg = WorkerMsg::MouseEvent(MouseSysMsg::new(minfo));
+        if LOCAL_MOUSE_ON.get() {
+            Self::post_msg_to_worker(WorkerMsg::MouseEvent(MouseSysMsg::new(minfo)));
+        }
+    }
 
-        let event_loops = { EVENT_LOOP_MANAGER.lock().unwrap().get_mouse_event_loop() };
-        for event_loop in event_loops.iter() {
-            event_loop.post_msg_to_worker(msg.clone());
+    fn raw_input_proc(rawinput: &RAWINPUT) {
+        // println!("rawinput: {:?}", rawinput.header.dwType);
+        match RID_DEVICE_INFO_TYPE(rawinput.header.dwType) {
+            RIM_TYPEKEYBOARD => {
+                Self::keyboard_proc(rawinput);
+            }
+            RIM_TYPEMOUSE => {
+                Self::mouse_proc(rawinput);
+            }
+            RIM_TYPEHID => {
+                Self::hid_proc(rawinput);
+            }
+            _ => {}
+        }
+    }
+
+    /// `rawinput` must be the start of the whole `header.dwSize` bytes.
+    fn hid_proc(rawinput: &RAWINPUT) {
+        let report = unsafe {
+            let hid = &rawinput.data.hid;
+            std::slice::from_raw_parts(
+                hid.bRawData.as_ptr(),
+                (hid.dwSizeHid * hid.dwCount) as usize,
+            )
+        };
+        let size = unsafe { rawinput.data.hid.dwSizeHid } as usize;
+        let time = unsafe { GetMessageTime() } as u32;
+        if let Some(info) = hid::parse(rawinput.header.hDevice, report, size, time) {
+            Self::post_msg_to_worker(WorkerMsg::HidEvent(info));
         }
     }
 
+    fn device_change(change: u32, handle: HANDLE) {
+        let event = match change {
+            GIDC_ARRIVAL => {
+                let kind = device_type(handle);
+                let Some(info) = kind.and_then(|kind| device::device_info(handle, kind)) else {
+                    return;
+                };
+                LOCAL_DEVICES.with_borrow_mut(|devices| devices.insert(info.id.0, info.clone()));
+                Event::DeviceArrived(info)
+            }
+            GIDC_REMOVAL => {
+                hid::forget(handle);
+                let id = DeviceId::from(handle);
+                match LOCAL_DEVICES.with_borrow_mut(|devices| devices.remove(&id.0)) {
+                    Some(info) => Event::DeviceRemoved(info),
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        Self::post_msg_to_worker(WorkerMsg::Notify(event));
+    }
+
+    /// Reads all raw input queued for the thread, a buffer at a time.
+    fn drain_raw_input_buffer() {
+        LOCAL_RAW_INPUT_BUFFER.with_borrow_mut(|buffer| loop {
+            let mut size = (buffer.len() * 8) as u32;
+            let count = unsafe {
+                GetRawInputBuffer(
+                    Some(buffer.as_mut_ptr() as *mut RAWINPUT),
+                    &mut size,
+                    std::mem::size_of::<RAWINPUTHEADER>() as u32,
+                )
+            };
+            if count == 0 || count == u32::MAX {
+                break;
+            }
+            let mut entry = buffer.as_ptr() as *const u8;
+            for _ in 0..count {
+                let rawinput = unsafe { &*(entry as *const RAWINPUT) };
+                Self::raw_input_proc(rawinput);
+                // NEXTRAWINPUTBLOCK: entries start on 8-byte boundaries.
+                let size = (rawinput.header.dwSize as usize + 7) & !7;
+                entry = unsafe { entry.add(size) };
+            }
+        });
+    }
+
     unsafe extern "system" fn fake_win_proc(
         hwnd: HWND,
         msg: u32,
@@ -269,37 +396,48 @@ impl EventLoop {
     ) -> LRESULT {
         match msg {
             WM_INPUT => {
-                let mut dw_size: u32 = 0;
+                // Keyboard and mouse input always fits a RAWINPUT, so read it
+                // straight onto the stack; only HID reports need more room.
+                let mut rawinput: RAWINPUT = std::mem::zeroed();
+                let mut dw_size = std::mem::size_of::<RAWINPUT>() as u32;
                 let hrawinput: HRAWINPUT = HRAWINPUT(lparam.0 as *mut std::ffi::c_void);
-                GetRawInputData(
-                    hrawinput,
-                    RID_INPUT,
-                    None,
-                    &mut dw_size,
-                    std::mem::size_of::<RAWINPUTHEADER>() as u32,
-                );
-                let mut buffer = vec![0u8; dw_size as usize];
-                GetRawInputData(
+                let header_size = std::mem::size_of::<RAWINPUTHEADER>() as u32;
+                let read = GetRawInputData(
                     hrawinput,
                     RID_INPUT,
-                    Some(buffer.as_mut_ptr() as *mut std::ffi::c_void),
+                    Some(&mut rawinput as *mut RAWINPUT as *mut std::ffi::c_void),
                     &mut dw_size,
-                    std::mem::size_of::<RAWINPUTHEADER>() as u32,
+                    header_size,
                 );
-
-                let rawinput = &*(buffer.as_ptr() as *const RAWINPUT);
-
-                // println!("rawinput: {:?}", rawinput.header.dwType);
-                match RID_DEVICE_INFO_TYPE(rawinput.header.dwType) {
-                    RIM_TYPEKEYBOARD => {
-                        Self::keyboard_proc(rawinput);
-                    }
-                    RIM_TYPEMOUSE => {
-                        Self::mouse_proc(rawinput);
+                if read != u32::MAX {
+                    Self::raw_input_proc(&rawinput);
+                } else if dw_size as usize > std::mem::size_of::<RAWINPUT>() {
+                    let mut buffer = vec![0u64; (dw_size as usize).div_ceil(8)];
+                    let read = GetRawInputData(
+                        hrawinput,
+                        RID_INPUT,
+                        Some(buffer.as_mut_ptr() as *mut std::ffi::c_void),
+                        &mut dw_size,
+                        header_size,
+                    );
+                    if read != u32::MAX {
+                        Self::raw_input_proc(&*(buffer.as_ptr() as *const RAWINPUT));
                     }
-                    _ => {}
                 }
             }
+            WM_INPUT_DEVICE_CHANGE => Self::device_change(wparam.0 as u32, HANDLE(lparam.0 as _)),
+            WM_DISPLAYCHANGE => {
+                if let Some(event) = monitor::display_change() {
+                    Self::post_msg_to_worker(WorkerMsg::Notify(event));
+                }
+                // The clip rectangle is in screen coordinates that just changed.
+                let _ = PostThreadMessageW(
+                    GetCurrentThreadId(),
+                    WM_USER,
+                    WPARAM(WM_USER_APPLY_CURSOR_CLIP as usize),
+                    None,
+                );
+            }
             _ => {}
         }
         DefWindowProcW(hwnd, msg, wparam, lparam)
@@ -361,42 +499,49 @@ impl EventLoop {
 
     fn recheck_hook(&self) {
         if let Some(listener) = self.listener.upgrade() {
+            let routes = listener.routes();
+            LOCAL_ROUTES.with_borrow_mut(|local| *local = routes);
+            hotkey::sync(&listener);
+
             if listener.has_keyboard_event() {
                 self.set_keyboard_hook();
             } else {
                 self.unhook_keyboard();
             }
+            LOCAL_KEYBOARD_ON.set(listener.has_keyboard_event());
 
             if listener.has_mouse_event() {
                 self.set_mouse_hook();
             } else {
                 self.unhook_mouse();
             }
+            LOCAL_MOUSE_ON.set(listener.has_mouse_event());
         }
     }
 
-    fn post_msg_to_worker(&self, msg: WorkerMsg) {
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} post_msg_to_worker {:?}",
-            std::thread::current().id(),
-            msg
-        );
-
-        if let Some(listener) = self.listener.upgrade() {
-            if let Some(worker) = listener.get_worker() {
-                worker.post_msg(msg);
+    /// Raw input registrations can be lost over sleep; register again.
+    fn resume(&self) {
+        let hwnd = LOCAL_HWDN.with_borrow(|hwdn| hwdn.get(&self.id).cloned());
+        if let Some(hwnd) = hwnd {
+            if let Err(_e) = self.register_raw_input(hwnd) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = ?_e, "resume: register_raw_input failed");
             }
         }
+        self.recheck_hook();
+        Self::post_msg_to_worker(WorkerMsg::Notify(Event::Resumed));
+    }
+
+    fn post_msg_to_worker(msg: WorkerMsg) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?msg, "post_msg_to_worker");
+
+        LOCAL_ROUTES.with_borrow_mut(|routes| routes.send(msg));
     }
 
     pub fn post_msg_to_loop(&self, msg_type: u32) {
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} post_msg_to_loop {:?}",
-            std::thread::current().id(),
-            msg_type
-        );
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?msg_type, "post_msg_to_loop");
 
         let thread_id = {
             let binding = self.loop_thread_id.lock().unwrap();
@@ -410,8 +555,10 @@ impl EventLoop {
         }
     }
 
-    fn init_fake_win(&self) -> std::result::Result<(), ()> {
-        let hinstance = unsafe { GetModuleHandleW(None).unwrap().into() };
+    fn init_fake_win(&self) -> Result<(), KmHookError> {
+        let hinstance = unsafe { GetModuleHandleW(None) }
+            .map_err(|e| KmHookError::WindowCreation(e.to_string()))?
+            .into();
         let class_name: Vec<u16> =
             std::os::windows::ffi::OsStrExt::encode_wide(std::ffi::OsStr::new("kmhook_app"))
                 .chain(std::iter::once(0))
@@ -422,31 +569,54 @@ impl EventLoop {
             lpszClassName: PCWSTR::from_raw(class_name.as_ptr()),
             ..Default::default()
         };
+        let message_only = self
+            .listener
+            .upgrade()
+            .map_or(true, |l| l.config().message_only_window);
         unsafe {
             let _ = RegisterClassW(&wnd_class);
-            let hwnd = CreateWindowExW(
-                WS_EX_NOACTIVATE | WS_EX_TRANSPARENT | WS_EX_LAYERED | WS_EX_TOOLWINDOW,
-                PCWSTR(class_name.as_ptr()),
-                None,
-                WS_OVERLAPPED,
-                CW_USEDEFAULT,
-                0,
-                CW_USEDEFAULT,
-                0,
-                None,
-                None,
-                hinstance,
-                None,
-            );
-            if hwnd.is_err() {
-                return Err(());
-            }
-            let hwnd = hwnd.unwrap();
+            let hwnd = if message_only {
+                CreateWindowExW(
+                    WINDOW_EX_STYLE::default(),
+                    PCWSTR(class_name.as_ptr()),
+                    None,
+                    WINDOW_STYLE::default(),
+                    0,
+                    0,
+                    0,
+                    0,
+                    HWND_MESSAGE,
+                    None,
+                    hinstance,
+                    None,
+                )
+            } else {
+                CreateWindowExW(
+                    WS_EX_NOACTIVATE | WS_EX_TRANSPARENT | WS_EX_LAYERED | WS_EX_TOOLWINDOW,
+                    PCWSTR(class_name.as_ptr()),
+                    None,
+                    WS_OVERLAPPED,
+                    CW_USEDEFAULT,
+                    0,
+                    CW_USEDEFAULT,
+                    0,
+                    None,
+                    None,
+                    hinstance,
+                    None,
+                )
+            };
+            let hwnd = hwnd.map_err(|e| KmHookError::WindowCreation(e.to_string()))?;
             if hwnd.is_invalid() {
-                return Err(());
+                return Err(KmHookError::WindowCreation(
+                    "invalid window handle".to_string(),
+                ));
             }
 
-            self.register_raw_input(hwnd.clone());
+            if let Err(e) = self.register_raw_input(hwnd.clone()) {
+                let _ = DestroyWindow(hwnd);
+                return Err(e);
+            }
             LOCAL_HWDN.with(|hwdn| {
                 hwdn.borrow_mut().insert(self.id, hwnd);
             });
@@ -455,25 +625,44 @@ impl EventLoop {
         }
     }
 
-    fn register_raw_input(&self, hwnd: HWND) {
+    fn register_raw_input(&self, hwnd: HWND) -> Result<(), KmHookError> {
+        let (exclusive, game_controllers) = self.listener.upgrade().map_or((false, false), |l| {
+            (l.config().exclusive_raw_input, l.config().game_controllers)
+        });
+        let flags = RIDEV_INPUTSINK | RIDEV_DEVNOTIFY;
+        let (keyboard_flags, mouse_flags) = if exclusive {
+            (
+                flags | RIDEV_NOLEGACY,
+                flags | RIDEV_NOLEGACY | RIDEV_CAPTUREMOUSE,
+            )
+        } else {
+            (flags, flags)
+        };
         let rid = RAWINPUTDEVICE {
             usUsagePage: HID_USAGE_PAGE_GENERIC,
             usUsage: HID_USAGE_GENERIC_KEYBOARD,
-            dwFlags: RIDEV_INPUTSINK,
+            dwFlags: keyboard_flags,
             hwndTarget: hwnd,
         };
         let rid_mouse = RAWINPUTDEVICE {
             usUsagePage: HID_USAGE_PAGE_GENERIC,
             usUsage: HID_USAGE_GENERIC_MOUSE,
-            dwFlags: RIDEV_INPUTSINK,
+            dwFlags: mouse_flags,
             hwndTarget: hwnd,
         };
-        unsafe {
-            let _ = RegisterRawInputDevices(
-                &[rid, rid_mouse],
-                std::mem::size_of::<RAWINPUTDEVICE>() as u32,
-            );
+        let mut devices = vec![rid, rid_mouse];
+        if game_controllers {
+            for usage in [HID_USAGE_GENERIC_GAMEPAD, HID_USAGE_GENERIC_JOYSTICK] {
+                devices.push(RAWINPUTDEVICE {
+                    usUsagePage: HID_USAGE_PAGE_GENERIC,
+                    usUsage: usage,
+                    dwFlags: flags,
+                    hwndTarget: hwnd,
+                });
+            }
         }
+        unsafe { RegisterRawInputDevices(&devices, std::mem::size_of::<RAWINPUTDEVICE>() as u32) }
+            .map_err(|e| KmHookError::RawInputRegistration(e.to_string()))
     }
 
     fn uninit_fake_win(&self) {
@@ -486,37 +675,129 @@ impl EventLoop {
         });
     }
 
-    fn run(&self) {
-        {
-            *self.loop_thread_id.lock().unwrap() = unsafe { GetCurrentThreadId() };
+    /// Windows drops the clip when focus moves between applications, so while a
+    /// confinement is active it is re-applied on a timer.
+    fn apply_cursor_clip(&self) {
+        let rect = self.listener.upgrade().and_then(|l| l.cursor_clip());
+        match rect {
+            Some(rect) => {
+                let rect: RECT = rect.into();
+                unsafe {
+                    let _ = ClipCursor(Some(&rect as *const RECT));
+                }
+                if !LOCAL_CLIP_TIMER.with_borrow(|timers| timers.contains_key(&self.id)) {
+                    let timer = unsafe { SetTimer(None, 0, 500, None) };
+                    LOCAL_CLIP_TIMER.with_borrow_mut(|timers| {
+                        timers.insert(self.id, timer);
+                    });
+                }
+            }
+            None => self.release_cursor_clip(),
         }
-        unsafe {
-            let thread_handle = GetCurrentThread();
-            if SetThreadPriority(thread_handle, THREAD_PRIORITY_TIME_CRITICAL).is_err() {
-                #[cfg(feature = "Debug")]
-                println!("SetThreadPriority failed {:?}", thread_handle);
+    }
+
+    fn release_cursor_clip(&self) {
+        if let Some(timer) = LOCAL_CLIP_TIMER.with_borrow_mut(|timers| timers.remove(&self.id)) {
+            unsafe {
+                let _ = KillTimer(None, timer);
+                let _ = ClipCursor(None);
+            }
+        }
+    }
+
+    fn is_clip_timer(&self, timer: usize) -> bool {
+        LOCAL_CLIP_TIMER.with_borrow(|timers| timers.get(&self.id) == Some(&timer))
+    }
+
+    fn handle_msg(&self, msg: &MSG) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?msg, "GetMessageW");
+
+        match msg.message {
+            WM_USER if msg.wParam.0 as u32 == WM_USER_RECHECK_HOOK => self.recheck_hook(),
+            WM_USER if msg.wParam.0 as u32 == WM_USER_APPLY_CURSOR_CLIP => self.apply_cursor_clip(),
+            WM_USER if msg.wParam.0 as u32 == WM_USER_POWER_RESUME => self.resume(),
+            WM_TIMER if self.is_clip_timer(msg.wParam.0) => self.apply_cursor_clip(),
+            WM_TIMER if layout::is_poll_timer(msg.wParam.0) => {
+                if let Some(info) = layout::poll() {
+                    Self::post_msg_to_worker(WorkerMsg::Notify(Event::LayoutChanged(info)));
+                }
+                if let Some(event) = monitor::display_change() {
+                    Self::post_msg_to_worker(WorkerMsg::Notify(event));
+                    self.apply_cursor_clip();
+                }
+            }
+            WM_HOTKEY => {
+                if let Some(id) = hotkey::shortcut_id(msg.wParam.0) {
+                    Self::post_msg_to_worker(WorkerMsg::Hotkey(id));
+                }
             }
+            _ => unsafe {
+                let _ = TranslateMessage(msg);
+                DispatchMessageW(msg);
+            },
         }
+    }
 
-        if let Err(_) = self.init_fake_win() {
+    /// Reports whether the raw input window could be set up through `ready`
+    /// before entering the message loop.
+    fn run(&self, ready: mpsc::Sender<Result<(), KmHookError>>) {
+        {
+            *self.loop_thread_id.lock().unwrap() = unsafe { GetCurrentThreadId() };
+        }
+        set_thread_dpi_aware();
+
+        let result = self.init_fake_win();
+        let failed = result.is_err();
+        let _ = ready.send(result);
+        if failed {
+            *self.loop_thread_id.lock().unwrap() = 0;
             return;
         }
 
+        layout::start_polling();
+        power::watch_resume();
+        if self
+            .listener
+            .upgrade()
+            .map_or(false, |l| l.config().focus_events)
+        {
+            window::start_focus_events(|event| Self::post_msg_to_worker(WorkerMsg::Notify(event)));
+        }
+        let buffered = self
+            .listener
+            .upgrade()
+            .map_or(false, |l| l.config().buffered_raw_input);
         let mut msg = MSG::default();
         unsafe {
-            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
-                #[cfg(feature = "Debug")]
-                println!("{:?} GetMessageW {:?}", std::thread::current().id(), msg);
-
-                match msg.message {
-                    WM_USER if msg.wParam.0 as u32 == WM_USER_RECHECK_HOOK => self.recheck_hook(),
-                    _ => {
-                        let _ = TranslateMessage(&msg);
-                        DispatchMessageW(&msg);
+            if buffered {
+                'wait: loop {
+                    Self::drain_raw_input_buffer();
+                    let _ = MsgWaitForMultipleObjectsEx(
+                        None,
+                        INFINITE,
+                        QS_ALLINPUT,
+                        MWMO_INPUTAVAILABLE,
+                    );
+                    Self::drain_raw_input_buffer();
+                    while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+                        if msg.message == WM_QUIT {
+                            break 'wait;
+                        }
+                        self.handle_msg(&msg);
                     }
                 }
+            } else {
+                while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                    self.handle_msg(&msg);
+                }
             }
         }
+        self.release_cursor_clip();
+        window::stop_focus_events();
+        power::unwatch_resume();
+        layout::stop_polling();
+        hotkey::clear(self.listener.upgrade().as_deref());
     }
 
     pub fn stop(&self) {
@@ -530,18 +811,56 @@ impl EventLoop {
         *self.loop_thread_id.lock().unwrap() = 0;
     }
 
-    pub fn run_with_thread(self: &Arc<Self>) {
+    /// Returns once the raw input window is registered.
+    pub fn run_with_thread(self: &Arc<Self>) -> Result<(), KmHookError> {
         let event_loop = Arc::clone(self);
-        let handle = thread::spawn(move || {
-            #[cfg(feature = "Debug")]
-            println!(
-                "Event loop thread started with ID: {:?}",
-                std::thread::current().id()
-            );
-            event_loop.recheck_hook();
-            event_loop.run();
-        });
+        let (ready_tx, ready_rx) = mpsc::channel();
+        let handle = ThreadSpec::event_loop(&self.listener)
+            .spawn(move || {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("event loop thread started");
+                event_loop.recheck_hook();
+                event_loop.run(ready_tx);
+            })
+            .map_err(|e| KmHookError::Thread(e.to_string()))?;
         self.thread_handle.lock().unwrap().replace(Arc::new(handle));
+        ready_rx
+            .recv()
+            .map_err(|e| KmHookError::Thread(e.to_string()))?
+    }
+}
+
+impl InputBackend for EventLoop {
+    fn start(self: Arc<Self>) -> Result<(), KmHookError> {
+        self.run_with_thread()
+    }
+
+    fn stop(&self) {
+        EventLoop::stop(self)
+    }
+
+    fn run_blocking(self: Arc<Self>) -> Result<(), KmHookError> {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        self.recheck_hook();
+        self.run(ready_tx);
+        ready_rx
+            .recv()
+            .map_err(|e| KmHookError::Thread(e.to_string()))?
+    }
+
+    fn post_msg_to_loop(&self, msg_type: u32) {
+        EventLoop::post_msg_to_loop(self, msg_type)
+    }
+
+    fn status(&self) -> HookStatus {
+        let loop_thread_id = *self.loop_thread_id.lock().unwrap();
+        let manager = EVENT_LOOP_MANAGER.lock().unwrap();
+        HookStatus {
+            running: loop_thread_id != 0,
+            keyboard: manager.has_keyboard_event(&self.id),
+            mouse: manager.has_mouse_event(&self.id),
+            loop_thread_id: (loop_thread_id != 0).then_some(loop_thread_id),
+        }
     }
 }
 
@@ -591,26 +910,6 @@ impl EventLoopManager {
         self.mouse_event_ids.retain(|&x| x != id);
     }
 
-    fn get_keyboard_event_loop(&self) -> Vec<Arc<EventLoop>> {
-        let mut event_loops = Vec::new();
-        for id in self.keyboard_event_ids.iter() {
-            if let Some(event_loop) = self.event_loops.get(id) {
-                event_loops.push(event_loop.clone());
-            }
-        }
-        event_loops
-    }
-
-    fn get_mouse_event_loop(&self) -> Vec<Arc<EventLoop>> {
-        let mut event_loops = Vec::new();
-        for id in self.mouse_event_ids.iter() {
-            if let Some(event_loop) = self.event_loops.get(id) {
-                event_loops.push(event_loop.clone());
-            }
-        }
-        event_loops
-    }
-
     fn del_event_loop(&mut self, id: ID) {
         self.event_loops.remove(&id);
         self.del_keyboard_event(id);
@@ -0,0 +1,153 @@
+//! Reports of game controllers and other HID devices read through raw input,
+//! parsed with the device's preparsed data.
+
+use crate::types::{DeviceId, HidInfo, HidValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime};
+use windows::Win32::Devices::HumanInterfaceDevice::{
+    HidP_GetCaps, HidP_GetUsageValue, HidP_GetUsages, HidP_GetValueCaps, HidP_Input,
+    HidP_MaxUsageListLength, HIDP_CAPS, HIDP_STATUS_SUCCESS, HIDP_VALUE_CAPS,
+    HID_USAGE_PAGE_BUTTON, PHIDP_PREPARSED_DATA,
+};
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::UI::Input::{GetRawInputDeviceInfoW, RIDI_PREPARSEDDATA};
+
+/// What is needed to parse the reports of one device.
+struct Layout {
+    /// Preparsed data, in u64s to keep it aligned.
+    preparsed: Vec<u64>,
+    caps: HIDP_CAPS,
+    values: Vec<HIDP_VALUE_CAPS>,
+}
+
+impl Layout {
+    fn load(device: HANDLE) -> Option<Self> {
+        let mut size = 0u32;
+        unsafe { GetRawInputDeviceInfoW(device, RIDI_PREPARSEDDATA, None, &mut size) };
+        if size == 0 {
+            return None;
+        }
+        let mut preparsed = vec![0u64; (size as usize).div_ceil(8)];
+        let read = unsafe {
+            GetRawInputDeviceInfoW(
+                device,
+                RIDI_PREPARSEDDATA,
+                Some(preparsed.as_mut_ptr() as *mut std::ffi::c_void),
+                &mut size,
+            )
+        };
+        if read == u32::MAX {
+            return None;
+        }
+        let data = PHIDP_PREPARSED_DATA(preparsed.as_ptr() as isize);
+
+        let mut caps = HIDP_CAPS::default();
+        if unsafe { HidP_GetCaps(data, &mut caps) } != HIDP_STATUS_SUCCESS {
+            return None;
+        }
+        let mut count = caps.NumberInputValueCaps;
+        let mut values = vec![HIDP_VALUE_CAPS::default(); count as usize];
+        if count > 0
+            && unsafe { HidP_GetValueCaps(HidP_Input, values.as_mut_ptr(), &mut count, data) }
+                != HIDP_STATUS_SUCCESS
+        {
+            values.clear();
+        }
+        values.truncate(count as usize);
+        Some(Self {
+            preparsed,
+            caps,
+            values,
+        })
+    }
+
+    fn data(&self) -> PHIDP_PREPARSED_DATA {
+        PHIDP_PREPARSED_DATA(self.preparsed.as_ptr() as isize)
+    }
+
+    fn buttons(&self, report: &mut [u8]) -> Vec<u16> {
+        let mut len =
+            unsafe { HidP_MaxUsageListLength(HidP_Input, HID_USAGE_PAGE_BUTTON, self.data()) };
+        let mut usages = vec![0u16; len as usize];
+        let status = unsafe {
+            HidP_GetUsages(
+                HidP_Input,
+                HID_USAGE_PAGE_BUTTON,
+                0,
+                usages.as_mut_ptr(),
+                &mut len,
+                self.data(),
+                report,
+            )
+        };
+        if status != HIDP_STATUS_SUCCESS {
+            return Vec::new();
+        }
+        usages.truncate(len as usize);
+        usages
+    }
+
+    fn values(&self, report: &mut [u8]) -> Vec<HidValue> {
+        self.values
+            .iter()
+            .filter_map(|cap| {
+                // Ranges are vendor-specific blocks; axes and hats are single usages.
+                if cap.IsRange.as_bool() {
+                    return None;
+                }
+                let usage = unsafe { cap.Anonymous.NotRange.Usage };
+                let mut value = 0u32;
+                let status = unsafe {
+                    HidP_GetUsageValue(
+                        HidP_Input,
+                        cap.UsagePage,
+                        cap.LinkCollection,
+                        usage,
+                        &mut value,
+                        self.data(),
+                        report,
+                    )
+                };
+                (status == HIDP_STATUS_SUCCESS).then_some(HidValue {
+                    usage_page: cap.UsagePage,
+                    usage,
+                    value,
+                })
+            })
+            .collect()
+    }
+}
+
+thread_local! {
+    static LOCAL_LAYOUTS: RefCell<HashMap<isize, Option<Layout>>> = RefCell::new(HashMap::new());
+}
+
+/// Parses the last of the `count` reports of `size` bytes in `raw`; earlier
+/// ones are only kept in `HidInfo::report`.
+pub(crate) fn parse(device: HANDLE, raw: &[u8], size: usize, time: u32) -> Option<HidInfo> {
+    let id = DeviceId::from(device);
+    LOCAL_LAYOUTS.with_borrow_mut(|layouts| {
+        let layout = layouts
+            .entry(id.0)
+            .or_insert_with(|| Layout::load(device))
+            .as_ref()?;
+        let mut last = raw.get(raw.len().checked_sub(size)?..)?.to_vec();
+        Some(HidInfo {
+            device: id,
+            usage_page: layout.caps.UsagePage,
+            usage: layout.caps.Usage,
+            buttons: layout.buttons(&mut last),
+            values: layout.values(&mut last),
+            report: raw.to_vec(),
+            timestamp: Instant::now(),
+            system_time: SystemTime::now(),
+            time,
+        })
+    })
+}
+
+/// Drops what was cached for a device that went away.
+pub(crate) fn forget(device: HANDLE) {
+    LOCAL_LAYOUTS.with_borrow_mut(|layouts| layouts.remove(&DeviceId::from(device).0));
+}
@@ -0,0 +1,97 @@
+//! Shortcuts served by `RegisterHotKey` instead of the input hooks; see
+//! `ListenerBuilder::register_hotkeys`.
+
+use crate::types::{KeyMap, KeyMappingId, ShortcutSequence, VirtualKeyId, ID};
+use crate::Listener;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    MapVirtualKeyW, RegisterHotKey, UnregisterHotKey, HOT_KEY_MODIFIERS, MAPVK_VSC_TO_VK_EX,
+    MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+};
+
+/// Highest ID an application may pass to `RegisterHotKey`.
+const MAX_HOTKEY_ID: i32 = 0xBFFF;
+
+thread_local! {
+    /// Hotkeys registered by the event loop on this thread, by hotkey ID.
+    static LOCAL_HOTKEYS: RefCell<HashMap<i32, ID>> = RefCell::new(HashMap::new());
+}
+
+/// `RegisterHotKey` arguments for `sequence` if it is a single chord of
+/// side-less modifiers and one key, the only kind the system can watch.
+fn hotkey_for(sequence: &ShortcutSequence) -> Option<(HOT_KEY_MODIFIERS, u32)> {
+    let [chord] = sequence.steps() else {
+        return None;
+    };
+    let [key] = chord.normal_keys() else {
+        return None;
+    };
+    if chord.has_mouse_key() || !chord.has_modifier() {
+        return None;
+    }
+    let mut modifiers = MOD_NOREPEAT.0;
+    for modifier in chord.modifier_keys() {
+        modifiers |= match modifier {
+            VirtualKeyId::Control => MOD_CONTROL.0,
+            VirtualKeyId::Shift => MOD_SHIFT.0,
+            VirtualKeyId::Alt => MOD_ALT.0,
+            VirtualKeyId::Meta => MOD_WIN.0,
+            // Hotkeys cannot tell the left and right keys apart.
+            _ => return None,
+        };
+    }
+    let scancode = KeyMap::from(KeyMappingId::try_from(*key).ok()?).win as u32;
+    let vk = unsafe { MapVirtualKeyW(scancode, MAPVK_VSC_TO_VK_EX) };
+    (vk != 0).then_some((HOT_KEY_MODIFIERS(modifiers), vk))
+}
+
+/// Registers the listener's eligible shortcuts as hotkeys of the calling
+/// thread, which then receives their `WM_HOTKEY`, and drops the ones no
+/// longer wanted. A hotkey another application holds is left to the hooks.
+pub(crate) fn sync(listener: &Listener) {
+    let wanted: HashMap<ID, (HOT_KEY_MODIFIERS, u32)> = listener
+        .hotkey_candidates()
+        .into_iter()
+        .filter_map(|(id, sequence)| Some((id, hotkey_for(&sequence)?)))
+        .collect();
+    LOCAL_HOTKEYS.with_borrow_mut(|hotkeys| {
+        hotkeys.retain(|&hotkey, id| {
+            let keep = wanted.contains_key(id);
+            if !keep {
+                let _ = unsafe { UnregisterHotKey(None, hotkey) };
+            }
+            keep
+        });
+        let registered: HashSet<ID> = hotkeys.values().copied().collect();
+        for (id, (modifiers, vk)) in wanted {
+            if registered.contains(&id) {
+                continue;
+            }
+            let Some(hotkey) = (1..=MAX_HOTKEY_ID).find(|h| !hotkeys.contains_key(h)) else {
+                break;
+            };
+            if unsafe { RegisterHotKey(None, hotkey, modifiers, vk) }.is_ok() {
+                hotkeys.insert(hotkey, id);
+            }
+        }
+        listener.set_hotkeys(hotkeys.values().copied().collect());
+    });
+}
+
+/// The shortcut a `WM_HOTKEY` with `wparam` belongs to.
+pub(crate) fn shortcut_id(wparam: usize) -> Option<ID> {
+    LOCAL_HOTKEYS.with_borrow(|hotkeys| hotkeys.get(&(wparam as i32)).copied())
+}
+
+/// Unregisters every hotkey of the calling thread.
+pub(crate) fn clear(listener: Option<&Listener>) {
+    LOCAL_HOTKEYS.with_borrow_mut(|hotkeys| {
+        for (hotkey, _) in hotkeys.drain() {
+            let _ = unsafe { UnregisterHotKey(None, hotkey) };
+        }
+    });
+    if let Some(listener) = listener {
+        listener.set_hotkeys(HashSet::new());
+    }
+}
@@ -0,0 +1,94 @@
+//! The keyboard layout of the foreground thread, which decides what text a
+//! key types.
+
+use crate::consts;
+use crate::types::LayoutInfo;
+use std::cell::Cell;
+use windows::Win32::Globalization::LCIDToLocaleName;
+use windows::Win32::UI::Input::KeyboardAndMouse::{GetKeyboardLayout, VkKeyScanExW, HKL};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, GetWindowThreadProcessId, KillTimer, SetTimer,
+};
+
+/// Longest locale name, `LOCALE_NAME_MAX_LENGTH`.
+const LOCALE_NAME_MAX_LENGTH: usize = 85;
+
+thread_local! {
+    /// Layout last seen by `poll` on this thread.
+    static LOCAL_LAYOUT: Cell<Option<isize>> = const { Cell::new(None) };
+    static LOCAL_POLL_TIMER: Cell<usize> = const { Cell::new(0) };
+    /// Layout last asked about in `has_alt_gr`, and the answer.
+    static LOCAL_ALT_GR: Cell<Option<(isize, bool)>> = const { Cell::new(None) };
+}
+
+/// Layout of the thread owning the foreground window.
+pub(crate) fn foreground_layout() -> HKL {
+    unsafe {
+        let thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        GetKeyboardLayout(thread)
+    }
+}
+
+/// Whether `layout` types characters with AltGr, which it reports as left
+/// Ctrl plus right Alt.
+pub(crate) fn has_alt_gr(layout: HKL) -> bool {
+    let id = layout.0 as isize;
+    if let Some((last, alt_gr)) = LOCAL_ALT_GR.get() {
+        if last == id {
+            return alt_gr;
+        }
+    }
+    // The high byte of `VkKeyScanExW` holds the shift state: 2 Ctrl, 4 Alt.
+    let alt_gr = (0x21..0x250).any(|ch| {
+        let scan = unsafe { VkKeyScanExW(ch, layout) };
+        scan != -1 && (scan >> 8) & 6 == 6
+    });
+    LOCAL_ALT_GR.set(Some((id, alt_gr)));
+    alt_gr
+}
+
+fn layout_info(layout: HKL) -> LayoutInfo {
+    let id = layout.0 as isize;
+    let language = (id & 0xFFFF) as u16;
+    let mut name = [0u16; LOCALE_NAME_MAX_LENGTH];
+    let len = unsafe { LCIDToLocaleName(language as u32, Some(&mut name), 0) };
+    let locale = (len > 1).then(|| String::from_utf16_lossy(&name[..len as usize - 1]));
+    LayoutInfo {
+        id,
+        language,
+        locale,
+    }
+}
+
+/// The layout keys are currently typed with.
+pub fn current_layout() -> LayoutInfo {
+    layout_info(foreground_layout())
+}
+
+/// The foreground layout if it differs from the last call on this thread.
+/// The first call only takes note of it.
+pub(crate) fn poll() -> Option<LayoutInfo> {
+    let layout = foreground_layout();
+    let last = LOCAL_LAYOUT.replace(Some(layout.0 as isize));
+    (last.is_some() && last != Some(layout.0 as isize)).then(|| layout_info(layout))
+}
+
+/// Starts the timer `poll` is driven by on the calling event loop thread;
+/// the foreground thread's `WM_INPUTLANGCHANGE` is not sent to us.
+pub(crate) fn start_polling() {
+    poll();
+    let timer = unsafe { SetTimer(None, 0, consts::LAYOUT_POLL_INTERVAL, None) };
+    LOCAL_POLL_TIMER.set(timer);
+}
+
+pub(crate) fn is_poll_timer(timer: usize) -> bool {
+    timer != 0 && LOCAL_POLL_TIMER.get() == timer
+}
+
+pub(crate) fn stop_polling() {
+    let timer = LOCAL_POLL_TIMER.replace(0);
+    if timer != 0 {
+        let _ = unsafe { KillTimer(None, timer) };
+    }
+    LOCAL_LAYOUT.set(None);
+}
@@ -6,25 +6,107 @@
 //! Created Data: 2024-09-29
 //!
 //! Description: add msg listener
-use super::event_loop::{EventLoop, EVENT_LOOP_MANAGER};
-use super::worker::{Worker, WorkerMsg};
-use super::WM_USER_RECHECK_HOOK;
+use super::backend::InputBackend;
+use super::worker::{
+    CallbackPool, MetricsRecorder, MoveThrottle, Routes, ThreadSpec, Worker, WorkerMsg,
+};
+use super::{WM_USER_APPLY_CURSOR_CLIP, WM_USER_RECHECK_HOOK};
 use crate::consts;
-use crate::types::{EventListener, JoinHandleType};
-use crate::types::{EventType, KeyState, Shortcut, ID};
+use crate::hotcorner::HotCorner;
+use crate::shake::ShakeDetector;
+use crate::types::{
+    Backend, CallbackPanic, ClickState, ConflictError, CoordinateSpace, Corner, Event, EventFilter,
+    EventListenerReport, GroupId, KeyId, KeyInfo, KeyState, ListenerStateReport, Metrics,
+    Modifiers, MouseKey, Pos, Propagation, Rect, RegisterError, ShakeOptions, Shortcut,
+    ShortcutEvent, ShortcutOptions, ShortcutReport, ShortcutSequence, SidePolicy, SlowCallback,
+    ThreadPriority, TriggerEdge, VirtualKeyId, WorkerAffinity, WorkerReport, ID,
+};
+use crate::types::{EventListener, JoinHandleType, KmHookError};
 use crate::utils::gen_id;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
 use std::result::Result;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
 
-type FnEvent = Arc<Box<dyn Fn(EventType) + Send + Sync + 'static>>;
-type FnShourtcut = Arc<Box<dyn Fn() + Send + Sync + 'static>>;
+type FnEvent = Arc<Box<dyn Fn(Event) -> Propagation + Send + Sync + 'static>>;
+type FnShourtcut = Arc<Box<dyn Fn(ShortcutEvent) + Send + Sync + 'static>>;
+type FnPredicate = Arc<Box<dyn Fn(&Event) -> bool + Send + Sync + 'static>>;
+type FnError = Arc<Box<dyn Fn(CallbackPanic) + Send + Sync + 'static>>;
+type FnSlow = Arc<Box<dyn Fn(SlowCallback) + Send + Sync + 'static>>;
+
+/// The error and slow-callback handlers of a listener, for the callbacks run
+/// off the worker: autofire repeats, queued listeners and hot corners.
+#[derive(Clone)]
+struct CallbackGuard {
+    error_handler: Arc<Mutex<Option<FnError>>>,
+    slow_callback: Arc<Mutex<Option<(Duration, FnSlow)>>>,
+}
+
+impl CallbackGuard {
+    /// Runs the callback of registration `id`. A panic is caught and
+    /// reported to the error handler, a slow run to the slow-callback handler.
+    fn run<F>(&self, id: ID, job: F) -> Propagation
+    where
+        F: FnOnce() -> Propagation,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("dispatch", id).entered();
+        let started = Instant::now();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+        let elapsed = started.elapsed();
+        let slow = self.slow_callback.lock().unwrap().clone();
+        if let Some((threshold, handler)) = slow {
+            if elapsed > threshold {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?elapsed, "slow callback");
+                handler(SlowCallback { id, elapsed });
+            }
+        }
+        result.unwrap_or_else(|payload| {
+            #[cfg(feature = "tracing")]
+            tracing::error!("callback panicked");
+            let handler = self.error_handler.lock().unwrap().clone();
+            if let Some(handler) = handler {
+                handler(CallbackPanic::new(id, payload));
+            }
+            Propagation::Continue
+        })
+    }
+}
+
+/// How far a `ShortcutSequence` has been typed.
+#[derive(Debug)]
+struct SequenceProgress {
+    next: usize,
+    last: Instant,
+    /// Complete and waiting for a release (`TriggerEdge::Release`).
+    armed: bool,
+    fired: Option<Instant>,
+    /// Cleared to stop a running autofire thread.
+    autofire: Option<Arc<AtomicBool>>,
+    /// Runs so far, autofire repeats included.
+    count: u64,
+}
+
+impl SequenceProgress {
+    /// Disarms a pending release and stops autofire.
+    fn interrupt(&mut self) {
+        self.armed = false;
+        if let Some(running) = self.autofire.take() {
+            running.store(false, Ordering::Relaxed);
+        }
+    }
+}
 
 #[derive(Clone)]
 struct FnShourtcutTrigger {
     cb: FnShourtcut,
+    options: ShortcutOptions,
+    progress: Arc<Mutex<SequenceProgress>>,
+    enabled: bool,
 }
 
 impl FnShourtcutTrigger {
@@ -32,266 +114,2561 @@ impl FnShourtcutTrigger {
     where
         F: Fn() + Send + Sync + 'static,
     {
-        Self {
-            cb: Arc::new(Box::new(cb)),
-        }
-    }
-}
+        Self::with_options(move |_| cb(), ShortcutOptions::default())
+    }
+
+    fn with_options<F>(cb: F, options: ShortcutOptions) -> Self
+    where
+        F: Fn(ShortcutEvent) + Send + Sync + 'static,
+    {
+        Self {
+            cb: Arc::new(Box::new(cb)),
+            options,
+            progress: Arc::new(Mutex::new(SequenceProgress {
+                next: 0,
+                last: Instant::now(),
+                armed: false,
+                fired: None,
+                autofire: None,
+                count: 0,
+            })),
+            enabled: true,
+        }
+    }
+
+    fn tap_timeout(&self) -> Duration {
+        self.options
+            .tap_timeout
+            .unwrap_or(Duration::from_millis(consts::DEFAULT_TAP_TIMEOUT))
+    }
+
+    fn side_policy(&self) -> SidePolicy {
+        self.options.side_policy.unwrap_or_default()
+    }
+
+    /// Whether `fired` used up `ShortcutOptions::max_triggers`.
+    fn is_spent(&self, fired: &ShortcutEvent) -> bool {
+        self.spent_at(fired.trigger_count)
+    }
+
+    fn spent_at(&self, trigger_count: u64) -> bool {
+        self.options
+            .max_triggers
+            .map_or(false, |max| trigger_count >= max)
+    }
+
+    fn sequence_timeout(&self) -> Duration {
+        self.options
+            .sequence_timeout
+            .unwrap_or(Duration::from_millis(consts::DEFAULT_SEQUENCE_TIMEOUT))
+    }
+
+    /// The step `key_info` has to complete next; restarts sequences that
+    /// timed out.
+    fn next_step(&self) -> usize {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.next > 0 && progress.last.elapsed() > self.sequence_timeout() {
+            progress.next = 0;
+        }
+        progress.next
+    }
+
+    /// Advances the sequence and returns `true` when its last step was typed.
+    fn advance(&self, sequence: &ShortcutSequence, pressed: &PressedChord) -> bool {
+        // Pressing anything changes the chord an armed shortcut waits to see released.
+        self.progress.lock().unwrap().interrupt();
+        let steps = sequence.steps();
+        let mut next = self.next_step();
+        let policy = self.side_policy();
+        if !pressed.triggers(&steps[next], policy) {
+            // Modifiers held between steps ("Ctrl+K, Ctrl+C") keep the progress;
+            // any other key starts over, possibly as the first step.
+            if pressed.modifier {
+                return false;
+            }
+            next = 0;
+            if !pressed.triggers(&steps[0], policy) {
+                self.progress.lock().unwrap().next = 0;
+                return false;
+            }
+        }
+        let mut progress = self.progress.lock().unwrap();
+        progress.last = Instant::now();
+        if next + 1 == steps.len() {
+            progress.next = 0;
+            if self.options.edge != TriggerEdge::Press && !pressed.chord.has_wheel_key() {
+                progress.armed = true;
+                return false;
+            }
+            true
+        } else {
+            progress.next = next + 1;
+            false
+        }
+    }
+
+    /// Fires a shortcut armed by `advance` for `TriggerEdge::Release` or
+    /// `TriggerEdge::Tap`.
+    fn release(&self) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+        let armed = progress.armed;
+        progress.interrupt();
+        if !armed {
+            return false;
+        }
+        self.options.edge != TriggerEdge::Tap || progress.last.elapsed() <= self.tap_timeout()
+    }
+
+    /// Records a completion and returns what to pass the callback; `None`
+    /// while `ShortcutOptions::cooldown` from the previous run has not elapsed.
+    fn fire(
+        &self,
+        id: ID,
+        sequence: &ShortcutSequence,
+        key_info: Option<&KeyInfo>,
+        config: &ListenerConfig,
+        guard: &CallbackGuard,
+    ) -> Option<ShortcutEvent> {
+        let mut progress = self.progress.lock().unwrap();
+        // Spent by autofire repeats, which cannot unregister it.
+        if self.spent_at(progress.count) {
+            return None;
+        }
+        if let (Some(cooldown), Some(fired)) = (self.options.cooldown, progress.fired) {
+            if fired.elapsed() < cooldown {
+                return None;
+            }
+        }
+        progress.fired = Some(Instant::now());
+        progress.count += 1;
+        let event = ShortcutEvent {
+            id,
+            shortcut: sequence.clone(),
+            timestamp: Instant::now(),
+            trigger_count: progress.count,
+            key_info: key_info.cloned(),
+        };
+        let autofire = match self.options.edge {
+            TriggerEdge::Press if !self.is_spent(&event) => self.options.autofire,
+            _ => None,
+        };
+        if let Some(interval) = autofire {
+            let max_triggers = self.options.max_triggers;
+            let running = Arc::new(AtomicBool::new(true));
+            progress.autofire = Some(running.clone());
+            let cb = self.cb.clone();
+            // Ends with the shortcut if it is removed while held.
+            let registered = Arc::downgrade(&self.progress);
+            let first = event.clone();
+            let guard = guard.clone();
+            let spawned = config.worker_thread("autofire").spawn(move || loop {
+                std::thread::sleep(interval);
+                let trigger_count = match registered.upgrade() {
+                    Some(progress) if running.load(Ordering::Relaxed) => {
+                        let mut progress = progress.lock().unwrap();
+                        progress.count += 1;
+                        progress.count
+                    }
+                    _ => break,
+                };
+                let spent = max_triggers.map_or(false, |max| trigger_count >= max);
+                let repeat = ShortcutEvent {
+                    timestamp: Instant::now(),
+                    trigger_count,
+                    ..first.clone()
+                };
+                guard.run(id, || {
+                    cb(repeat);
+                    Propagation::Continue
+                });
+                if spent {
+                    break;
+                }
+            });
+            if spawned.is_err() {
+                progress.autofire = None;
+            }
+        }
+        Some(event)
+    }
+}
+
+/// The keys and mouse buttons held when something was pressed.
+struct PressedChord {
+    chord: Shortcut,
+    /// The press was a modifier key.
+    modifier: bool,
+}
+
+impl PressedChord {
+    fn from_key(key_info: &KeyInfo) -> Option<Self> {
+        if key_info.state != KeyState::Pressed {
+            return None;
+        }
+        key_info.keyboard_state.as_ref().map(|snapshot| Self {
+            chord: snapshot.to_shortcut(),
+            modifier: key_info.key_id.is_modifier(),
+        })
+    }
+
+    fn triggers(&self, shortcut: &Shortcut, policy: SidePolicy) -> bool {
+        // Check if the modifier key is pressed, and when used with other keys,
+        // the last key pressed must not be a modifier key.
+        shortcut.is_match_with(&self.chord, policy)
+            && !(shortcut.has_modifier() && shortcut.has_normal_key() && self.modifier)
+    }
+}
+
+/// Input held down, as last seen by the shared worker.
+struct ChordState {
+    keys: Shortcut,
+    buttons: Vec<MouseKey>,
+}
+
+/// Input held down and the cursor position, for the state queries such as
+/// `Listener::is_key_pressed`. Updated by the shared worker before callbacks
+/// run.
+#[derive(Debug, Default)]
+struct HeldInput {
+    keys: Vec<KeyId>,
+    modifiers: Modifiers,
+    buttons: Vec<MouseKey>,
+    cursor: Pos,
+}
+
+impl HeldInput {
+    fn update(&mut self, event: &Event) {
+        match event {
+            Event::KeyboardEvent(key_info) => match &key_info.keyboard_state {
+                Some(snapshot) => {
+                    self.keys = snapshot.keys.iter().map(|&key| KeyId::from(key)).collect();
+                    self.modifiers = snapshot.modifiers;
+                }
+                None if key_info.state == KeyState::Pressed => {
+                    if !self.keys.contains(&key_info.key_id) {
+                        self.keys.push(key_info.key_id);
+                    }
+                    self.modifiers |= Modifiers::from_key(key_info.key_id.into());
+                }
+                None => {
+                    self.keys.retain(|&held| held != key_info.key_id);
+                    self.modifiers
+                        .remove(Modifiers::from_key(key_info.key_id.into()));
+                }
+            },
+            Event::MouseEvent(mouse_info) => {
+                self.cursor = mouse_info.pos.clone();
+                if let Some(button) = &mouse_info.button {
+                    let key = MouseKey::from(button);
+                    if button.state() == ClickState::Pressed {
+                        if !self.buttons.contains(&key) {
+                            self.buttons.push(key);
+                        }
+                    } else {
+                        self.buttons.retain(|&held| held != key);
+                    }
+                }
+            }
+            #[allow(unreachable_patterns)]
+            _ => {}
+        }
+    }
+}
+
+impl Default for ChordState {
+    fn default() -> Self {
+        Self {
+            keys: Shortcut::default(),
+            buttons: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ShortcutTriggerInfo {
+    trigger: u32,
+    last_trigger_time: Instant,
+}
+
+impl ShortcutTriggerInfo {
+    fn new() -> Self {
+        Self {
+            trigger: 0,
+            last_trigger_time: Instant::now(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.trigger = 0;
+        self.last_trigger_time = Instant::now();
+    }
+
+    fn increase(&mut self) {
+        self.trigger += 1;
+        self.last_trigger_time = Instant::now();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ListenerConfig {
+    pub(crate) backend: Backend,
+    pub(crate) message_only_window: bool,
+    pub(crate) detect_conflicts: bool,
+    pub(crate) ignore_injected: bool,
+    pub(crate) key_repeats: bool,
+    pub(crate) suppress_in_fullscreen: bool,
+    pub(crate) window_info: bool,
+    pub(crate) cursor_window_info: bool,
+    pub(crate) coordinate_space: CoordinateSpace,
+    pub(crate) max_mouse_moves: Option<u32>,
+    pub(crate) coalesce_mouse_moves: bool,
+    pub(crate) hook_watchdog: Option<Duration>,
+    pub(crate) register_hotkeys: bool,
+    pub(crate) dll_hook: Option<(PathBuf, u32)>,
+    pub(crate) buffered_raw_input: bool,
+    pub(crate) exclusive_raw_input: bool,
+    pub(crate) game_controllers: bool,
+    pub(crate) side_policy: SidePolicy,
+    pub(crate) velocity_window: Duration,
+    pub(crate) focus_events: bool,
+    pub(crate) poll_buffer: Option<usize>,
+    pub(crate) track_input_state: bool,
+    pub(crate) callback_threads: Option<usize>,
+    pub(crate) subscriber_queues: Option<usize>,
+    pub(crate) hook_thread_priority: ThreadPriority,
+    pub(crate) worker_thread_priority: ThreadPriority,
+    pub(crate) cpu_affinity: Option<usize>,
+    pub(crate) worker_queue_capacity: Option<usize>,
+    pub(crate) keyboard_state: bool,
+    pub(crate) thread_name_prefix: String,
+    #[cfg(feature = "suppression")]
+    pub(crate) block_escape: Shortcut,
+    #[cfg(feature = "suppression")]
+    pub(crate) suppressed_chords: Vec<Shortcut>,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            message_only_window: true,
+            detect_conflicts: false,
+            ignore_injected: false,
+            key_repeats: false,
+            suppress_in_fullscreen: false,
+            window_info: false,
+            cursor_window_info: false,
+            coordinate_space: CoordinateSpace::Physical,
+            max_mouse_moves: None,
+            coalesce_mouse_moves: false,
+            hook_watchdog: None,
+            register_hotkeys: true,
+            dll_hook: None,
+            buffered_raw_input: false,
+            exclusive_raw_input: false,
+            game_controllers: false,
+            side_policy: SidePolicy::default(),
+            velocity_window: Duration::from_millis(consts::DEFAULT_VELOCITY_WINDOW),
+            focus_events: false,
+            poll_buffer: None,
+            track_input_state: false,
+            callback_threads: None,
+            subscriber_queues: None,
+            hook_thread_priority: ThreadPriority::TimeCritical,
+            worker_thread_priority: ThreadPriority::Normal,
+            cpu_affinity: None,
+            worker_queue_capacity: None,
+            keyboard_state: true,
+            thread_name_prefix: "kmhook".to_string(),
+            #[cfg(feature = "suppression")]
+            block_escape: "Ctrl+Alt+Shift+Escape".parse().unwrap(),
+            #[cfg(feature = "suppression")]
+            suppressed_chords: Vec::new(),
+        }
+    }
+}
+
+impl ListenerConfig {
+    pub(crate) fn thread_name(&self, role: &str) -> String {
+        format!("{}-{}", self.thread_name_prefix, role)
+    }
+
+    pub(crate) fn event_loop_thread(&self) -> ThreadSpec {
+        ThreadSpec {
+            name: self.thread_name("event-loop"),
+            priority: self.hook_thread_priority,
+            cpu_affinity: self.cpu_affinity,
+        }
+    }
+
+    /// Workers and the other threads running callbacks.
+    pub(crate) fn worker_thread(&self, role: &str) -> ThreadSpec {
+        ThreadSpec {
+            name: self.thread_name(role),
+            priority: self.worker_thread_priority,
+            cpu_affinity: self.cpu_affinity,
+        }
+    }
+}
+
+/// Configures a `Listener` before it is created.
+#[derive(Debug, Clone, Default)]
+pub struct ListenerBuilder {
+    config: ListenerConfig,
+}
+
+impl ListenerBuilder {
+    /// How input is captured. `startup()` fails with
+    /// `KmHookError::BackendUnavailable` if its feature is not enabled, so an
+    /// application can fall back to another one. Default is `Backend::default()`.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.config.backend = backend;
+        self
+    }
+
+    /// Receive raw input through a message-only window (`HWND_MESSAGE` parent)
+    /// instead of a hidden top-level window. Default is `true`.
+    ///
+    /// Message-only windows are invisible to window enumeration but do not
+    /// receive broadcast messages such as `WM_DISPLAYCHANGE`.
+    pub fn message_only_window(mut self, enable: bool) -> Self {
+        self.config.message_only_window = enable;
+        self
+    }
+
+    /// Reject shortcuts that overlap a registered one (see
+    /// `Listener::find_conflicts`) with `RegisterError::Conflict`, instead
+    /// of only exact duplicates. Default is `false`.
+    pub fn detect_conflicts(mut self, enable: bool) -> Self {
+        self.config.detect_conflicts = enable;
+        self
+    }
+
+    /// Drop events synthesized by `SendInput` and similar before they reach
+    /// callbacks or shortcuts, so automation driving the same machine cannot
+    /// trigger itself. Default is `false`.
+    pub fn ignore_injected(mut self, enable: bool) -> Self {
+        self.config.ignore_injected = enable;
+        self
+    }
+
+    /// Deliver auto-repeated key presses to event listeners, tagged with
+    /// `KeyInfo::is_repeat`. Shortcuts never fire on repeats. Default is `false`.
+    pub fn key_repeats(mut self, enable: bool) -> Self {
+        self.config.key_repeats = enable;
+        self
+    }
+
+    /// Don't fire (or swallow for) shortcuts while a fullscreen application,
+    /// typically a game, is in the foreground. Event listeners still receive
+    /// everything. Not detected on Linux. Default is `false`.
+    pub fn suppress_in_fullscreen(mut self, enable: bool) -> Self {
+        self.config.suppress_in_fullscreen = enable;
+        self
+    }
+
+    /// Attach the foreground window (`KeyInfo::window`, `MouseInfo::window`)
+    /// to every event. Costs a few system calls per event. Not available on
+    /// Linux. Default is `false`.
+    pub fn window_info(mut self, enable: bool) -> Self {
+        self.config.window_info = enable;
+        self
+    }
+
+    /// Attach the window under the cursor (`MouseInfo::target`) to mouse
+    /// button and wheel events. Not available on Linux. Default is `false`.
+    pub fn cursor_window_info(mut self, enable: bool) -> Self {
+        self.config.cursor_window_info = enable;
+        self
+    }
+
+    /// Report `Event::FocusChanged` when another window comes to the
+    /// foreground, in order with the input events. Hook and raw-input
+    /// backends on Windows only. Default is `false`.
+    pub fn focus_events(mut self, enable: bool) -> Self {
+        self.config.focus_events = enable;
+        self
+    }
+
+    /// Keep the last `capacity` events for `Listener::poll_events`, so a
+    /// GUI can take them once per frame instead of handling callbacks. Older
+    /// events are dropped when it is full. Default is no buffer.
+    pub fn poll_buffer(mut self, capacity: usize) -> Self {
+        self.config.poll_buffer = Some(capacity);
+        self
+    }
+
+    /// Keep the hooks installed while the listener runs, even with nothing
+    /// registered, so `Listener::is_key_pressed` and the other state queries
+    /// stay current. Default is `false`: the state is only tracked while a
+    /// listener, shortcut or `poll_buffer` needs the hooks.
+    pub fn track_input_state(mut self, enable: bool) -> Self {
+        self.config.track_input_state = enable;
+        self
+    }
+
+    /// Run event and shortcut callbacks on a pool of `threads` threads
+    /// instead of on the worker, so a slow callback does not hold up the
+    /// others. Callbacks may then run concurrently and out of order. Default
+    /// is to run them on the worker, one after another.
+    pub fn callback_threads(mut self, threads: usize) -> Self {
+        self.config.callback_threads = Some(threads);
+        self
+    }
+
+    /// Give every event listener a thread and a queue of up to `capacity`
+    /// events of its own, as `Listener::add_event_listener_queued` does for
+    /// one. Listeners then run side by side and a slow one holds up no
+    /// other; a full queue drops events for its listener alone, counted in
+    /// `Metrics::dropped_events`. `Propagation::Stop` has no effect. Default
+    /// is to run listeners on the worker, one after another.
+    pub fn subscriber_queues(mut self, capacity: usize) -> Self {
+        self.config.subscriber_queues = Some(capacity);
+        self
+    }
+
+    /// Priority of the event loop thread, which runs the hooks or the raw
+    /// input window. Lower it if `TimeCritical` starves other work; too low and Windows
+    /// may drop a low-level hook that answers late. Ignored on Linux.
+    /// Default is `ThreadPriority::TimeCritical`.
+    pub fn hook_thread_priority(mut self, priority: ThreadPriority) -> Self {
+        self.config.hook_thread_priority = priority;
+        self
+    }
+
+    /// Priority of the worker threads and the other threads running
+    /// callbacks, hot corner timers included. Ignored on Linux. Default is `ThreadPriority::Normal`.
+    pub fn worker_thread_priority(mut self, priority: ThreadPriority) -> Self {
+        self.config.worker_thread_priority = priority;
+        self
+    }
+
+    /// Run the event loop and the threads running callbacks only on the
+    /// CPUs set in `mask`, as for `SetThreadAffinityMask`. Ignored on Linux.
+    /// Default is any CPU.
+    pub fn cpu_affinity(mut self, mask: Option<usize>) -> Self {
+        self.config.cpu_affinity = mask;
+        self
+    }
+
+    /// Bound each worker's queue to `capacity` messages. Events that arrive
+    /// while it is full are dropped and counted in
+    /// `Metrics::dropped_events`, so a stuck callback cannot grow memory
+    /// without limit. Default is unbounded.
+    pub fn worker_queue_capacity(mut self, capacity: Option<usize>) -> Self {
+        self.config.worker_queue_capacity = capacity;
+        self
+    }
+
+    /// Attach the pressed keys to each `KeyInfo::keyboard_state` delivered
+    /// to event listeners. Shortcuts work either way. Default is `true`.
+    pub fn keyboard_state(mut self, enable: bool) -> Self {
+        self.config.keyboard_state = enable;
+        self
+    }
+
+    /// Names of the spawned threads start with `prefix`, e.g.
+    /// "kmhook-event-loop" and "kmhook-worker". Default is "kmhook".
+    pub fn thread_name_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config.thread_name_prefix = prefix.into();
+        self
+    }
+
+    /// Chord that ends a `Listener::block_input` early, so a stuck block can
+    /// always be escaped. Default is Ctrl+Alt+Shift+Escape.
+    #[cfg(feature = "suppression")]
+    pub fn block_input_escape(mut self, chord: Shortcut) -> Self {
+        self.config.block_escape = chord;
+        self
+    }
+
+    /// Keeps `chord` from reaching the system while the listener runs, e.g.
+    /// `LWin` to disable the Start menu or `Alt+Tab`. Only the key completing
+    /// the chord is swallowed; listeners still receive it. May be called
+    /// more than once. Needs the ll-hook backend: `startup` fails with
+    /// `KmHookError::BackendUnavailable` on the others.
+    #[cfg(feature = "suppression")]
+    pub fn suppress_chord(mut self, chord: Shortcut) -> Self {
+        self.config.suppressed_chords.push(chord);
+        self
+    }
+
+    /// Units of reported mouse positions. Default is
+    /// `CoordinateSpace::Physical`.
+    pub fn coordinate_space(mut self, space: CoordinateSpace) -> Self {
+        self.config.coordinate_space = space;
+        self
+    }
+
+    /// Forward at most `per_second` mouse moves; the movement of skipped ones
+    /// is added to the next, or sent on its own once the mouse rests, so the
+    /// last position is never lost. Clicks and wheel turns are never dropped.
+    /// Default is unlimited.
+    pub fn max_mouse_moves(mut self, per_second: Option<u32>) -> Self {
+        self.config.max_mouse_moves = per_second;
+        self
+    }
+
+    /// When a worker falls behind, deliver only the latest of the mouse moves
+    /// queued for it, with their movement summed. Default is `false`.
+    pub fn coalesce_mouse_moves(mut self, enable: bool) -> Self {
+        self.config.coalesce_mouse_moves = enable;
+        self
+    }
+
+    /// Span `MouseInfo::velocity` is averaged over; longer is smoother but
+    /// slower to follow. Default is 50 ms.
+    pub fn velocity_window(mut self, window: Duration) -> Self {
+        self.config.velocity_window = window;
+        self
+    }
+
+    /// Every `interval`, check that the low-level hooks still see the input
+    /// the system receives and reinstall the one Windows dropped, reporting
+    /// it as `Event::HookLost` followed by `Event::HookReinstalled`. Missed
+    /// input is put down to the mouse hook if the cursor moved and to the
+    /// keyboard hook otherwise, so mouse clicks without movement can be
+    /// mistaken for a lost keyboard hook. ll-hook backend only; default is
+    /// off.
+    pub fn hook_watchdog(mut self, interval: Option<Duration>) -> Self {
+        self.config.hook_watchdog = interval;
+        self
+    }
+
+    /// Serve shortcuts that are a single chord of side-less modifiers and one
+    /// key, fire on press and have no autofire, context or device through
+    /// `RegisterHotKey` rather than the keyboard hook, which is then only
+    /// installed if something else needs it. Combinations another
+    /// application holds, or that cannot be registered, fall back to the
+    /// hook. The system consumes hotkey keystrokes as if
+    /// `ShortcutOptions::swallow` were set, and their `ShortcutEvent` has no
+    /// `key_info`; turn this off to let them reach the focused application.
+    /// Windows only; default is `true`.
+    pub fn register_hotkeys(mut self, enable: bool) -> Self {
+        self.config.register_hotkeys = enable;
+        self
+    }
+
+    /// Read raw input in batches with `GetRawInputBuffer` whenever the event
+    /// loop wakes up, instead of one `GetRawInputData` call per `WM_INPUT`.
+    /// Meant for high polling rate mice. The `time` of events read in a
+    /// batch is that of the last message the loop took. raw-input backend
+    /// only; default is `false`.
+    pub fn buffered_raw_input(mut self, enable: bool) -> Self {
+        self.config.buffered_raw_input = enable;
+        self
+    }
+
+    /// Register raw input with `RIDEV_NOLEGACY`, plus `RIDEV_CAPTUREMOUSE`
+    /// for the mouse, so keyboard and mouse input no longer produce legacy
+    /// messages (`WM_KEYDOWN`, `WM_MOUSEMOVE`, ...) in this process and
+    /// clicks don't activate other windows. Other applications still get
+    /// their input; kiosk tools pair this with swallowing and re-injecting.
+    /// raw-input backend only; default is `false`.
+    pub fn exclusive_raw_input(mut self, enable: bool) -> Self {
+        self.config.exclusive_raw_input = enable;
+        self
+    }
+
+    /// Also read gamepads and joysticks through raw input and deliver their
+    /// reports as `Event::HidEvent`. raw-input backend only; default is
+    /// `false`.
+    pub fn game_controllers(mut self, enable: bool) -> Self {
+        self.config.game_controllers = enable;
+        self
+    }
+
+    /// How shortcuts match the left and right modifier keys, unless set in
+    /// their `ShortcutOptions`. Default is `SidePolicy::StrictSides`.
+    pub fn side_policy(mut self, policy: SidePolicy) -> Self {
+        self.config.side_policy = policy;
+        self
+    }
+
+    /// Capture the input of the thread `thread_id` of another process, e.g.
+    /// the one `GetWindowThreadProcessId` returns for its window, with hooks
+    /// set from `dll`, a build of `kmhook-winhook` matching the target's
+    /// bitness. Selects `Backend::DllHook`. Only sees what that thread takes
+    /// off its message queue and cannot swallow it. Windows only.
+    pub fn dll_hook(mut self, dll: impl Into<PathBuf>, thread_id: u32) -> Self {
+        self.config.backend = Backend::DllHook;
+        self.config.dll_hook = Some((dll.into(), thread_id));
+        self
+    }
+
+    pub fn build(self) -> Arc<Listener> {
+        Listener::with_config(self.config)
+    }
+}
+
+/// An active `Listener::block_input`.
+#[cfg(feature = "suppression")]
+#[derive(Debug)]
+struct InputBlock {
+    id: ID,
+    until: Option<Instant>,
+}
+
+struct EventHandler {
+    filter: EventFilter,
+    /// Checked on the worker after `filter`; see `add_event_listener_filtered`.
+    predicate: Option<FnPredicate>,
+    affinity: WorkerAffinity,
+    priority: i32,
+    /// Runs left before the handler unregisters itself; see
+    /// `add_event_listener_times`.
+    remaining: Option<Arc<AtomicU64>>,
+    cb: FnEvent,
+}
+
+pub struct Listener {
+    config: ListenerConfig,
+    listener_event_loop: Mutex<Option<Arc<dyn InputBackend>>>,
+    workers: Mutex<HashMap<WorkerAffinity, Arc<Worker>>>,
+    event_map: Mutex<HashMap<ID, EventHandler>>,
+    shortcut_map: Mutex<HashMap<ID, (ShortcutSequence, FnShourtcutTrigger)>>,
+    shortcut_ex_map: Mutex<HashMap<ID, Vec<ID>>>,
+    shortcut_groups: Mutex<HashMap<GroupId, Vec<ID>>>,
+    chord_state: Mutex<ChordState>,
+    held: Mutex<HeldInput>,
+    polled: Mutex<VecDeque<Event>>,
+    /// Started by the first `startup` or `run_blocking`.
+    callback_pool: OnceLock<CallbackPool>,
+    error_handler: Arc<Mutex<Option<FnError>>>,
+    slow_callback: Arc<Mutex<Option<(Duration, FnSlow)>>>,
+    cursor_clip: Mutex<Option<Rect>>,
+    move_throttle: Arc<Mutex<MoveThrottle>>,
+    metrics: Arc<MetricsRecorder>,
+    /// Shortcuts currently registered with `RegisterHotKey`.
+    hotkeys: Mutex<HashSet<ID>>,
+    #[cfg(feature = "suppression")]
+    input_block: Mutex<Option<InputBlock>>,
+}
+
+impl Listener {
+    pub fn builder() -> ListenerBuilder {
+        ListenerBuilder::default()
+    }
+
+    fn with_config(config: ListenerConfig) -> Arc<Self> {
+        let listener = Self {
+            callback_pool: OnceLock::new(),
+            config,
+            listener_event_loop: Mutex::new(None),
+            event_map: Mutex::new(HashMap::new()),
+            shortcut_map: Mutex::new(HashMap::new()),
+            workers: Mutex::new(HashMap::new()),
+            shortcut_ex_map: Mutex::new(HashMap::new()),
+            shortcut_groups: Mutex::new(HashMap::new()),
+            chord_state: Mutex::new(ChordState::default()),
+            held: Mutex::new(HeldInput::default()),
+            polled: Mutex::new(VecDeque::new()),
+            error_handler: Arc::new(Mutex::new(None)),
+            slow_callback: Arc::new(Mutex::new(None)),
+            cursor_clip: Mutex::new(None),
+            move_throttle: Arc::new(Mutex::new(MoveThrottle::default())),
+            metrics: Arc::new(MetricsRecorder::default()),
+            hotkeys: Mutex::new(HashSet::new()),
+            #[cfg(feature = "suppression")]
+            input_block: Mutex::new(None),
+        };
+        let rc = Arc::new(listener);
+        *rc.listener_event_loop.lock().unwrap() = super::new_backend(rc.config.backend, &rc);
+        rc.add_worker(WorkerAffinity::Shared);
+        rc
+    }
+
+    /// A listener with default settings that captures input with `backend`.
+    pub fn with_backend(backend: Backend) -> Arc<Self> {
+        ListenerBuilder::default().backend(backend).build()
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.config.backend
+    }
+
+    pub(crate) fn config(&self) -> &ListenerConfig {
+        &self.config
+    }
+
+    fn min_move_interval(&self) -> Option<Duration> {
+        self.config
+            .max_mouse_moves
+            .map(|per_second| Duration::from_secs(1) / per_second.max(1))
+    }
+
+    pub(crate) fn post_worker_msg(&self, mut msg: WorkerMsg) {
+        self.metrics.received(&mut msg);
+        let msgs = match self.min_move_interval() {
+            Some(min_interval) => {
+                self.move_throttle
+                    .lock()
+                    .unwrap()
+                    .filter(msg, min_interval, &self.metrics)
+            }
+            None => [None, Some(msg)],
+        };
+        for msg in msgs.into_iter().flatten() {
+            for worker in self.workers.lock().unwrap().values() {
+                worker.post_msg(msg.clone());
+            }
+        }
+    }
+
+    /// Sends the mouse move held back by `max_mouse_moves` once it is due and
+    /// returns when to check again. Run by the shared worker while idle.
+    fn flush_moves(&self) -> Option<Instant> {
+        let min_interval = self.min_move_interval()?;
+        let (due, next) = self.move_throttle.lock().unwrap().tick(min_interval);
+        if let Some(msg) = due {
+            for worker in self.workers.lock().unwrap().values() {
+                worker.post_msg(msg.clone());
+            }
+        }
+        next
+    }
+
+    /// The delivery table handed to the event loop thread.
+    #[cfg(target_os = "windows")]
+    pub(crate) fn routes(&self) -> Routes {
+        let senders = self
+            .workers
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|worker| worker.sender())
+            .collect();
+        Routes::new(
+            senders,
+            self.move_throttle.clone(),
+            self.min_move_interval(),
+            self.metrics.clone(),
+        )
+    }
+
+    /// Shortcuts that may be registered as system hotkeys.
+    #[cfg(target_os = "windows")]
+    pub(crate) fn hotkey_candidates(&self) -> Vec<(ID, ShortcutSequence)> {
+        if !self.config.register_hotkeys {
+            return Vec::new();
+        }
+        self.shortcut_map
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (_, trigger))| {
+                trigger.enabled
+                    && trigger.options.edge == TriggerEdge::Press
+                    && trigger.options.autofire.is_none()
+                    && trigger.options.context.is_none()
+                    && trigger.options.device.is_none()
+                    // Hotkeys cannot tell the left and right keys apart.
+                    && trigger.side_policy() != SidePolicy::PreferLeft
+            })
+            .map(|(id, (sequence, _))| (*id, sequence.clone()))
+            .collect()
+    }
+
+    #[cfg(target_os = "windows")]
+    pub(crate) fn set_hotkeys(&self, ids: HashSet<ID>) {
+        *self.hotkeys.lock().unwrap() = ids;
+    }
+
+    fn is_hotkey(&self, id: &ID) -> bool {
+        self.hotkeys.lock().unwrap().contains(id)
+    }
+
+    /// Runs the shortcut behind a `WM_HOTKEY` on the shared worker.
+    fn on_hotkey(&self, id: ID) {
+        let (sequence, trigger) = match self.shortcut_map.lock().unwrap().get(&id) {
+            Some((sequence, trigger)) if trigger.enabled => (sequence.clone(), trigger.clone()),
+            _ => return,
+        };
+        if self.config.suppress_in_fullscreen && super::is_foreground_fullscreen() {
+            return;
+        }
+        if let Some(fired) = trigger.fire(id, &sequence, None, &self.config, &self.guard()) {
+            if trigger.is_spent(&fired) {
+                self.del_event_by_id(id);
+            }
+            let (cb, arg) = (trigger.cb.clone(), fired.clone());
+            self.dispatch(id, move || {
+                cb(arg);
+                Propagation::Continue
+            });
+            self.notify_shortcut(fired);
+        }
+    }
+
+    /// Runs the callback of registration `id` on the callback pool, or right
+    /// here without one. A panic is caught and reported to the error handler
+    /// so the worker keeps running; a slow run to the slow-callback handler.
+    /// Callbacks on the pool cannot stop propagation.
+    fn dispatch<F>(&self, id: ID, job: F) -> Propagation
+    where
+        F: FnOnce() -> Propagation + Send + 'static,
+    {
+        let guard = self.guard();
+        let guarded = move || guard.run(id, job);
+        match self.callback_pool.get() {
+            Some(pool) => {
+                pool.execute(Box::new(move || {
+                    guarded();
+                }));
+                Propagation::Continue
+            }
+            None => guarded(),
+        }
+    }
+
+    fn guard(&self) -> CallbackGuard {
+        CallbackGuard {
+            error_handler: self.error_handler.clone(),
+            slow_callback: self.slow_callback.clone(),
+        }
+    }
+
+    /// Called, on the thread that ran it, after each callback that took longer
+    /// than `threshold`, to find the handler behind laggy shortcuts.
+    pub fn set_slow_callback_handler<F>(&self, threshold: Duration, handler: F)
+    where
+        F: Fn(SlowCallback) + Send + Sync + 'static,
+    {
+        *self.slow_callback.lock().unwrap() = Some((threshold, Arc::new(Box::new(handler))));
+    }
+
+    /// Called with each panic caught from a callback. Without a handler a
+    /// panic is only reported by the panic hook, which prints it by default.
+    pub fn set_error_handler<F>(&self, handler: F)
+    where
+        F: Fn(CallbackPanic) + Send + Sync + 'static,
+    {
+        *self.error_handler.lock().unwrap() = Some(Arc::new(Box::new(handler)));
+    }
+
+    /// Passes a fired shortcut on to event listeners as `Event::ShortcutEvent`.
+    /// Runs on the shared worker; listeners on other workers get it posted.
+    fn notify_shortcut(&self, fired: ShortcutEvent) {
+        let event = Event::ShortcutEvent(fired);
+        for (id, cb) in self.filter_events(WorkerAffinity::Shared, &event) {
+            let event = event.clone();
+            if self.dispatch(id, move || cb(event)) == Propagation::Stop {
+                break;
+            }
+        }
+        for (affinity, worker) in self.workers.lock().unwrap().iter() {
+            if *affinity != WorkerAffinity::Shared {
+                worker.post_msg(WorkerMsg::Notify(event.clone()));
+            }
+        }
+    }
+
+    /// Event counts, worker queue depths and hook-to-callback latency over the
+    /// most recent events.
+    pub fn metrics(&self) -> Metrics {
+        let mut metrics = self.metrics.snapshot();
+        metrics.queue_depth = self
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(affinity, worker)| (*affinity, worker.queue_depth()))
+            .collect();
+        metrics
+    }
+
+    /// Registrations, backend and worker state, for bug reports about
+    /// shortcuts that stopped firing.
+    pub fn dump_state(&self) -> ListenerStateReport {
+        let mut shortcuts: Vec<ShortcutReport> = self
+            .shortcut_map
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, (sequence, trigger))| ShortcutReport {
+                id: *id,
+                shortcut: sequence.to_string(),
+                enabled: trigger.enabled,
+                hotkey: self.is_hotkey(id),
+            })
+            .collect();
+        shortcuts.sort_by_key(|shortcut| shortcut.id);
+
+        let mut event_listeners: Vec<EventListenerReport> = self
+            .event_map
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, handler)| EventListenerReport {
+                id: *id,
+                filter: handler.filter.clone(),
+                affinity: handler.affinity,
+                priority: handler.priority,
+            })
+            .collect();
+        event_listeners.sort_by_key(|listener| listener.id);
+
+        let mut workers: Vec<WorkerReport> = self
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(affinity, worker)| WorkerReport {
+                affinity: *affinity,
+                thread_name: worker.name().to_string(),
+                thread_id: worker.thread_id().map(|id| format!("{:?}", id)),
+                queue_depth: worker.queue_depth(),
+            })
+            .collect();
+        workers.sort_by_key(|worker| worker.thread_name.clone());
+
+        let metrics = self.metrics.snapshot();
+        ListenerStateReport {
+            backend: self.config.backend,
+            hook: self
+                .get_event_loop()
+                .map(|event_loop| event_loop.status())
+                .unwrap_or_default(),
+            shortcuts,
+            event_listeners,
+            workers,
+            keyboard_events: metrics.keyboard_events,
+            mouse_events: metrics.mouse_events,
+            dropped_events: metrics.dropped_events,
+        }
+    }
+
+    /// Spawns a dedicated worker thread for `affinity` on the next `startup()`.
+    /// Registrations made with that affinity then run there instead of on the
+    /// shared worker.
+    pub fn add_worker(&self, affinity: WorkerAffinity) {
+        self.workers
+            .lock()
+            .unwrap()
+            .entry(affinity)
+            .or_insert_with(|| {
+                Arc::new(Worker::new(
+                    self.config.worker_thread(affinity.thread_role()),
+                    self.config.coalesce_mouse_moves,
+                    self.config.velocity_window,
+                    self.config.worker_queue_capacity,
+                    self.metrics.clone(),
+                ))
+            });
+    }
+
+    fn resolve_affinity(&self, affinity: WorkerAffinity) -> WorkerAffinity {
+        if self.workers.lock().unwrap().contains_key(&affinity) {
+            affinity
+        } else {
+            WorkerAffinity::Shared
+        }
+    }
+
+    fn get_event_loop(&self) -> Option<Arc<dyn InputBackend>> {
+        self.listener_event_loop.lock().unwrap().clone()
+    }
+
+    /// Fails if an option needs a backend other than the configured one.
+    fn check_backend(&self) -> Result<(), KmHookError> {
+        #[cfg(feature = "suppression")]
+        if !self.config.suppressed_chords.is_empty() && self.config.backend != Backend::LowLevelHook
+        {
+            return Err(KmHookError::BackendUnavailable(Backend::LowLevelHook));
+        }
+        Ok(())
+    }
+
+    /// Starts the `callback_threads` pool if there is none yet.
+    fn open_callback_pool(&self) -> Result<(), KmHookError> {
+        if let (Some(threads), None) = (self.config.callback_threads, self.callback_pool.get()) {
+            let pool = CallbackPool::new(threads, self.config.worker_thread("callback"))?;
+            let _ = self.callback_pool.set(pool);
+        }
+        Ok(())
+    }
+
+    /// Creates the worker queues so the event loop can deliver to them
+    /// before the workers run.
+    fn open_workers(&self) -> Vec<(WorkerAffinity, Arc<Worker>)> {
+        let workers: Vec<(WorkerAffinity, Arc<Worker>)> = self
+            .workers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(affinity, worker)| (*affinity, worker.clone()))
+            .collect();
+        for (_, worker) in workers.iter() {
+            worker.open();
+        }
+        workers
+    }
+
+    /// Runs each worker on its own thread, except the shared one when
+    /// `work_thread` is `Some(false)`, which then runs on the calling thread.
+    fn run_workers(
+        self: &Arc<Self>,
+        workers: Vec<(WorkerAffinity, Arc<Worker>)>,
+        work_thread: Option<bool>,
+    ) -> Result<Option<JoinHandleType>, KmHookError> {
+        let mut shared = None;
+        for (affinity, worker) in workers {
+            if affinity == WorkerAffinity::Shared {
+                shared = Some(worker);
+                continue;
+            }
+            let _self = self.clone();
+            worker.run(
+                move |event| _self.on_event(affinity, event),
+                |_| {},
+                || None,
+                Some(true),
+            )?;
+        }
+
+        let Some(w) = shared else {
+            return Ok(None);
+        };
+        let _self = self.clone();
+        let hotkey_self = self.clone();
+        let idle_self = self.clone();
+        w.run(
+            move |event| {
+                _self.on_event(WorkerAffinity::Shared, event);
+            },
+            move |id| hotkey_self.on_hotkey(id),
+            move || idle_self.flush_moves(),
+            work_thread,
+        )
+    }
+
+    /// Runs the event loop on the calling thread instead of spawning one,
+    /// for hosts that already dedicate a thread to it, or DLLs that must not
+    /// spawn it while they are being loaded. Workers still run on their own
+    /// threads. Returns once `shutdown()` is called from a
+    /// callback or another thread; the calling thread's priority is left
+    /// as it is. Use instead of `startup()`.
+    pub fn run_blocking(self: &Arc<Self>) -> Result<(), KmHookError> {
+        self.check_backend()?;
+        let Some(event_loop) = self.get_event_loop() else {
+            return Err(KmHookError::BackendUnavailable(self.config.backend));
+        };
+        self.open_callback_pool()?;
+        let workers = self.open_workers();
+        // The loop takes this thread, so the workers go first.
+        self.run_workers(workers, Some(true))?;
+        let result = event_loop.run_blocking();
+        if result.is_err() {
+            self.post_worker_msg(WorkerMsg::Stop);
+        }
+        result
+    }
+
+    /// The callbacks `event` is for, highest priority first, then in order
+    /// of registration.
+    fn filter_events(&self, worker: WorkerAffinity, event: &Event) -> Vec<(ID, FnEvent)> {
+        type Candidate = (
+            i32,
+            Option<FnPredicate>,
+            Option<Arc<AtomicU64>>,
+            (ID, FnEvent),
+        );
+        let mut candidates: Vec<Candidate> = {
+            let binding = self.event_map.lock().unwrap();
+            binding
+                .iter()
+                .filter_map(|(id, handler)| {
+                    if handler.filter.matches(event)
+                        && self.resolve_affinity(handler.affinity) == worker
+                    {
+                        Some((
+                            handler.priority,
+                            handler.predicate.clone(),
+                            handler.remaining.clone(),
+                            (*id, handler.cb.clone()),
+                        ))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        };
+        candidates.sort_by_key(|(priority, _, _, (id, _))| (std::cmp::Reverse(*priority), *id));
+        // Predicates are user code and may call back into the listener, so
+        // they run without the map locked.
+        let mut spent = Vec::new();
+        let handlers = candidates
+            .into_iter()
+            .filter(|(_, predicate, _, _)| predicate.as_ref().map_or(true, |p| p(event)))
+            .filter(|(_, _, remaining, (id, _))| {
+                let Some(remaining) = remaining else {
+                    return true;
+                };
+                match remaining
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                {
+                    Ok(1) => {
+                        spent.push(*id);
+                        true
+                    }
+                    Ok(_) => true,
+                    // Spent, but not yet removed.
+                    Err(_) => false,
+                }
+            })
+            .map(|(_, _, _, handler)| handler)
+            .collect();
+        for id in spent {
+            self.del_event_by_id(id);
+        }
+        handlers
+    }
+
+    /// Tracks held keys and mouse buttons and returns the chord completed by
+    /// `event`, if it pressed something. Only called on the shared worker.
+    fn pressed_chord(&self, event: &Event) -> Option<PressedChord> {
+        let mut state = self.chord_state.lock().unwrap();
+        match event {
+            Event::KeyboardEvent(key_info) => {
+                if let Some(snapshot) = &key_info.keyboard_state {
+                    state.keys = snapshot.to_shortcut();
+                }
+                PressedChord::from_key(key_info)
+            }
+            Event::MouseEvent(mouse_info) => {
+                let wheel = match &mouse_info.button {
+                    Some(button) => {
+                        let key = MouseKey::from(button);
+                        if button.state() != ClickState::Pressed {
+                            state.buttons.retain(|&held| held != key);
+                            return None;
+                        }
+                        if !state.buttons.contains(&key) {
+                            state.buttons.push(key);
+                        }
+                        None
+                    }
+                    // Plain moves press nothing.
+                    None => Some(MouseKey::from_wheel(&mouse_info.wheel)?),
+                };
+                let mut chord = state.keys.clone();
+                for key in state.buttons.iter().chain(wheel.iter()) {
+                    chord.set_mouse_key(*key);
+                }
+                Some(PressedChord {
+                    chord,
+                    modifier: false,
+                })
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    fn is_release(event: &Event) -> bool {
+        match event {
+            Event::KeyboardEvent(key_info) => key_info.state == KeyState::Released,
+            Event::MouseEvent(mouse_info) => mouse_info
+                .button
+                .as_ref()
+                .map_or(false, |button| button.state() == ClickState::Released),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    fn filter_shortcut(&self, event: &Event) -> Option<Vec<(FnShourtcut, ShortcutEvent)>> {
+        let pressed = self.pressed_chord(event);
+        if pressed.is_none() && !Self::is_release(event) {
+            return None;
+        }
+        let hotkeys = self.hotkeys.lock().unwrap().clone();
+        let binding = self.shortcut_map.lock().unwrap();
+        let foreground = std::cell::OnceCell::new();
+        let guard = self.guard();
+        let mut completed: Vec<(&ID, &(ShortcutSequence, FnShourtcutTrigger))> = binding
+            .iter()
+            .filter(|(id, _)| !hotkeys.contains(id))
+            .filter(|(_, (sequence, trigger))| {
+                trigger.enabled
+                    && trigger
+                        .options
+                        .device
+                        .map_or(true, |device| event.device() == Some(device))
+                    && match &pressed {
+                        Some(pressed) => trigger.advance(sequence, pressed),
+                        None => trigger.release(),
+                    }
+                    && trigger.options.context.as_ref().map_or(true, |context| {
+                        foreground
+                            .get_or_init(super::foreground_window)
+                            .as_ref()
+                            .map_or(false, |window| context.matches(window))
+                    })
+            })
+            .collect();
+        if !completed.is_empty()
+            && self.config.suppress_in_fullscreen
+            && super::is_foreground_fullscreen()
+        {
+            return Some(Vec::new());
+        }
+        // A shortcut bound for the foreground app shadows its global twin.
+        let key_info = match event {
+            Event::KeyboardEvent(key_info) => Some(key_info),
+            _ => None,
+        };
+        completed
+            .sort_by_key(|(id, (_, trigger))| (std::cmp::Reverse(trigger.options.priority), **id));
+        let result = completed
+            .iter()
+            .filter(|(_, (sequence, trigger))| {
+                trigger.options.context.is_some()
+                    || !completed
+                        .iter()
+                        .any(|(_, (other, t))| t.options.context.is_some() && other == sequence)
+            })
+            .filter_map(|(id, (sequence, trigger))| {
+                let fired = trigger.fire(**id, sequence, key_info, &self.config, &guard)?;
+                Some((trigger.cb.clone(), trigger.is_spent(&fired), fired))
+            })
+            .collect::<Vec<_>>();
+        drop(binding);
+        let result = result
+            .into_iter()
+            .map(|(cb, spent, fired)| {
+                if spent {
+                    self.del_event_by_id(fired.id);
+                }
+                (cb, fired)
+            })
+            .collect();
+        Some(result)
+    }
+
+    /// Swallows all keyboard and mouse input until the returned guard is
+    /// dropped or the escape chord (`ListenerBuilder::block_input_escape`) is
+    /// pressed. Listeners still receive the blocked input. Keys and buttons
+    /// held when the block starts are let up normally. ll-hook backend only.
+    #[cfg(feature = "suppression")]
+    pub fn block_input(self: &Arc<Self>) -> Result<InputBlockGuard, String> {
+        let id = self.start_block(None)?;
+        Ok(InputBlockGuard {
+            id,
+            listener: Arc::downgrade(self),
+        })
+    }
+
+    /// Like `block_input`, ending by itself after `duration`.
+    #[cfg(feature = "suppression")]
+    pub fn block_input_for(&self, duration: Duration) -> Result<(), String> {
+        self.start_block(Some(Instant::now() + duration))
+            .map(|_| ())
+    }
+
+    /// Ends any `block_input` early.
+    #[cfg(feature = "suppression")]
+    pub fn unblock_input(&self) {
+        *self.input_block.lock().unwrap() = None;
+    }
+
+    #[cfg(feature = "suppression")]
+    fn start_block(&self, until: Option<Instant>) -> Result<ID, String> {
+        if self.config.backend != Backend::LowLevelHook {
+            return Err("Blocking input needs the ll-hook backend".to_string());
+        }
+        let id = self.gen_id();
+        *self.input_block.lock().unwrap() = Some(InputBlock { id, until });
+        Ok(id)
+    }
+
+    /// Whether input is blocked, ending an expired block. A press of the
+    /// escape chord ends the block and is itself swallowed. Called
+    /// synchronously from the hook.
+    #[cfg(feature = "suppression")]
+    pub(crate) fn blocks_input(&self, key_info: Option<&KeyInfo>) -> bool {
+        let mut block = self.input_block.lock().unwrap();
+        let Some(active) = block.as_ref() else {
+            return false;
+        };
+        if active.until.map_or(false, |until| Instant::now() >= until) {
+            *block = None;
+            return false;
+        }
+        let escaped = key_info
+            .and_then(PressedChord::from_key)
+            .map_or(false, |pressed| {
+                pressed.triggers(&self.config.block_escape, self.config.side_policy)
+            });
+        if escaped {
+            *block = None;
+        }
+        true
+    }
+
+    /// Whether `key_info` completes a shortcut registered with
+    /// `ShortcutOptions::swallow` or a `ListenerBuilder::suppress_chord`.
+    /// Called synchronously from the hook.
+    #[cfg(feature = "suppression")]
+    pub(crate) fn swallows(&self, key_info: &KeyInfo) -> bool {
+        if self.config.ignore_injected && key_info.injected {
+            return false;
+        }
+        let Some(pressed) = PressedChord::from_key(key_info) else {
+            return false;
+        };
+        if self
+            .config
+            .suppressed_chords
+            .iter()
+            .any(|chord| pressed.triggers(chord, self.config.side_policy))
+        {
+            return true;
+        }
+        let swallow = self
+            .shortcut_map
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| !self.is_hotkey(id))
+            .any(|(_, (sequence, trigger))| {
+                // Only the chord completing a sequence is swallowed.
+                let last = sequence.steps().len() - 1;
+                trigger.enabled
+                    && trigger.options.swallow
+                    && pressed.triggers(&sequence.steps()[last], trigger.side_policy())
+                    && (last == 0 || trigger.next_step() == last)
+            });
+        swallow && !(self.config.suppress_in_fullscreen && super::is_foreground_fullscreen())
+    }
+
+    fn on_event(&self, worker: WorkerAffinity, mut event: Event) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(?event, "on_event");
+
+        if self.config.ignore_injected && event.is_injected() {
+            return;
+        }
+        let is_repeat = matches!(&event, Event::KeyboardEvent(key_info) if key_info.is_repeat);
+        if is_repeat && !self.config.key_repeats {
+            return;
+        }
+        if self.config.window_info {
+            let window = super::foreground_window();
+            match &mut event {
+                Event::KeyboardEvent(key_info) => key_info.window = window,
+                Event::MouseEvent(mouse_info) => mouse_info.window = window,
+                #[allow(unreachable_patterns)]
+                _ => {}
+            }
+        }
+        if let Event::MouseEvent(mouse_info) = &mut event {
+            mouse_info.monitor = super::monitor_at(&mouse_info.pos);
+            if self.config.cursor_window_info && !mouse_info.is_move() {
+                mouse_info.target = super::window_at(&mouse_info.pos);
+            }
+            if let (CoordinateSpace::Logical, Some(monitor)) =
+                (self.config.coordinate_space, &mouse_info.monitor)
+            {
+                let logical = |v: i32| monitor.logical_length(v);
+                mouse_info.pos = monitor.logical_point(&mouse_info.pos);
+                mouse_info.relative_pos = Pos {
+                    x: logical(mouse_info.relative_pos.x),
+                    y: logical(mouse_info.relative_pos.y),
+                };
+                mouse_info.velocity = Pos {
+                    x: logical(mouse_info.velocity.x),
+                    y: logical(mouse_info.velocity.y),
+                };
+                mouse_info.acceleration = Pos {
+                    x: logical(mouse_info.acceleration.x),
+                    y: logical(mouse_info.acceleration.y),
+                };
+            }
+        }
+
+        if worker == WorkerAffinity::Shared {
+            self.held.lock().unwrap().update(&event);
+            if let Some(capacity) = self.config.poll_buffer {
+                let mut polled = self.polled.lock().unwrap();
+                polled.push_back(event.clone());
+                while polled.len() > capacity {
+                    polled.pop_front();
+                }
+            }
+        }
+
+        let delivered = match &event {
+            Event::KeyboardEvent(key_info) if !self.config.keyboard_state => {
+                Event::KeyboardEvent(KeyInfo {
+                    keyboard_state: None,
+                    ..key_info.clone()
+                })
+            }
+            _ => event.clone(),
+        };
+        for (id, cb) in self.filter_events(worker, &event) {
+            let event = delivered.clone();
+            if self.dispatch(id, move || cb(event)) == Propagation::Stop {
+                break;
+            }
+        }
+
+        if worker != WorkerAffinity::Shared || is_repeat {
+            return;
+        }
+
+        if let Some(cbs) = self.filter_shortcut(&event) {
+            for (cb, fired) in cbs {
+                let arg = fired.clone();
+                self.dispatch(fired.id, move || {
+                    cb(arg);
+                    Propagation::Continue
+                });
+                self.notify_shortcut(fired);
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!("on_event finished");
+    }
+
+    fn gen_id(&self) -> ID {
+        gen_id()
+    }
+
+    fn post_recheck_hook(&self) {
+        self.post_msg_to_loop(WM_USER_RECHECK_HOOK);
+    }
+
+    fn post_msg_to_loop(&self, msg_type: u32) {
+        if let Some(event_loop) = self.listener_event_loop.lock().unwrap().as_ref() {
+            event_loop.post_msg_to_loop(msg_type);
+        }
+    }
+
+    pub(crate) fn cursor_clip(&self) -> Option<Rect> {
+        *self.cursor_clip.lock().unwrap()
+    }
+
+    /// Confines the cursor to `rect` until `release_cursor` or shutdown. The
+    /// clip is applied from the event loop thread once `startup` has run.
+    pub fn confine_cursor(&self, rect: Rect) {
+        self.cursor_clip.lock().unwrap().replace(rect);
+        self.post_msg_to_loop(WM_USER_APPLY_CURSOR_CLIP);
+    }
+
+    pub fn release_cursor(&self) {
+        self.cursor_clip.lock().unwrap().take();
+        self.post_msg_to_loop(WM_USER_APPLY_CURSOR_CLIP);
+    }
+
+    /// Takes up to `max` of the events buffered since the last call, oldest
+    /// first. Always empty unless `ListenerBuilder::poll_buffer` is set.
+    pub fn poll_events(&self, max: usize) -> Vec<Event> {
+        let mut polled = self.polled.lock().unwrap();
+        let count = max.min(polled.len());
+        polled.drain(..count).collect()
+    }
+
+    /// Whether `key` is held, as last seen by the listener. Generic
+    /// modifiers such as `Control` match either side.
+    ///
+    /// The state queries only see input while a hook is installed; without
+    /// `ListenerBuilder::track_input_state` that needs something registered.
+    pub fn is_key_pressed(&self, key: KeyId) -> bool {
+        let held = self.held.lock().unwrap();
+        let modifier = Modifiers::from_key(key.into());
+        if modifier.is_empty() {
+            held.keys.contains(&key)
+        } else {
+            held.modifiers.intersects(modifier)
+        }
+    }
+
+    /// Keys held, in the order they went down.
+    pub fn pressed_keys(&self) -> Vec<KeyId> {
+        self.held.lock().unwrap().keys.clone()
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.held.lock().unwrap().modifiers
+    }
+
+    /// Cursor position of the last mouse event, in the configured
+    /// `CoordinateSpace`. The origin until the mouse first moves.
+    pub fn mouse_position(&self) -> Pos {
+        self.held.lock().unwrap().cursor.clone()
+    }
+
+    /// Mouse buttons held, in the order they went down.
+    pub fn pressed_mouse_buttons(&self) -> Vec<MouseKey> {
+        self.held.lock().unwrap().buttons.clone()
+    }
+
+    pub fn has_keyboard_event(&self) -> bool {
+        if self.config.poll_buffer.is_some() || self.config.track_input_state {
+            return true;
+        }
+        {
+            let binding = self.shortcut_map.lock().unwrap();
+            if binding
+                .iter()
+                .any(|(id, (_, trigger))| trigger.enabled && !self.is_hotkey(id))
+            {
+                return true;
+            }
+        }
+
+        let binding = self.event_map.lock().unwrap();
+        for (_, handler) in binding.iter() {
+            if handler.filter.wants_keyboard() {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn has_mouse_event(&self) -> bool {
+        if self.config.poll_buffer.is_some() || self.config.track_input_state {
+            return true;
+        }
+        {
+            let binding = self.shortcut_map.lock().unwrap();
+            let mouse_shortcut = binding.values().any(|(sequence, trigger)| {
+                trigger.enabled && sequence.steps().iter().any(Shortcut::has_mouse_key)
+            });
+            if mouse_shortcut {
+                return true;
+            }
+        }
+
+        let binding = self.event_map.lock().unwrap();
+        for (_, handler) in binding.iter() {
+            if handler.filter.wants_mouse() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Like `add_event_listener`, but runs `cb` on the worker selected by
+    /// `affinity`.
+    pub fn add_event_listener_with_affinity<F>(
+        &self,
+        cb: F,
+        filter: Option<EventFilter>,
+        affinity: WorkerAffinity,
+    ) -> Result<ID, String>
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        self.insert_event_handler(EventHandler {
+            filter: filter.unwrap_or_default(),
+            predicate: None,
+            affinity,
+            priority: 0,
+            remaining: None,
+            cb: Arc::new(Box::new(move |event| {
+                cb(event);
+                Propagation::Continue
+            })),
+        })
+    }
+
+    /// Like `add_event_listener`, for listeners that need to run in a fixed
+    /// order. Listeners get an event highest `priority` first, then in the
+    /// order they were registered; those registered without one have
+    /// priority 0. Returning `Propagation::Stop` keeps the event from the
+    /// listeners after it on the same worker.
+    pub fn add_event_listener_with_priority<F>(
+        &self,
+        cb: F,
+        filter: Option<EventFilter>,
+        priority: i32,
+    ) -> Result<ID, String>
+    where
+        F: Fn(Event) -> Propagation + Send + Sync + 'static,
+    {
+        self.insert_event_handler(EventHandler {
+            filter: filter.unwrap_or_default(),
+            predicate: None,
+            affinity: WorkerAffinity::Shared,
+            priority,
+            remaining: None,
+            cb: Arc::new(Box::new(cb)),
+        })
+    }
+
+    /// Like `add_event_listener`, but runs `cb` on a thread of its own, fed
+    /// through a queue of up to `capacity` events. A slow callback then holds
+    /// up neither the worker nor other listeners; when its queue is full, new
+    /// events are dropped for it alone and counted in `Metrics::dropped_events`.
+    pub fn add_event_listener_queued<F>(
+        &self,
+        cb: F,
+        filter: Option<EventFilter>,
+        capacity: usize,
+    ) -> Result<ID, String>
+    where
+        F: Fn(Event) + Send + 'static,
+    {
+        let id = self.gen_id();
+        let handler = EventHandler {
+            filter: filter.unwrap_or_default(),
+            predicate: None,
+            affinity: WorkerAffinity::Shared,
+            priority: 0,
+            remaining: None,
+            cb: self.queue_callback(id, cb, capacity)?,
+        };
+        self.store_event_handler(id, handler)
+    }
+
+    /// Runs `cb` on a thread of its own and returns the handler feeding it
+    /// through a queue of up to `capacity` events. The thread ends once the
+    /// handler, and with it the sender, is dropped.
+    fn queue_callback<F>(&self, id: ID, cb: F, capacity: usize) -> Result<FnEvent, String>
+    where
+        F: Fn(Event) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel::<Event>(capacity);
+        let guard = self.guard();
+        self.config
+            .worker_thread("subscriber")
+            .spawn(move || {
+                for event in rx {
+                    guard.run(id, || {
+                        cb(event);
+                        Propagation::Continue
+                    });
+                }
+            })
+            .map_err(|e| e.to_string())?;
+        let tx = Mutex::new(tx);
+        let metrics = self.metrics.clone();
+        Ok(Arc::new(Box::new(move |event| {
+            if tx.lock().unwrap().try_send(event).is_err() {
+                metrics.dropped();
+            }
+            Propagation::Continue
+        })))
+    }
+
+    /// Like `add_event_listener`, but only passes events for which
+    /// `predicate` returns true. The predicate runs once per event on the
+    /// worker, before anything is cloned for `cb`.
+    pub fn add_event_listener_filtered<P, F>(&self, predicate: P, cb: F) -> Result<ID, String>
+    where
+        P: Fn(&Event) -> bool + Send + Sync + 'static,
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        self.insert_event_handler(EventHandler {
+            filter: EventFilter::All,
+            predicate: Some(Arc::new(Box::new(predicate))),
+            affinity: WorkerAffinity::Shared,
+            priority: 0,
+            remaining: None,
+            cb: Arc::new(Box::new(move |event| {
+                cb(event);
+                Propagation::Continue
+            })),
+        })
+    }
+
+    /// Fires `cb` when the cursor rests `dwell_ms` in `corner` of the monitor
+    /// it is on, then not again until it has left the corner. Remove with
+    /// `del_event_by_id`. Needs monitor information, so Windows only.
+    pub fn add_hot_corner<F>(&self, corner: Corner, dwell_ms: u32, cb: F) -> Result<ID, String>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let id = self.gen_id();
+        let guard = self.guard();
+        let hot_corner = HotCorner::new(
+            corner,
+            Duration::from_millis(dwell_ms as u64),
+            self.config.worker_thread("hot-corner"),
+            move || {
+                guard.run(id, || {
+                    cb();
+                    Propagation::Continue
+                });
+            },
+        );
+        let logical = self.config.coordinate_space == CoordinateSpace::Logical;
+        let cb = move |event: Event| {
+            let Event::MouseEvent(mouse_info) = event else {
+                return Propagation::Continue;
+            };
+            let Some(monitor) = mouse_info.monitor else {
+                return Propagation::Continue;
+            };
+            // Positions were scaled in `on_event`; scale the bounds alike.
+            let bounds = if logical {
+                monitor.logical_bounds()
+            } else {
+                monitor.bounds
+            };
+            hot_corner.on_mouse(&mouse_info.pos, &bounds, mouse_info.is_move());
+            Propagation::Continue
+        };
+        self.insert_event_handler_as(
+            id,
+            EventHandler {
+                filter: EventFilter::Mouse,
+                predicate: None,
+                affinity: WorkerAffinity::Shared,
+                priority: 0,
+                remaining: None,
+                cb: Arc::new(Box::new(cb)),
+            },
+        )
+    }
+
+    /// Fires `cb` when the cursor is shaken back and forth as described by
+    /// `options`. Remove with `del_event_by_id`.
+    pub fn add_shake_trigger<F>(&self, options: ShakeOptions, cb: F) -> Result<ID, String>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let detector = Mutex::new(ShakeDetector::new(&options));
+        self.add_event_listener(
+            move |event| {
+                if let Event::MouseEvent(mouse_info) = event {
+                    let shaken = detector
+                        .lock()
+                        .unwrap()
+                        .feed(&mouse_info.pos, mouse_info.timestamp);
+                    if shaken {
+                        cb();
+                    }
+                }
+            },
+            Some(EventFilter::MouseMove),
+        )
+    }
+
+    fn insert_event_handler(&self, handler: EventHandler) -> Result<ID, String> {
+        self.insert_event_handler_as(self.gen_id(), handler)
+    }
+
+    /// For callbacks that need their ID before they are registered.
+    fn insert_event_handler_as(&self, id: ID, mut handler: EventHandler) -> Result<ID, String> {
+        if let Some(capacity) = self.config.subscriber_queues {
+            let cb = handler.cb.clone();
+            handler.cb = self.queue_callback(
+                id,
+                move |event| {
+                    cb(event);
+                },
+                capacity,
+            )?;
+        }
+        self.store_event_handler(id, handler)
+    }
+
+    fn store_event_handler(&self, id: ID, handler: EventHandler) -> Result<ID, String> {
+        self.event_map.lock().unwrap().insert(id, handler);
+        self.post_recheck_hook();
+        Ok(id)
+    }
+
+    /// Replaces the filter of an existing `add_event_listener` registration,
+    /// keeping its ID and callback.
+    pub fn update_event_filter(&self, id: ID, filter: EventFilter) -> Result<(), String> {
+        {
+            let mut binding = self.event_map.lock().unwrap();
+            let handler = binding
+                .get_mut(&id)
+                .ok_or_else(|| format!("Event listener not found: {}", id))?;
+            handler.filter = filter;
+        }
+        self.post_recheck_hook();
+        Ok(())
+    }
+
+    /// Like `add_global_shortcut`, with per-shortcut `options`.
+    pub fn add_global_shortcut_with_options<F>(
+        &self,
+        shortcut: &str,
+        cb: F,
+        options: ShortcutOptions,
+    ) -> Result<ID, RegisterError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.add_global_shortcut_with_event(shortcut, move |_| cb(), options)
+    }
+
+    /// Like `add_global_shortcut_with_options`, but `cb` is told which
+    /// registration fired and how, so one closure can serve several.
+    pub fn add_global_shortcut_with_event<F>(
+        &self,
+        shortcut: &str,
+        cb: F,
+        options: ShortcutOptions,
+    ) -> Result<ID, RegisterError>
+    where
+        F: Fn(ShortcutEvent) + Send + Sync + 'static,
+    {
+        let id = self
+            .register_shortcut_callback(shortcut, FnShourtcutTrigger::with_options(cb, options))?;
+        self.post_recheck_hook();
+        Ok(id)
+    }
+
+    /// A shortcut whose callback returns a future, spawned on `runtime` each
+    /// time the shortcut fires, so slow work such as network requests does
+    /// not hold up the worker.
+    #[cfg(feature = "tokio")]
+    pub fn add_global_shortcut_async<F, Fut>(
+        &self,
+        shortcut: &str,
+        runtime: tokio::runtime::Handle,
+        cb: F,
+    ) -> Result<ID, RegisterError>
+    where
+        F: Fn(ShortcutEvent) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.add_global_shortcut_with_event(
+            shortcut,
+            move |fired| {
+                runtime.spawn(cb(fired));
+            },
+            ShortcutOptions::default(),
+        )
+    }
+
+    /// Like `add_global_shortcut_trigger`, with per-shortcut `options`. With
+    /// `TriggerEdge::Tap`, `trigger` counts taps, e.g. a double tap of "Ctrl".
+    pub fn add_global_shortcut_trigger_with_options<F>(
+        &self,
+        shortcut: &str,
+        cb: F,
+        trigger: u32,
+        internal: Option<u32>,
+        options: ShortcutOptions,
+    ) -> Result<ID, RegisterError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let trigger_info = Arc::new(Mutex::new(ShortcutTriggerInfo::new()));
+        // Repeated clicks ("MouseX1" twice) follow the system double-click time.
+        let mouse_only = ShortcutSequence::from_str(shortcut)
+            .map_err(RegisterError::Parse)?
+            .steps()
+            .iter()
+            .all(|step| step.has_mouse_key() && !step.has_modifier() && !step.has_normal_key());
+        let default_internal = if mouse_only {
+            super::double_click_interval()
+        } else {
+            consts::DEFAULT_SHORTCUT_TRIGGER_INTERVAL
+        };
+        let next_internal = internal.unwrap_or(default_internal) as u128;
 
-#[derive(Debug)]
-struct ShortcutTriggerInfo {
-    trigger: u32,
-    last_trigger_time: Instant,
-}
+        self.add_global_shortcut_with_options(
+            shortcut,
+            move || {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("global_shortcut trigger");
 
-impl ShortcutTriggerInfo {
-    fn new() -> Self {
-        Self {
-            trigger: 0,
-            last_trigger_time: Instant::now(),
-        }
-    }
+                let need_trigger = {
+                    let mut mtrigger_info = trigger_info.lock().unwrap();
 
-    fn reset(&mut self) {
-        self.trigger = 0;
-        self.last_trigger_time = Instant::now();
+                    let elapsed = mtrigger_info.last_trigger_time.elapsed().as_millis();
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        times = mtrigger_info.trigger,
+                        elapsed = elapsed as u64,
+                        "global_shortcut trigger count"
+                    );
+
+                    if mtrigger_info.trigger == 0 || elapsed < next_internal {
+                        mtrigger_info.increase();
+                    } else {
+                        mtrigger_info.reset();
+                        mtrigger_info.increase();
+                    }
+                    if mtrigger_info.trigger >= trigger {
+                        mtrigger_info.reset();
+                        true
+                    } else {
+                        false
+                    }
+                };
+                if need_trigger {
+                    cb();
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!("global_shortcut fired");
+                }
+            },
+            options,
+        )
     }
 
-    fn increase(&mut self) {
-        self.trigger += 1;
-        self.last_trigger_time = Instant::now();
+    /// Turns a shortcut off and on again without unregistering it. A disabled
+    /// shortcut never fires and does not keep the keyboard hook installed.
+    pub fn set_shortcut_enabled(&self, id: ID, enabled: bool) -> Result<(), String> {
+        let ids = self
+            .shortcut_ex_map
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| vec![id]);
+        {
+            let mut binding = self.shortcut_map.lock().unwrap();
+            for id in ids {
+                let (_, trigger) = binding
+                    .get_mut(&id)
+                    .ok_or_else(|| format!("Shortcut not found: {}", id))?;
+                trigger.enabled = enabled;
+                let mut progress = trigger.progress.lock().unwrap();
+                progress.next = 0;
+                progress.interrupt();
+            }
+        }
+        self.post_recheck_hook();
+        Ok(())
     }
-}
 
-pub struct Listener {
-    listener_event_loop: Mutex<Option<Arc<EventLoop>>>,
-    worker: Mutex<Option<Arc<Worker>>>,
-    event_map: Mutex<HashMap<ID, (EventType, FnEvent)>>,
-    shortcut_map: Mutex<HashMap<ID, (Shortcut, FnShourtcutTrigger)>>,
-    shortcut_ex_map: Mutex<HashMap<ID, Vec<ID>>>,
-}
+    /// Registered shortcuts in registration order. Single chords are
+    /// sequences of one step.
+    pub fn shortcuts(&self) -> Vec<(ID, ShortcutSequence)> {
+        let mut shortcuts: Vec<(ID, ShortcutSequence)> = self
+            .shortcut_map
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, (sequence, _))| (*id, sequence.clone()))
+            .collect();
+        shortcuts.sort_by_key(|(id, _)| *id);
+        shortcuts
+    }
 
-impl Listener {
-    pub(crate) fn get_worker(&self) -> Option<Arc<Worker>> {
-        self.worker.lock().unwrap().clone()
+    /// Registered event listeners and their filters in registration order.
+    pub fn event_listeners(&self) -> Vec<(ID, EventFilter)> {
+        let mut listeners: Vec<(ID, EventFilter)> = self
+            .event_map
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, handler)| (*id, handler.filter.clone()))
+            .collect();
+        listeners.sort_by_key(|(id, _)| *id);
+        listeners
     }
 
-    fn get_event_loop(&self) -> Option<Arc<EventLoop>> {
-        self.listener_event_loop.lock().unwrap().clone()
+    /// Checks `shortcut` against the registered shortcuts without
+    /// registering it, reporting every one it overlaps.
+    pub fn find_conflicts(&self, shortcut: &ShortcutSequence) -> Result<(), ConflictError> {
+        self.check_conflicts(shortcut.clone(), &self.shortcut_map.lock().unwrap())
     }
 
-    fn filter_events(&self, event_type: &EventType) -> Vec<(EventType, FnEvent)> {
-        let binding = self.event_map.lock().unwrap();
-        binding
+    fn check_conflicts(
+        &self,
+        shortcut: ShortcutSequence,
+        shortcut_map: &HashMap<ID, (ShortcutSequence, FnShourtcutTrigger)>,
+    ) -> Result<(), ConflictError> {
+        let mut conflicts: Vec<ID> = shortcut_map
             .iter()
-            .filter_map(|(_, (et, cb))| {
-                if matches!(et, EventType::All)
-                    || std::mem::discriminant(et) == std::mem::discriminant(event_type)
-                {
-                    Some((et.clone(), cb.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect()
+            .filter(|(_, (sequence, _))| sequence.overlaps(&shortcut))
+            .map(|(id, _)| *id)
+            .collect();
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        conflicts.sort();
+        Err(ConflictError {
+            shortcut,
+            conflicts,
+        })
     }
 
-    fn filter_shortcut(&self, et: &EventType) -> Option<Vec<FnShourtcut>> {
-        match et {
-            EventType::KeyboardEvent(Some(key_info)) => {
-                if key_info.state != KeyState::Pressed {
-                    return None;
-                }
-                let mut result: Vec<FnShourtcut> = Vec::new();
-                if let Some(keyboard_state) = &key_info.keyboard_state {
-                    // println!("filter shortcut: {:?}", keyboard_state);
-                    let binding = self.shortcut_map.lock().unwrap();
-                    // let usb_input = keyboard_state.clone().usb_input_report().to_vec();
-                    for (_, (shortcut, trigger)) in binding.iter() {
-                        // println!("filter shortcut check: {:?}", shortcut);
-                        if shortcut.is_match(keyboard_state) {
-                            // Check if the modifier key is pressed, and when used with other keys,
-                            // the last key pressed must not be a modifier key.
-                            if shortcut.has_modifier()
-                                & shortcut.has_normal_key()
-                                & key_info.key_id.is_modifier()
-                            {
-                                continue;
-                            }
-                            result.push(trigger.cb.clone());
-                        }
-                    }
-                    return Some(result);
-                }
-                None
-            }
-            _ => None,
+    /// Creates an empty shortcut group, e.g. for the keymap of one app mode.
+    pub fn create_group(&self) -> GroupId {
+        let group = self.gen_id();
+        self.shortcut_groups
+            .lock()
+            .unwrap()
+            .insert(group, Vec::new());
+        group
+    }
+
+    /// `add_global_shortcut` into `group`. The shortcut can still be managed
+    /// on its own by the returned ID.
+    pub fn add_global_shortcut_to_group<F>(
+        &self,
+        group: GroupId,
+        shortcut: &str,
+        cb: F,
+    ) -> Result<ID, RegisterError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        if !self.shortcut_groups.lock().unwrap().contains_key(&group) {
+            return Err(RegisterError::GroupNotFound(group));
+        }
+        let id = self.add_global_shortcut(shortcut, cb)?;
+        if let Some(ids) = self.shortcut_groups.lock().unwrap().get_mut(&group) {
+            ids.push(id);
         }
+        Ok(id)
     }
 
-    fn on_event(&self, event_type: EventType) {
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} on_event {:?}",
-            std::thread::current().id(),
-            event_type
-        );
+    fn group_members(&self, group: GroupId) -> Result<Vec<ID>, String> {
+        self.shortcut_groups
+            .lock()
+            .unwrap()
+            .get(&group)
+            .cloned()
+            .ok_or_else(|| format!("Group not found: {}", group))
+    }
 
-        let events = self.filter_events(&event_type);
-        for (et, cb) in events.iter() {
-            if matches!(et, EventType::All)
-                || std::mem::discriminant(et) == std::mem::discriminant(&event_type)
-            {
-                cb(event_type.clone());
-            }
+    pub fn set_group_enabled(&self, group: GroupId, enabled: bool) -> Result<(), String> {
+        for id in self.group_members(group)? {
+            self.set_shortcut_enabled(id, enabled)?;
         }
+        Ok(())
+    }
 
-        if let Some(cbs) = self.filter_shortcut(&event_type) {
-            for cb in cbs {
-                cb();
-            }
+    /// Unregisters every shortcut in `group` and the group itself.
+    pub fn del_group(&self, group: GroupId) -> Result<(), String> {
+        let ids = self.group_members(group)?;
+        self.shortcut_groups.lock().unwrap().remove(&group);
+        for id in ids {
+            self.del_event_by_id(id);
         }
+        Ok(())
+    }
 
-        #[cfg(feature = "Debug")]
-        println!(
-            "{:?} event_type: {:?}\n ----------------on_event Finish ",
-            std::thread::current().id(),
-            event_type
-        );
+    /// Like `add_event_listener`, unregistering itself after `cb` has run
+    /// `times` times.
+    pub fn add_event_listener_times<F>(
+        &self,
+        cb: F,
+        filter: Option<EventFilter>,
+        times: u64,
+    ) -> Result<ID, String>
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        if times == 0 {
+            return Err("times must be at least 1".to_string());
+        }
+        self.insert_event_handler(EventHandler {
+            filter: filter.unwrap_or_default(),
+            predicate: None,
+            affinity: WorkerAffinity::Shared,
+            priority: 0,
+            remaining: Some(Arc::new(AtomicU64::new(times))),
+            cb: Arc::new(Box::new(move |event| {
+                cb(event);
+                Propagation::Continue
+            })),
+        })
     }
 
-    fn gen_id(&self) -> ID {
-        gen_id()
+    /// `add_event_listener_times` for a single event.
+    pub fn add_event_listener_once<F>(
+        &self,
+        cb: F,
+        filter: Option<EventFilter>,
+    ) -> Result<ID, String>
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        self.add_event_listener_times(cb, filter, 1)
     }
 
-    fn post_recheck_hook(&self) {
-        self.listener_event_loop
-            .lock()
-            .unwrap()
-            .as_ref()
-            .unwrap()
-            .post_msg_to_loop(WM_USER_RECHECK_HOOK);
+    /// A shortcut that unregisters itself after firing once.
+    pub fn add_global_shortcut_once<F>(&self, shortcut: &str, cb: F) -> Result<ID, RegisterError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.add_global_shortcut_with_options(
+            shortcut,
+            cb,
+            ShortcutOptions {
+                max_triggers: Some(1),
+                ..Default::default()
+            },
+        )
     }
 
-    pub fn has_keyboard_event(&self) -> bool {
-        {
-            if !self.shortcut_map.lock().unwrap().is_empty() {
-                return true;
-            }
+    /// `add_event_listener` returning a guard that unregisters on drop.
+    pub fn add_event_listener_scoped<F>(
+        self: &Arc<Self>,
+        cb: F,
+        filter: Option<EventFilter>,
+    ) -> Result<Subscription, String>
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        let id = self.add_event_listener(cb, filter)?;
+        Ok(self.subscription(id))
+    }
+
+    /// `add_global_shortcut` returning a guard that unregisters on drop.
+    pub fn add_global_shortcut_scoped<F>(
+        self: &Arc<Self>,
+        shortcut: &str,
+        cb: F,
+    ) -> Result<Subscription, RegisterError>
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let id = self.add_global_shortcut(shortcut, cb)?;
+        Ok(self.subscription(id))
+    }
+
+    /// Delivers events matching `filter` through a queue the caller drains,
+    /// instead of a callback. Unregisters when the receiver is dropped. Holds
+    /// up to 4096 events; see `subscribe_with_capacity`.
+    pub fn subscribe(
+        self: &Arc<Self>,
+        filter: Option<EventFilter>,
+    ) -> Result<EventReceiver, String> {
+        self.subscribe_with_capacity(filter, consts::SUBSCRIBE_QUEUE_CAPACITY)
+    }
+
+    /// `subscribe` with a queue of up to `capacity` events. While it is full,
+    /// because the caller stopped draining it, new events are dropped and
+    /// counted in `Metrics::dropped_events`.
+    pub fn subscribe_with_capacity(
+        self: &Arc<Self>,
+        filter: Option<EventFilter>,
+        capacity: usize,
+    ) -> Result<EventReceiver, String> {
+        let (tx, rx) = mpsc::sync_channel(capacity);
+        let tx = Mutex::new(tx);
+        let metrics = self.metrics.clone();
+        let subscription = self.add_event_listener_scoped(
+            move |event| {
+                if let Err(mpsc::TrySendError::Full(_)) = tx.lock().unwrap().try_send(event) {
+                    metrics.dropped();
+                }
+            },
+            filter,
+        )?;
+        Ok(EventReceiver { rx, subscription })
+    }
+
+    /// `subscribe` for async code: events matching `filter` as a `Stream`.
+    /// As with `subscribe`, up to 4096 events wait to be polled; newer ones
+    /// are dropped and counted in `Metrics::dropped_events`.
+    #[cfg(feature = "async")]
+    pub fn event_stream(
+        self: &Arc<Self>,
+        filter: Option<EventFilter>,
+    ) -> Result<crate::stream::EventStream, String> {
+        let metrics = self.metrics.clone();
+        crate::stream::EventStream::new(
+            |tx| {
+                self.add_event_listener_scoped(
+                    move |event| {
+                        if !tx.send(event) {
+                            metrics.dropped();
+                        }
+                    },
+                    filter,
+                )
+            },
+            consts::SUBSCRIBE_QUEUE_CAPACITY,
+        )
+    }
+
+    /// Each firing of `shortcut` as an item of a `Stream`, queued like the
+    /// events of `event_stream`.
+    #[cfg(feature = "async")]
+    pub fn shortcut_stream(
+        self: &Arc<Self>,
+        shortcut: &str,
+    ) -> Result<crate::stream::ShortcutStream, String> {
+        let metrics = self.metrics.clone();
+        crate::stream::ShortcutStream::new(
+            |tx| {
+                let id = self.add_global_shortcut_with_event(
+                    shortcut,
+                    move |fired| {
+                        if !tx.send(fired) {
+                            metrics.dropped();
+                        }
+                    },
+                    ShortcutOptions::default(),
+                )?;
+                Ok(self.subscription(id))
+            },
+            consts::SUBSCRIBE_QUEUE_CAPACITY,
+        )
+    }
+
+    /// Blocks until an event matching `filter` arrives and returns it, or
+    /// returns `None` once `timeout` has passed. Must not be called from a
+    /// callback, which would hold up the worker that delivers the event.
+    pub fn next_event(
+        self: &Arc<Self>,
+        filter: Option<EventFilter>,
+        timeout: Option<Duration>,
+    ) -> Option<Event> {
+        let mut receiver = self.subscribe(filter).ok()?;
+        match timeout {
+            Some(timeout) => receiver.recv_timeout(timeout),
+            None => receiver.next(),
         }
+    }
 
-        let binding = self.event_map.lock().unwrap();
-        for (_, (et, _)) in binding.iter() {
-            if matches!(et, EventType::KeyboardEvent(_) | EventType::All) {
-                return true;
-            }
+    /// `next_event` for the next key press, e.g. for "press any key".
+    pub fn wait_for_key(self: &Arc<Self>, timeout: Option<Duration>) -> Option<KeyInfo> {
+        match self.next_event(Some(EventFilter::KeyDown), timeout)? {
+            Event::KeyboardEvent(key_info) => Some(key_info),
+            _ => None,
         }
-        false
     }
 
-    pub fn has_mouse_event(&self) -> bool {
-        let binding = self.event_map.lock().unwrap();
-        for (_, (et, _)) in binding.iter() {
-            if matches!(et, EventType::MouseEvent(_) | EventType::All) {
-                return true;
+    /// Records the next shortcut typed, for "press the new shortcut" fields:
+    /// keys are collected until all of them are let go. Modifiers are
+    /// returned without their side, "Ctrl+K" rather than "CtrlLeft+K".
+    /// Escape on its own cancels. Like `next_event`, must not be called
+    /// from a callback.
+    pub fn capture_shortcut(
+        self: &Arc<Self>,
+        timeout: Option<Duration>,
+    ) -> Result<Shortcut, String> {
+        let mut receiver = self.subscribe(Some(EventFilter::Keyboard))?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut shortcut = Shortcut::default();
+        let mut held = Vec::new();
+        loop {
+            let event = match deadline {
+                Some(deadline) => receiver
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok_or("Timed out waiting for a shortcut")?,
+                None => receiver.next().ok_or("Listener dropped")?,
+            };
+            let Event::KeyboardEvent(key_info) = event else {
+                continue;
+            };
+            let key: VirtualKeyId = key_info.key_id.into();
+            if key_info.state == KeyState::Pressed {
+                if !held.contains(&key) {
+                    held.push(key);
+                }
+                shortcut.set_key(Self::generic_modifier(key));
+                continue;
+            }
+            // Keys held before the capture began are not part of it.
+            if !held.contains(&key) {
+                continue;
+            }
+            held.retain(|&k| k != key);
+            if held.is_empty() {
+                break;
             }
         }
-        false
+        if !shortcut.has_modifier() && shortcut.normal_keys() == [VirtualKeyId::Escape] {
+            return Err("Shortcut capture cancelled".to_string());
+        }
+        Ok(shortcut.canonicalize())
+    }
+
+    fn generic_modifier(key: VirtualKeyId) -> VirtualKeyId {
+        match key {
+            VirtualKeyId::ControlLeft | VirtualKeyId::ControlRight => VirtualKeyId::Control,
+            VirtualKeyId::ShiftLeft | VirtualKeyId::ShiftRight => VirtualKeyId::Shift,
+            VirtualKeyId::AltLeft | VirtualKeyId::AltRight => VirtualKeyId::Alt,
+            VirtualKeyId::MetaLeft | VirtualKeyId::MetaRight => VirtualKeyId::Meta,
+            key => key,
+        }
+    }
+
+    fn subscription(self: &Arc<Self>, id: ID) -> Subscription {
+        Subscription {
+            id,
+            listener: Arc::downgrade(self),
+        }
     }
 
     fn register_shortcut_callback(
         &self,
         shortcut: &str,
-        trigger: FnShourtcutTrigger,
-    ) -> Result<usize, String> {
+        mut trigger: FnShourtcutTrigger,
+    ) -> Result<ID, RegisterError> {
         let id = self.gen_id();
+        trigger
+            .options
+            .side_policy
+            .get_or_insert(self.config.side_policy);
         {
-            let shortcut = Shortcut::from_str(shortcut)?;
-            let mut binding = self.shortcut_map.lock().map_err(|e| e.to_string())?;
-            for (_, (sc, _)) in binding.iter() {
+            let shortcut = ShortcutSequence::from_str(shortcut).map_err(RegisterError::Parse)?;
+            let mut binding = self.shortcut_map.lock().unwrap();
+            for (_, (sc, existing)) in binding.iter() {
                 // println!("sc usb_input: {:?}", sc.usb_input());
                 // println!("shortcut usb_input: {:?}", shortcut.usb_input());
-                if *sc == shortcut {
-                    return Err("Shortcut already exists".to_string());
+                // The same keys may be bound once per application context and device.
+                if *sc == shortcut
+                    && existing.options.context == trigger.options.context
+                    && existing.options.device == trigger.options.device
+                {
+                    return Err(RegisterError::Duplicate(shortcut));
                 }
             }
+            if self.config.detect_conflicts {
+                self.check_conflicts(shortcut.clone(), &binding)?;
+            }
             binding.insert(id, (shortcut, trigger));
         }
         Ok(id)
     }
 }
 
+/// Unregisters a callback when dropped. Returned by the `*_scoped` methods
+/// so a registration can follow the lifetime of the component that owns it.
+#[must_use = "the callback is unregistered as soon as the Subscription is dropped"]
+#[derive(Debug)]
+pub struct Subscription {
+    id: ID,
+    listener: Weak<Listener>,
+}
+
+impl Subscription {
+    pub fn id(&self) -> ID {
+        self.id
+    }
+
+    /// Keeps the registration alive past the guard; returns its ID for
+    /// manual `del_event_by_id`.
+    pub fn detach(self) -> ID {
+        let id = self.id;
+        std::mem::forget(self);
+        id
+    }
+}
+
+/// Events queued by `Listener::subscribe`. Iterating blocks for the next
+/// event and ends when the listener is dropped.
+#[derive(Debug)]
+pub struct EventReceiver {
+    rx: mpsc::Receiver<Event>,
+    subscription: Subscription,
+}
+
+impl EventReceiver {
+    pub fn id(&self) -> ID {
+        self.subscription.id()
+    }
+
+    /// The next queued event, without waiting.
+    pub fn try_recv(&self) -> Option<Event> {
+        self.rx.try_recv().ok()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Event> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for EventReceiver {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Ends a `Listener::block_input` when dropped, unless another block
+/// replaced it.
+#[cfg(feature = "suppression")]
+#[must_use = "input is unblocked as soon as the InputBlockGuard is dropped"]
+#[derive(Debug)]
+pub struct InputBlockGuard {
+    id: ID,
+    listener: Weak<Listener>,
+}
+
+#[cfg(feature = "suppression")]
+impl Drop for InputBlockGuard {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.upgrade() {
+            let mut block = listener.input_block.lock().unwrap();
+            if block.as_ref().map_or(false, |active| active.id == self.id) {
+                *block = None;
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(listener) = self.listener.upgrade() {
+            listener.del_event_by_id(self.id);
+        }
+    }
+}
+
 impl Drop for Listener {
     fn drop(&mut self) {
-        println!("Listener drop");
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Listener drop");
         self.shutdown();
     }
 }
 
 impl EventListener for Listener {
     fn new() -> Arc<Self> {
-        let listener = Self {
-            listener_event_loop: Mutex::new(None),
-            event_map: Mutex::new(HashMap::new()),
-            shortcut_map: Mutex::new(HashMap::new()),
-            worker: Mutex::new(None),
-            shortcut_ex_map: Mutex::new(HashMap::new()),
-        };
-        let rc = Arc::new(listener);
-        rc.listener_event_loop
-            .lock()
-            .unwrap()
-            .replace(EVENT_LOOP_MANAGER.lock().unwrap().new_event_loop(&rc));
-        rc.worker.lock().unwrap().replace(Arc::new(Worker::new()));
-        rc
+        Self::with_config(ListenerConfig::default())
     }
 
     /// `work_thread`:
     /// Handle event callbacks in a separate thread. Default is `true`.
-    /// return: `Option<JoinHandleType>` if `work_thread` is `true`, else `None`.
-    fn startup(self: &Arc<Self>, work_thread: Option<bool>) -> Option<JoinHandleType> {
-        if let Some(event_loop) = self.get_event_loop().as_ref() {
-            event_loop.run_with_thread();
-        }
-
-        if let Some(w) = self.get_worker() {
-            let _self = self.clone();
-            w.run(
-                move |event_type| {
-                    _self.on_event(event_type);
-                },
-                work_thread,
-            )
-        } else {
-            None
+    /// Dedicated workers added with `add_worker` always run in their own thread.
+    /// return: `Option<JoinHandleType>` of the shared worker if `work_thread` is `true`, else `None`.
+    /// Fails if the input backend could not be set up. Hooks are only
+    /// installed once a callback needs them, so failures for callbacks added
+    /// after startup are not reported here.
+    fn startup(
+        self: &Arc<Self>,
+        work_thread: Option<bool>,
+    ) -> Result<Option<JoinHandleType>, KmHookError> {
+        self.check_backend()?;
+        self.open_callback_pool()?;
+        let workers = self.open_workers();
+        let Some(event_loop) = self.get_event_loop() else {
+            return Err(KmHookError::BackendUnavailable(self.config.backend));
+        };
+        event_loop.start()?;
+        let result = self.run_workers(workers, work_thread);
+        if result.is_err() {
+            self.post_worker_msg(WorkerMsg::Stop);
+            event_loop.stop();
         }
+        result
     }
 
     fn shutdown(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("shutdown");
         self.del_all_events();
-        if let Some(worker) = self.get_worker() {
-            worker.post_msg(WorkerMsg::Stop);
-        }
+        self.post_worker_msg(WorkerMsg::Stop);
         if let Some(event_loop) = self.listener_event_loop.lock().unwrap().as_ref() {
             event_loop.stop();
         }
     }
 
-    fn add_event_listener<F>(&self, cb: F, event_type: Option<EventType>) -> Result<ID, String>
+    fn add_event_listener<F>(&self, cb: F, filter: Option<EventFilter>) -> Result<ID, String>
     where
-        F: Fn(EventType) + Send + Sync + 'static,
+        F: Fn(Event) + Send + Sync + 'static,
     {
-        let id = self.gen_id();
-        let et = event_type.unwrap_or(EventType::All);
-        self.event_map
-            .lock()
-            .unwrap()
-            .insert(id, (et, Arc::new(Box::new(cb))));
-        self.post_recheck_hook();
-        Ok(id)
+        self.add_event_listener_with_affinity(cb, filter, WorkerAffinity::Shared)
     }
 
-    fn add_global_shortcut<F>(&self, shortcut: &str, cb: F) -> std::result::Result<ID, String>
+    fn add_global_shortcut<F>(
+        &self,
+        shortcut: &str,
+        cb: F,
+    ) -> std::result::Result<ID, RegisterError>
     where
         F: Fn() + Send + Sync + 'static,
     {
@@ -306,54 +2683,23 @@ impl EventListener for Listener {
         cb: F,
         trigger: u32,
         internal: Option<u32>,
-    ) -> std::result::Result<ID, String>
+    ) -> std::result::Result<ID, RegisterError>
     where
         F: Fn() + Send + Sync + 'static,
     {
-        let trigger_info = Arc::new(Mutex::new(ShortcutTriggerInfo::new()));
-        let next_internal = internal.unwrap_or(consts::DEFAULT_SHORTCUT_TRIGGER_INTERVAL) as u128;
-
-        self.add_global_shortcut(shortcut, move || {
-            #[cfg(feature = "Debug")]
-            println!("global_shortcut trigger: {:?}", Instant::now());
-
-            let need_trigger = {
-                let mut mtrigger_info = trigger_info.lock().unwrap();
-
-                let elapsed = mtrigger_info.last_trigger_time.elapsed().as_millis();
-                #[cfg(feature = "Debug")]
-                println!(
-                    "trigger times: {:?}, elapsed: {:?}",
-                    mtrigger_info.trigger, elapsed
-                );
-
-                if mtrigger_info.trigger == 0 || elapsed < next_internal {
-                    mtrigger_info.increase();
-                } else {
-                    mtrigger_info.reset();
-                    mtrigger_info.increase();
-                }
-                if mtrigger_info.trigger >= trigger {
-                    mtrigger_info.reset();
-                    true
-                } else {
-                    false
-                }
-            };
-            if need_trigger {
-                cb();
-                #[cfg(feature = "Debug")]
-                println!(
-                    "------------------------Trigger------------------------{:?}",
-                    Instant::now()
-                );
-            }
-        })
+        self.add_global_shortcut_trigger_with_options(
+            shortcut,
+            cb,
+            trigger,
+            internal,
+            ShortcutOptions::default(),
+        )
     }
 
     fn del_all_events(&self) {
         self.event_map.lock().unwrap().clear();
         self.shortcut_map.lock().unwrap().clear();
+        self.shortcut_groups.lock().unwrap().clear();
         self.post_recheck_hook();
     }
 
@@ -366,7 +2712,146 @@ impl EventListener for Listener {
         }
         self.event_map.lock().unwrap().remove(&id);
         self.shortcut_map.lock().unwrap().remove(&id);
+        for ids in self.shortcut_groups.lock().unwrap().values_mut() {
+            ids.retain(|&member| member != id);
+        }
         self.post_recheck_hook();
-        println!("del_event_by_id finish {:?}", id);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(?id, "del_event_by_id finish");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_buffer_installs_hooks() {
+        let listener = Listener::builder().build();
+        assert!(!listener.has_keyboard_event());
+        assert!(!listener.has_mouse_event());
+
+        let listener = Listener::builder().poll_buffer(16).build();
+        assert!(listener.has_keyboard_event());
+        assert!(listener.has_mouse_event());
+    }
+
+    #[test]
+    fn autofire_panics_reach_error_handler() {
+        let listener = Listener::builder().build();
+        let panics = Arc::new(AtomicU64::new(0));
+        {
+            let panics = panics.clone();
+            listener.set_error_handler(move |_| {
+                panics.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        let trigger = FnShourtcutTrigger::with_options(
+            |_| panic!("autofire"),
+            ShortcutOptions {
+                autofire: Some(Duration::from_millis(5)),
+                ..Default::default()
+            },
+        );
+        let sequence: ShortcutSequence = "Ctrl+A".parse().unwrap();
+        trigger
+            .fire(1, &sequence, None, &listener.config, &listener.guard())
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        trigger.progress.lock().unwrap().interrupt();
+        // The repeats went on after the first one panicked.
+        assert!(panics.load(Ordering::Relaxed) >= 2);
+    }
+
+    #[test]
+    fn autofire_stops_at_max_triggers() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let trigger = {
+            let fired = fired.clone();
+            FnShourtcutTrigger::with_options(
+                move |event| fired.lock().unwrap().push(event.trigger_count),
+                ShortcutOptions {
+                    autofire: Some(Duration::from_millis(5)),
+                    max_triggers: Some(3),
+                    ..Default::default()
+                },
+            )
+        };
+        let listener = Listener::builder().build();
+        let sequence: ShortcutSequence = "Ctrl+A".parse().unwrap();
+        let first = trigger
+            .fire(1, &sequence, None, &listener.config, &listener.guard())
+            .unwrap();
+        assert!(!trigger.is_spent(&first));
+        std::thread::sleep(Duration::from_millis(100));
+        // Repeats 2 and 3 only; the first is run by the caller.
+        assert_eq!(*fired.lock().unwrap(), vec![2, 3]);
+        // Still held, but spent.
+        assert!(trigger
+            .fire(1, &sequence, None, &listener.config, &listener.guard())
+            .is_none());
+        trigger.progress.lock().unwrap().interrupt();
+    }
+
+    #[test]
+    fn register_errors() {
+        let listener = Listener::builder().detect_conflicts(true).build();
+        let id = listener.add_global_shortcut("Ctrl+C", || {}).unwrap();
+        assert!(matches!(
+            listener.add_global_shortcut("Ctrl+", || {}),
+            Err(RegisterError::Parse(_))
+        ));
+        assert!(matches!(
+            listener.add_global_shortcut("Ctrl+C", || {}),
+            Err(RegisterError::Duplicate(_))
+        ));
+        match listener.add_global_shortcut("ControlLeft+C", || {}) {
+            Err(RegisterError::Conflict(e)) => assert_eq!(e.conflicts, vec![id]),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert_eq!(
+            listener.add_global_shortcut_to_group(0, "Ctrl+D", || {}),
+            Err(RegisterError::GroupNotFound(0))
+        );
+    }
+
+    #[test]
+    fn del_group_removes_extra_shortcuts() {
+        let listener = Listener::new();
+        let group = listener.create_group();
+        let id = listener
+            .add_global_shortcut_to_group(group, "Ctrl+E", || {})
+            .unwrap();
+        let extra = listener.add_global_shortcut("Ctrl+F", || {}).unwrap();
+        listener
+            .shortcut_ex_map
+            .lock()
+            .unwrap()
+            .insert(id, vec![extra]);
+        listener.del_group(group).unwrap();
+        assert!(listener.shortcuts().is_empty());
+        assert!(listener.shortcut_ex_map.lock().unwrap().is_empty());
+        assert!(listener.del_group(group).is_err());
+    }
+
+    #[test]
+    fn subscribe_drops_when_full() {
+        let listener = Listener::new();
+        let receiver = listener.subscribe_with_capacity(None, 2).unwrap();
+        for _ in 0..3 {
+            let key_info = KeyInfo::new(KeyId::from(VirtualKeyId::UsA), KeyState::Pressed);
+            listener.on_event(WorkerAffinity::Shared, Event::KeyboardEvent(key_info));
+        }
+        assert_eq!(listener.metrics().dropped_events, 1);
+        assert!(receiver.try_recv().is_some());
+        assert!(receiver.try_recv().is_some());
+        assert!(receiver.try_recv().is_none());
+    }
+
+    #[test]
+    fn track_input_state_installs_hooks() {
+        let listener = Listener::builder().track_input_state(true).build();
+        assert!(listener.has_keyboard_event());
+        assert!(listener.has_mouse_event());
     }
 }
@@ -8,13 +8,76 @@
 //! Description: This is a windows event listener library.
 #![allow(incomplete_features)]
 
+use crate::types::{
+    Backend, KeyInfo, KeyMap, KeyMappingId, KeySnapshot, KeyState, LockKeyState, Modifiers, Pos,
+    ThreadPriority, VirtualKeyId,
+};
+use backend::InputBackend;
+use listener::Listener;
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub(crate) mod backend;
+pub(crate) mod device;
+pub(crate) mod hid;
+pub(crate) mod hotkey;
+pub(crate) mod layout;
 pub mod listener;
+pub(crate) mod monitor;
+pub(crate) mod power;
+#[cfg(feature = "simulate")]
+pub mod simulate;
 pub mod types_ext;
+pub(crate) mod window;
+
+pub(crate) use monitor::monitor_at;
+pub(crate) use window::{foreground_window, is_foreground_fullscreen, window_at};
+
+#[cfg(not(any(feature = "raw-input", feature = "ll-hook")))]
+compile_error!(
+    "kmhook needs an input backend: enable the `raw-input` (default) or `ll-hook` feature"
+);
 
-// #[cfg(all(feature = "Fake", not(feature = "DLL")))]
+#[cfg(feature = "raw-input")]
 #[path = "event_loop_fake.rs"]
+mod raw_input;
+
+#[cfg(feature = "ll-hook")]
 mod event_loop;
 
+#[cfg(feature = "dll-hook")]
+mod dll_hook;
+#[cfg(feature = "dll-hook")]
+mod dll_hook_abi;
+
+/// The event loop for `backend`; `None` if its feature is not enabled.
+pub(crate) fn new_backend(
+    backend: Backend,
+    listener: &Arc<Listener>,
+) -> Option<Arc<dyn InputBackend>> {
+    match backend {
+        #[cfg(feature = "raw-input")]
+        Backend::RawInput => Some(
+            raw_input::EVENT_LOOP_MANAGER
+                .lock()
+                .unwrap()
+                .new_event_loop(listener),
+        ),
+        #[cfg(feature = "ll-hook")]
+        Backend::LowLevelHook => Some(
+            event_loop::EVENT_LOOP_MANAGER
+                .lock()
+                .unwrap()
+                .new_event_loop(listener),
+        ),
+        #[cfg(feature = "dll-hook")]
+        Backend::DllHook => listener.config().dll_hook.clone().map(|(dll, thread_id)| {
+            Arc::new(dll_hook::DllHook::new(dll, thread_id, listener)) as Arc<dyn InputBackend>
+        }),
+        _ => None,
+    }
+}
 
 pub(crate) mod worker;
 
@@ -25,3 +88,256 @@ pub(crate) mod worker;
 // }
 
 pub(crate) const WM_USER_RECHECK_HOOK: u32 = 1;
+pub(crate) const WM_USER_APPLY_CURSOR_CLIP: u32 = 2;
+pub(crate) const WM_USER_POWER_RESUME: u32 = 3;
+
+thread_local! {
+    static LOCAL_KEY_SNAPSHOT: RefCell<KeySnapshot> = RefCell::new(KeySnapshot::default());
+    /// The last key pressed on this thread and its message time.
+    static LOCAL_LAST_PRESS: Cell<Option<(VirtualKeyId, u32)>> = const { Cell::new(None) };
+}
+
+/// Applies `key_info` to the pressed-key snapshot of the calling (event loop)
+/// thread and attaches the result to it. Returns `false` if nothing changed,
+/// e.g. for an auto-repeated key press.
+pub(crate) fn update_key_snapshot(key_info: &mut KeyInfo) -> bool {
+    LOCAL_KEY_SNAPSHOT.with_borrow_mut(|snapshot| {
+        let key: VirtualKeyId = key_info.key_id.into();
+        let changed = if key_info.state == KeyState::Pressed {
+            let last = LOCAL_LAST_PRESS.replace(Some((key, key_info.time)));
+            if is_alt_gr(last, key, key_info.time, || {
+                layout::has_alt_gr(layout::foreground_layout())
+            }) {
+                // The worker sends listeners the matching release.
+                snapshot.release(VirtualKeyId::ControlLeft);
+                snapshot.alt_gr = true;
+            }
+            if key == VirtualKeyId::ControlLeft && snapshot.alt_gr {
+                // Its auto-repeat while AltGr is held.
+                false
+            } else {
+                snapshot.press(key)
+            }
+        } else {
+            if key == VirtualKeyId::AltRight {
+                snapshot.alt_gr = false;
+            }
+            snapshot.release(key)
+        };
+        snapshot.locks = LockKeyState::current();
+        snapshot.timestamp = Instant::now();
+        key_info.keyboard_state.replace(snapshot.clone());
+        changed
+    })
+}
+
+/// Whether a press of `key` at message time `time` is AltGr: on layouts with
+/// AltGr, right Alt comes right after a left Ctrl press with the same time,
+/// which is not a real Ctrl. Elsewhere Ctrl+right Alt is just that, even if
+/// both land on the same tick.
+fn is_alt_gr(
+    last_press: Option<(VirtualKeyId, u32)>,
+    key: VirtualKeyId,
+    time: u32,
+    layout_has_alt_gr: impl FnOnce() -> bool,
+) -> bool {
+    key == VirtualKeyId::AltRight
+        && last_press == Some((VirtualKeyId::ControlLeft, time))
+        && layout_has_alt_gr()
+}
+
+/// `GetKeyNameTextW` for `key`; generic modifiers get the name of either side.
+pub(crate) fn key_name_text(key: VirtualKeyId) -> Option<String> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::GetKeyNameTextW;
+
+    // Bit 24 of the lParam marks an extended key, bit 25 ignores the side.
+    let (key, any_side) = match key {
+        VirtualKeyId::Control => (VirtualKeyId::ControlLeft, true),
+        VirtualKeyId::Shift => (VirtualKeyId::ShiftLeft, true),
+        VirtualKeyId::Alt => (VirtualKeyId::AltLeft, true),
+        VirtualKeyId::Meta => (VirtualKeyId::MetaLeft, true),
+        key => (key, false),
+    };
+    let scancode = KeyMap::from(KeyMappingId::try_from(key).ok()?).win as i32;
+    let mut lparam = (scancode & 0xFF) << 16;
+    if scancode & 0xFF00 == 0xE000 {
+        lparam |= 1 << 24;
+    }
+    if any_side {
+        lparam |= 1 << 25;
+    }
+    let mut name = [0u16; 64];
+    let len = unsafe { GetKeyNameTextW(lparam, &mut name) };
+    (len > 0).then(|| String::from_utf16_lossy(&name[..len as usize]))
+}
+
+/// Runs a key press through `ToUnicodeEx` with the foreground window's layout
+/// and the modifiers and locks of the press's snapshot. The keyboard state is
+/// left untouched so a dead key typed into the application still composes;
+/// in turn, dead keys yield no text here and are not combined with the next key.
+pub(crate) fn key_text(key_info: &KeyInfo) -> Option<String> {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        MapVirtualKeyExW, ToUnicodeEx, MAPVK_VSC_TO_VK_EX, VIRTUAL_KEY, VK_CAPITAL, VK_CONTROL,
+        VK_LCONTROL, VK_LMENU, VK_LSHIFT, VK_MENU, VK_NUMLOCK, VK_RCONTROL, VK_RMENU, VK_RSHIFT,
+        VK_SCROLL, VK_SHIFT,
+    };
+
+    // Leave the keyboard state unchanged (Windows 10 1607 and later).
+    const TO_UNICODE_KEEP_STATE: u32 = 1 << 2;
+
+    if key_info.state != KeyState::Pressed {
+        return None;
+    }
+    let snapshot = key_info.keyboard_state.as_ref()?;
+    let id = KeyMappingId::try_from(key_info.key_id.0).ok()?;
+    let scancode = KeyMap::from(id).win as u32;
+
+    let mut state = [0u8; 256];
+    let mut set = |vk: VIRTUAL_KEY, value: u8| state[vk.0 as usize] |= value;
+    for (modifier, side, generic) in [
+        (Modifiers::SHIFT_LEFT, VK_LSHIFT, VK_SHIFT),
+        (Modifiers::SHIFT_RIGHT, VK_RSHIFT, VK_SHIFT),
+        (Modifiers::CONTROL_LEFT, VK_LCONTROL, VK_CONTROL),
+        (Modifiers::CONTROL_RIGHT, VK_RCONTROL, VK_CONTROL),
+        (Modifiers::ALT_LEFT, VK_LMENU, VK_MENU),
+        (Modifiers::ALT_RIGHT, VK_RMENU, VK_MENU),
+    ] {
+        if snapshot.modifiers.contains(modifier) {
+            set(side, 0x80);
+            set(generic, 0x80);
+        }
+    }
+    // The layout expects the left Ctrl that AltGr implies.
+    if snapshot.alt_gr {
+        set(VK_LCONTROL, 0x80);
+        set(VK_CONTROL, 0x80);
+    }
+    for (locked, vk) in [
+        (snapshot.locks.caps, VK_CAPITAL),
+        (snapshot.locks.num, VK_NUMLOCK),
+        (snapshot.locks.scroll, VK_SCROLL),
+    ] {
+        if locked {
+            set(vk, 0x01);
+        }
+    }
+
+    let mut buf = [0u16; 8];
+    let len = unsafe {
+        let layout = layout::foreground_layout();
+        let vk = MapVirtualKeyExW(scancode, MAPVK_VSC_TO_VK_EX, layout);
+        if vk == 0 {
+            return None;
+        }
+        state[vk as usize] |= 0x80;
+        ToUnicodeEx(
+            vk,
+            scancode,
+            &state,
+            &mut buf,
+            TO_UNICODE_KEEP_STATE,
+            layout,
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    let text: String = String::from_utf16_lossy(&buf[..len as usize])
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Makes the calling thread per-monitor DPI aware so cursor positions and
+/// window/monitor queries use physical pixels whatever the process manifest
+/// declares. Only for threads owned by the crate.
+pub(crate) fn set_thread_dpi_aware() {
+    use windows::Win32::UI::HiDpi::{
+        SetThreadDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+    };
+    unsafe {
+        SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+/// Applies the priority and CPU affinity of a `worker::ThreadSpec` to the
+/// calling thread.
+pub(crate) fn configure_thread(priority: ThreadPriority, cpu_affinity: Option<usize>) {
+    use windows::Win32::System::Threading::{
+        GetCurrentThread, SetThreadAffinityMask, SetThreadPriority, THREAD_PRIORITY_ABOVE_NORMAL,
+        THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_NORMAL, THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    let level = match priority {
+        ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+        ThreadPriority::AboveNormal => THREAD_PRIORITY_ABOVE_NORMAL,
+        ThreadPriority::Highest => THREAD_PRIORITY_HIGHEST,
+        ThreadPriority::TimeCritical => THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    unsafe {
+        let thread_handle = GetCurrentThread();
+        if SetThreadPriority(thread_handle, level).is_err() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(?thread_handle, "SetThreadPriority failed");
+        }
+        if let Some(mask) = cpu_affinity {
+            if SetThreadAffinityMask(thread_handle, mask) == 0 {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(?thread_handle, mask, "SetThreadAffinityMask failed");
+            }
+        }
+    }
+}
+
+/// Runs `f` per-monitor DPI aware, restoring the thread's previous mode;
+/// for queries made from threads the crate doesn't own.
+pub(crate) fn with_physical_pixels<T>(f: impl FnOnce() -> T) -> T {
+    use windows::Win32::UI::HiDpi::{
+        SetThreadDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+    };
+    let previous =
+        unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+    let result = f();
+    if !previous.0.is_null() {
+        unsafe {
+            SetThreadDpiAwarenessContext(previous);
+        }
+    }
+    result
+}
+
+/// Milliseconds between clicks that still count as a double click, from the
+/// user's mouse settings.
+pub(crate) fn double_click_interval() -> u32 {
+    unsafe { windows::Win32::UI::Input::KeyboardAndMouse::GetDoubleClickTime() }
+}
+
+/// Width and height of the area the second click of a double click must
+/// land in, centered on the first.
+pub(crate) fn double_click_size() -> Pos {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetSystemMetrics, SM_CXDOUBLECLK, SM_CYDOUBLECLK,
+    };
+    unsafe {
+        Pos {
+            x: GetSystemMetrics(SM_CXDOUBLECLK),
+            y: GetSystemMetrics(SM_CYDOUBLECLK),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alt_gr_needs_layout_and_same_time() {
+        let ctrl = Some((VirtualKeyId::ControlLeft, 100));
+        assert!(is_alt_gr(ctrl, VirtualKeyId::AltRight, 100, || true));
+        // Ctrl and right Alt pressed together on a layout without AltGr.
+        assert!(!is_alt_gr(ctrl, VirtualKeyId::AltRight, 100, || false));
+        // Ctrl held down before right Alt.
+        assert!(!is_alt_gr(ctrl, VirtualKeyId::AltRight, 250, || true));
+        assert!(!is_alt_gr(ctrl, VirtualKeyId::AltLeft, 100, || true));
+    }
+}
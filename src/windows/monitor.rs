@@ -0,0 +1,168 @@
+//! Monitor lookup for mouse events.
+
+use crate::types::{Event, MonitorInfo, Pos, Rect};
+use lazy_static::lazy_static;
+use std::cell::Cell;
+use std::sync::Mutex;
+use windows::Win32::Foundation::{BOOL, LPARAM, POINT, RECT};
+use windows::Win32::Graphics::Gdi::{
+    EnumDisplayMonitors, GetMonitorInfoW, MonitorFromPoint, HDC, HMONITOR, MONITORINFO,
+    MONITOR_DEFAULTTONEAREST,
+};
+use windows::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetSystemMetrics, SM_CXSCREEN, SM_CXVIRTUALSCREEN, SM_CYSCREEN, SM_CYVIRTUALSCREEN,
+    SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+};
+
+lazy_static! {
+    /// Monitors in enumeration order, keyed by `HMONITOR` value.
+    static ref MONITORS: Mutex<Vec<(isize, MonitorInfo)>> = Mutex::new(Vec::new());
+    /// The monitor `monitor_at` found last. The cursor mostly stays on one,
+    /// so its bounds are checked before asking the system.
+    static ref LAST_MONITOR: Mutex<Option<MonitorInfo>> = Mutex::new(None);
+}
+
+/// The areas absolute mouse coordinates are mapped onto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ScreenMetrics {
+    pub virtual_screen: Rect,
+    /// The primary monitor, with its origin at 0, 0.
+    pub primary: Rect,
+}
+
+thread_local! {
+    static LOCAL_SCREEN: Cell<Option<ScreenMetrics>> = const { Cell::new(None) };
+}
+
+fn query_screen() -> ScreenMetrics {
+    unsafe {
+        let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        ScreenMetrics {
+            virtual_screen: Rect::new(
+                left,
+                top,
+                left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                top + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+            ),
+            primary: Rect::new(
+                0,
+                0,
+                GetSystemMetrics(SM_CXSCREEN),
+                GetSystemMetrics(SM_CYSCREEN),
+            ),
+        }
+    }
+}
+
+/// Screen metrics as last seen on the calling thread; see `display_change`.
+pub(crate) fn screen_metrics() -> ScreenMetrics {
+    LOCAL_SCREEN.get().unwrap_or_else(|| {
+        let metrics = query_screen();
+        LOCAL_SCREEN.set(Some(metrics));
+        metrics
+    })
+}
+
+/// Queries the screen metrics of the calling thread again and returns
+/// `Event::DisplayChanged` if they differ. Called on `WM_DISPLAYCHANGE` and
+/// from the event loops' poll timer, since a message-only window gets no
+/// broadcasts.
+pub(crate) fn display_change() -> Option<Event> {
+    let metrics = query_screen();
+    if LOCAL_SCREEN.replace(Some(metrics)) == Some(metrics) {
+        return None;
+    }
+    let monitors = super::with_physical_pixels(enumerate);
+    let infos = monitors.iter().map(|(_, info)| *info).collect();
+    *MONITORS.lock().unwrap() = monitors;
+    *LAST_MONITOR.lock().unwrap() = None;
+    Some(Event::DisplayChanged(infos))
+}
+
+fn bounds(monitor: HMONITOR) -> Option<Rect> {
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetMonitorInfoW(monitor, &mut info) }
+        .as_bool()
+        .then(|| info.rcMonitor.into())
+}
+
+fn dpi(monitor: HMONITOR) -> u32 {
+    let (mut x, mut y) = (0, 0);
+    match unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut x, &mut y) } {
+        Ok(()) => x,
+        Err(_) => 96,
+    }
+}
+
+unsafe extern "system" fn collect_monitor(
+    monitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    data: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(data.0 as *mut Vec<HMONITOR>);
+    monitors.push(monitor);
+    true.into()
+}
+
+fn enumerate() -> Vec<(isize, MonitorInfo)> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(collect_monitor),
+            LPARAM(&mut handles as *mut Vec<HMONITOR> as isize),
+        );
+    }
+    handles
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, monitor)| {
+            let info = MonitorInfo {
+                index,
+                bounds: bounds(monitor)?,
+                dpi: dpi(monitor),
+            };
+            Some((monitor.0 as isize, info))
+        })
+        .collect()
+}
+
+/// The monitor showing `pos`, or the nearest one. The monitor list is cached
+/// and enumerated again when a handle or its bounds are not in it; the
+/// system is only asked once `pos` leaves the last monitor found, or after
+/// the display configuration changed.
+pub(crate) fn monitor_at(pos: &Pos) -> Option<MonitorInfo> {
+    let mut last = LAST_MONITOR.lock().unwrap();
+    if let Some(info) = *last {
+        if info.bounds.contains(pos) {
+            return Some(info);
+        }
+    }
+    *last = super::with_physical_pixels(|| lookup_monitor(pos));
+    *last
+}
+
+fn lookup_monitor(pos: &Pos) -> Option<MonitorInfo> {
+    let monitor =
+        unsafe { MonitorFromPoint(POINT { x: pos.x, y: pos.y }, MONITOR_DEFAULTTONEAREST) };
+    let current = bounds(monitor)?;
+    let lookup = |monitors: &Vec<(isize, MonitorInfo)>| {
+        monitors
+            .iter()
+            .find(|(handle, info)| *handle == monitor.0 as isize && info.bounds == current)
+            .map(|(_, info)| *info)
+    };
+    let mut monitors = MONITORS.lock().unwrap();
+    if let Some(info) = lookup(&monitors) {
+        return Some(info);
+    }
+    *monitors = enumerate();
+    lookup(&monitors)
+}
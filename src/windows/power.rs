@@ -0,0 +1,64 @@
+//! Resume from sleep, after which Windows may have dropped the hooks and raw
+//! input registrations. Reported to the event loop as `WM_USER_POWER_RESUME`.
+
+use super::WM_USER_POWER_RESUME;
+use std::cell::Cell;
+use std::ffi::c_void;
+use windows::Win32::Foundation::{ERROR_SUCCESS, HANDLE, WPARAM};
+use windows::Win32::System::Power::{
+    PowerRegisterSuspendResumeNotification, PowerUnregisterSuspendResumeNotification,
+    DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS, HPOWERNOTIFY,
+};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::WindowsAndMessaging::{
+    PostThreadMessageW, DEVICE_NOTIFY_CALLBACK, PBT_APMRESUMEAUTOMATIC, WM_USER,
+};
+
+thread_local! {
+    static LOCAL_NOTIFY: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Runs on a system thread; `context` is the ID of the event loop thread.
+unsafe extern "system" fn on_power_event(
+    context: *const c_void,
+    kind: u32,
+    _setting: *const c_void,
+) -> u32 {
+    if kind == PBT_APMRESUMEAUTOMATIC {
+        let _ = PostThreadMessageW(
+            context as usize as u32,
+            WM_USER,
+            WPARAM(WM_USER_POWER_RESUME as usize),
+            None,
+        );
+    }
+    0
+}
+
+/// Subscribes the calling event loop thread to resume notifications. A
+/// callback is used rather than `WM_POWERBROADCAST`, which is only sent to
+/// top-level windows.
+pub(crate) fn watch_resume() {
+    let mut params = DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS {
+        Callback: Some(on_power_event),
+        Context: unsafe { GetCurrentThreadId() } as usize as *mut c_void,
+    };
+    let mut handle: *mut c_void = std::ptr::null_mut();
+    let status = unsafe {
+        PowerRegisterSuspendResumeNotification(
+            DEVICE_NOTIFY_CALLBACK,
+            HANDLE(&mut params as *mut DEVICE_NOTIFY_SUBSCRIBE_PARAMETERS as _),
+            &mut handle,
+        )
+    };
+    if status == ERROR_SUCCESS {
+        LOCAL_NOTIFY.set(handle as isize);
+    }
+}
+
+pub(crate) fn unwatch_resume() {
+    let handle = LOCAL_NOTIFY.replace(0);
+    if handle != 0 {
+        let _ = unsafe { PowerUnregisterSuspendResumeNotification(HPOWERNOTIFY(handle as _)) };
+    }
+}
@@ -0,0 +1,116 @@
+//! Synthesized keyboard and mouse input through `SendInput`.
+
+use crate::types::{KeyId, KeyMap, KeyMappingId, KeyState, MouseButton, Pos};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT, KEYEVENTF_EXTENDEDKEY,
+    KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_HWHEEL,
+    MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP,
+    MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK,
+    MOUSEEVENTF_WHEEL, MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP, MOUSEINPUT, MOUSE_EVENT_FLAGS,
+    VIRTUAL_KEY,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+    XBUTTON1, XBUTTON2,
+};
+
+fn send(input: INPUT) -> Result<(), String> {
+    let sent = unsafe { SendInput(&[input], std::mem::size_of::<INPUT>() as i32) };
+    if sent == 1 {
+        Ok(())
+    } else {
+        Err(format!(
+            "SendInput failed: {:?}",
+            windows::core::Error::from_win32()
+        ))
+    }
+}
+
+fn mouse_input(dx: i32, dy: i32, data: u32, flags: MOUSE_EVENT_FLAGS) -> INPUT {
+    INPUT {
+        r#type: INPUT_MOUSE,
+        Anonymous: INPUT_0 {
+            mi: MOUSEINPUT {
+                dx,
+                dy,
+                mouseData: data,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    }
+}
+
+/// Presses or releases `key` by scan code, so the result does not depend on the
+/// active keyboard layout.
+pub fn send_key(key: KeyId, state: KeyState) -> Result<(), String> {
+    let id = KeyMappingId::try_from(key.0).map_err(|_| format!("No scan code for {:?}", key))?;
+    let scancode = KeyMap::from(id).win;
+
+    let mut flags = KEYEVENTF_SCANCODE;
+    if scancode & 0xff00 == 0xe000 {
+        flags |= KEYEVENTF_EXTENDEDKEY;
+    }
+    if state == KeyState::Released {
+        flags |= KEYEVENTF_KEYUP;
+    }
+    send(INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(0),
+                wScan: scancode & 0xff,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    })
+}
+
+/// Moves the cursor to `pos` in virtual-screen coordinates.
+pub fn move_mouse(pos: Pos) -> Result<(), String> {
+    // Absolute coordinates are normalized to 0..=65535 across the virtual desktop.
+    let (left, top, width, height) = unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN).max(2),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN).max(2),
+        )
+    };
+    let normalize =
+        |v: i32, origin: i32, size: i32| ((v - origin) as i64 * 65535 / (size - 1) as i64) as i32;
+    send(mouse_input(
+        normalize(pos.x, left, width),
+        normalize(pos.y, top, height),
+        0,
+        MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK,
+    ))
+}
+
+/// Turns the wheel by `delta` as in `MouseInfo::wheel`.
+pub fn scroll_wheel(delta: Pos) -> Result<(), String> {
+    if delta.y != 0 {
+        send(mouse_input(0, 0, delta.y as u32, MOUSEEVENTF_WHEEL))?;
+    }
+    if delta.x != 0 {
+        send(mouse_input(0, 0, delta.x as u32, MOUSEEVENTF_HWHEEL))?;
+    }
+    Ok(())
+}
+
+/// Presses or releases `button` at the current cursor position.
+pub fn send_mouse_button(button: MouseButton) -> Result<(), String> {
+    let pressed = button.state() == KeyState::Pressed;
+    let pick = |down, up| if pressed { down } else { up };
+    let (flags, data) = match button {
+        MouseButton::Left(_) => (pick(MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP), 0),
+        MouseButton::Right(_) => (pick(MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP), 0),
+        MouseButton::Middle(_) => (pick(MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP), 0),
+        MouseButton::X1(_) => (pick(MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP), XBUTTON1 as u32),
+        MouseButton::X2(_) => (pick(MOUSEEVENTF_XDOWN, MOUSEEVENTF_XUP), XBUTTON2 as u32),
+    };
+    send(mouse_input(0, 0, data, flags))
+}
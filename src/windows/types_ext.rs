@@ -1,13 +1,20 @@
-use crate::types::{KeyId, KeyMap, VirtualKeyId};
+use crate::types::{KeyId, KeyMap, LockKeyState, MouseStateFlags, Rect, VirtualKeyId};
+use windows::Win32::Foundation::RECT;
 use windows::Win32::UI::{
     Input::{
         KeyboardAndMouse::{
-            MapVirtualKeyW, MAPVK_VK_TO_VSC_EX, VIRTUAL_KEY, VK_LCONTROL, VK_LMENU, VK_LWIN,
-            VK_RCONTROL, VK_RMENU, VK_RWIN,
+            GetKeyState, MapVirtualKeyW, MAPVK_VK_TO_VSC_EX, VIRTUAL_KEY, VK_BROWSER_BACK,
+            VK_BROWSER_FAVORITES, VK_BROWSER_FORWARD, VK_BROWSER_HOME, VK_BROWSER_REFRESH,
+            VK_BROWSER_SEARCH, VK_BROWSER_STOP, VK_CAPITAL, VK_F13, VK_F14, VK_F15, VK_F16, VK_F17,
+            VK_F18, VK_F19, VK_F20, VK_F21, VK_F22, VK_F23, VK_F24, VK_LAUNCH_APP1, VK_LAUNCH_APP2,
+            VK_LAUNCH_MAIL, VK_LAUNCH_MEDIA_SELECT, VK_LCONTROL, VK_LMENU, VK_LWIN,
+            VK_MEDIA_NEXT_TRACK, VK_MEDIA_PLAY_PAUSE, VK_MEDIA_PREV_TRACK, VK_MEDIA_STOP,
+            VK_NUMLOCK, VK_RCONTROL, VK_RMENU, VK_RWIN, VK_SCROLL, VK_VOLUME_DOWN, VK_VOLUME_MUTE,
+            VK_VOLUME_UP,
         },
         RAWKEYBOARD,
     },
-    WindowsAndMessaging::{KBDLLHOOKSTRUCT, RI_KEY_E0, RI_KEY_E1},
+    WindowsAndMessaging::{KBDLLHOOKSTRUCT, LLKHF_EXTENDED, RI_KEY_E0, RI_KEY_E1},
 };
 
 impl KeyId {
@@ -19,13 +26,58 @@ impl KeyId {
             Err(())
         }
     }
+
+    /// Keys known by their virtual key alone. Media and browser keys often
+    /// arrive from HID consumer devices without a usable scan code, and
+    /// F13–F24 scan codes vary between keyboards.
+    fn from_virtual_key(vk: VIRTUAL_KEY) -> Option<Self> {
+        let key = match vk {
+            VK_VOLUME_MUTE => VirtualKeyId::AudioVolumeMute,
+            VK_VOLUME_DOWN => VirtualKeyId::AudioVolumeDown,
+            VK_VOLUME_UP => VirtualKeyId::AudioVolumeUp,
+            VK_MEDIA_NEXT_TRACK => VirtualKeyId::MediaTrackNext,
+            VK_MEDIA_PREV_TRACK => VirtualKeyId::MediaTrackPrevious,
+            VK_MEDIA_STOP => VirtualKeyId::MediaStop,
+            VK_MEDIA_PLAY_PAUSE => VirtualKeyId::MediaPlayPause,
+            VK_BROWSER_BACK => VirtualKeyId::BrowserBack,
+            VK_BROWSER_FORWARD => VirtualKeyId::BrowserForward,
+            VK_BROWSER_REFRESH => VirtualKeyId::BrowserRefresh,
+            VK_BROWSER_STOP => VirtualKeyId::BrowserStop,
+            VK_BROWSER_SEARCH => VirtualKeyId::BrowserSearch,
+            VK_BROWSER_FAVORITES => VirtualKeyId::BrowserFavorites,
+            VK_BROWSER_HOME => VirtualKeyId::BrowserHome,
+            VK_LAUNCH_MAIL => VirtualKeyId::LaunchMail,
+            VK_LAUNCH_MEDIA_SELECT => VirtualKeyId::MediaSelect,
+            VK_LAUNCH_APP1 => VirtualKeyId::LaunchApp1,
+            VK_LAUNCH_APP2 => VirtualKeyId::LaunchApp2,
+            VK_F13 => VirtualKeyId::F13,
+            VK_F14 => VirtualKeyId::F14,
+            VK_F15 => VirtualKeyId::F15,
+            VK_F16 => VirtualKeyId::F16,
+            VK_F17 => VirtualKeyId::F17,
+            VK_F18 => VirtualKeyId::F18,
+            VK_F19 => VirtualKeyId::F19,
+            VK_F20 => VirtualKeyId::F20,
+            VK_F21 => VirtualKeyId::F21,
+            VK_F22 => VirtualKeyId::F22,
+            VK_F23 => VirtualKeyId::F23,
+            VK_F24 => VirtualKeyId::F24,
+            _ => return None,
+        };
+        Some(Self(key))
+    }
 }
 
 impl TryFrom<KBDLLHOOKSTRUCT> for KeyId {
     type Error = ();
 
     fn try_from(value: KBDLLHOOKSTRUCT) -> Result<Self, Self::Error> {
-        let scancode = value.scanCode;
+        // The hook strips the E0 prefix into a flag; without it the keypad and
+        // the navigation block (NumpadEnter and Enter, Numpad7 and Home) collide.
+        let mut scancode = value.scanCode & 0xFF;
+        if value.flags.0 & LLKHF_EXTENDED.0 != 0 {
+            scancode |= 0xE000;
+        }
         let vkcode = value.vkCode;
         match VIRTUAL_KEY(vkcode as u16) {
             VK_LWIN => Ok(Self(VirtualKeyId::MetaLeft)),
@@ -34,7 +86,7 @@ impl TryFrom<KBDLLHOOKSTRUCT> for KeyId {
             VK_RCONTROL => Ok(Self(VirtualKeyId::ControlRight)),
             VK_LMENU => Ok(Self(VirtualKeyId::AltLeft)),
             VK_RMENU => Ok(Self(VirtualKeyId::AltRight)),
-            _ => Self::from_scan_code(scancode),
+            vk => Self::from_virtual_key(vk).map_or_else(|| Self::from_scan_code(scancode), Ok),
         }
     }
 }
@@ -43,6 +95,9 @@ impl TryFrom<RAWKEYBOARD> for KeyId {
     type Error = ();
 
     fn try_from(keyboard: RAWKEYBOARD) -> Result<Self, Self::Error> {
+        if let Some(key) = Self::from_virtual_key(VIRTUAL_KEY(keyboard.VKey)) {
+            return Ok(key);
+        }
         let scancode = if keyboard.MakeCode != 0 {
             (keyboard.MakeCode as u32 & 0x7f)
                 | ((if keyboard.Flags as u32 & RI_KEY_E0 != 0 {
@@ -58,3 +113,55 @@ impl TryFrom<RAWKEYBOARD> for KeyId {
         Self::from_scan_code(scancode)
     }
 }
+
+impl LockKeyState {
+    pub(crate) fn current() -> Self {
+        let toggled = |vk: VIRTUAL_KEY| unsafe { GetKeyState(vk.0 as i32) } & 1 != 0;
+        Self {
+            caps: toggled(VK_CAPITAL),
+            num: toggled(VK_NUMLOCK),
+            scroll: toggled(VK_SCROLL),
+        }
+    }
+}
+
+// Signature Windows stores in the message extra info of mouse input promoted
+// from pen or touch. See "System Events and Mouse Messages" on MSDN.
+const MI_WP_SIGNATURE: usize = 0xFF515700;
+const SIGNATURE_MASK: usize = 0xFFFFFF00;
+const MI_TOUCH_FLAG: usize = 0x80;
+
+impl MouseStateFlags {
+    pub(crate) fn from_extra_info(extra_info: usize) -> Self {
+        if extra_info & SIGNATURE_MASK != MI_WP_SIGNATURE {
+            return Self::empty();
+        }
+        if extra_info & MI_TOUCH_FLAG != 0 {
+            Self::FROM_TOUCH
+        } else {
+            Self::FROM_PEN
+        }
+    }
+}
+
+impl From<RECT> for Rect {
+    fn from(rect: RECT) -> Self {
+        Rect {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
+
+impl From<Rect> for RECT {
+    fn from(rect: Rect) -> Self {
+        RECT {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Queries about top-level windows.
+
+use crate::types::{Event, Pos, WindowInfo};
+use std::cell::{Cell, RefCell};
+use windows::core::PWSTR;
+use windows::Win32::Foundation::{CloseHandle, HWND, MAX_PATH, POINT, RECT};
+use windows::Win32::Graphics::Gdi::{
+    GetMonitorInfoW, MonitorFromWindow, MONITORINFO, MONITOR_DEFAULTTONULL,
+};
+use windows::Win32::System::Threading::{
+    OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetAncestor, GetClassNameW, GetDesktopWindow, GetForegroundWindow, GetShellWindow,
+    GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, WindowFromPoint,
+    EVENT_SYSTEM_FOREGROUND, GA_ROOT, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT,
+};
+
+thread_local! {
+    /// The last process looked up; the foreground process rarely changes
+    /// between events.
+    static LOCAL_PROCESS_NAME: RefCell<Option<(u32, String)>> = RefCell::new(None);
+    /// The foreground WinEvent hook of this event loop thread and where it
+    /// reports to.
+    static LOCAL_FOCUS_HOOK: Cell<Option<(isize, fn(Event))>> = const { Cell::new(None) };
+}
+
+/// File name of the executable running `process_id`, e.g. "notepad.exe".
+fn process_name(process_id: u32) -> Option<String> {
+    if let Some(name) = LOCAL_PROCESS_NAME.with_borrow(|cached| {
+        cached
+            .as_ref()
+            .filter(|(id, _)| *id == process_id)
+            .map(|(_, name)| name.clone())
+    }) {
+        return Some(name);
+    }
+    let name = query_process_name(process_id)?;
+    LOCAL_PROCESS_NAME.with_borrow_mut(|cached| *cached = Some((process_id, name.clone())));
+    Some(name)
+}
+
+fn query_process_name(process_id: u32) -> Option<String> {
+    let mut buf = [0u16; MAX_PATH as usize];
+    let mut len = buf.len() as u32;
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, process_id).ok()?;
+        let result = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buf.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(process);
+        result.ok()?;
+    }
+    let path = String::from_utf16_lossy(&buf[..len as usize]);
+    path.rsplit('\\').next().map(str::to_string)
+}
+
+pub(crate) fn window_info(hwnd: HWND) -> Option<WindowInfo> {
+    if hwnd.0.is_null() {
+        return None;
+    }
+    let mut title = [0u16; 512];
+    let mut class = [0u16; 256];
+    let mut process_id = 0;
+    let (title_len, class_len) = unsafe {
+        GetWindowThreadProcessId(hwnd, Some(&mut process_id));
+        (
+            GetWindowTextW(hwnd, &mut title).max(0) as usize,
+            GetClassNameW(hwnd, &mut class).max(0) as usize,
+        )
+    };
+    Some(WindowInfo {
+        hwnd: hwnd.0 as isize,
+        title: String::from_utf16_lossy(&title[..title_len]),
+        class: String::from_utf16_lossy(&class[..class_len]),
+        process_id,
+        // Processes of other users or elevated ones cannot be opened.
+        process_name: process_name(process_id).unwrap_or_default(),
+    })
+}
+
+/// Whether the foreground window covers its whole monitor, as exclusive and
+/// borderless fullscreen games do. The desktop and shell windows don't count.
+pub(crate) fn is_foreground_fullscreen() -> bool {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.0.is_null() || hwnd == GetDesktopWindow() || hwnd == GetShellWindow() {
+            return false;
+        }
+        let mut rect = RECT::default();
+        if GetWindowRect(hwnd, &mut rect).is_err() {
+            return false;
+        }
+        let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONULL);
+        if monitor.0.is_null() {
+            return false;
+        }
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        if !GetMonitorInfoW(monitor, &mut info).as_bool() {
+            return false;
+        }
+        let screen = info.rcMonitor;
+        rect.left <= screen.left
+            && rect.top <= screen.top
+            && rect.right >= screen.right
+            && rect.bottom >= screen.bottom
+    }
+}
+
+/// The top-level window at `pos` in screen coordinates.
+pub(crate) fn window_at(pos: &Pos) -> Option<WindowInfo> {
+    let hwnd = super::with_physical_pixels(|| unsafe {
+        let child = WindowFromPoint(POINT { x: pos.x, y: pos.y });
+        GetAncestor(child, GA_ROOT)
+    });
+    window_info(hwnd)
+}
+
+/// The window that currently receives keyboard input.
+pub(crate) fn foreground_window() -> Option<WindowInfo> {
+    window_info(unsafe { GetForegroundWindow() })
+}
+
+unsafe extern "system" fn on_foreground(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    id_object: i32,
+    _id_child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    if id_object != OBJID_WINDOW.0 {
+        return;
+    }
+    if let (Some((_, sink)), Some(info)) = (LOCAL_FOCUS_HOOK.get(), window_info(hwnd)) {
+        sink(Event::FocusChanged(info));
+    }
+}
+
+/// Passes `Event::FocusChanged` to `sink` whenever another window comes to
+/// the foreground. The hook is out of context, so it runs on the calling
+/// thread, which must pump messages.
+pub(crate) fn start_focus_events(sink: fn(Event)) {
+    let hook = unsafe {
+        SetWinEventHook(
+            EVENT_SYSTEM_FOREGROUND,
+            EVENT_SYSTEM_FOREGROUND,
+            None,
+            Some(on_foreground),
+            0,
+            0,
+            WINEVENT_OUTOFCONTEXT,
+        )
+    };
+    if !hook.is_invalid() {
+        LOCAL_FOCUS_HOOK.set(Some((hook.0 as isize, sink)));
+    }
+}
+
+pub(crate) fn stop_focus_events() {
+    if let Some((hook, _)) = LOCAL_FOCUS_HOOK.take() {
+        let _ = unsafe { UnhookWinEvent(HWINEVENTHOOK(hook as _)) };
+    }
+}
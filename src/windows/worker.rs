@@ -1,19 +1,23 @@
 #![allow(unused)]
 
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use std::{
-    sync::{mpsc::Sender, Arc, Mutex},
-    thread,
-};
-use windows::Win32::UI::WindowsAndMessaging::{
-    KBDLLHOOKSTRUCT, MSLLHOOKSTRUCT, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
-    WM_MBUTTONUP, WM_MOUSEMOVE, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError},
+        Arc, Mutex, Weak,
+    },
+    thread::{self, ThreadId},
 };
 
+use super::listener::{Listener, ListenerConfig};
 use crate::consts;
 use crate::types::{
-    EventType, JoinHandleType, KeyId, KeyInfo, KeyState, KeyboardState, MouseButton, MouseInfo,
-    ClickState, Pos,
+    ClickState, Event, HidInfo, JoinHandleType, KeyId, KeyInfo, KeyState, KeyboardState,
+    KmHookError, Latency, Metrics, MouseButton, MouseInfo, MouseKey, MouseStateFlags, Pos,
+    ThreadPriority, VirtualKeyId, ID,
 };
 
 #[derive(Debug, Clone)]
@@ -26,9 +30,10 @@ impl KeyboardSysMsg {
         Self { key_info }
     }
 
-    fn translate_msg(&self) -> Option<EventType> {
-        let mut key = self.key_info.clone();
-        Some(EventType::KeyboardEvent(Some(key)))
+    fn translate_msg(&self) -> Option<Event> {
+        let mut key_info = self.key_info.clone();
+        key_info.text = super::key_text(&key_info);
+        Some(Event::KeyboardEvent(key_info))
     }
 }
 
@@ -42,8 +47,334 @@ impl MouseSysMsg {
         Self { mouse_info }
     }
 
-    fn translate_msg(&self) -> Option<EventType> {
-        Some(EventType::MouseEvent(Some(self.mouse_info.clone())))
+    fn translate_msg(&self) -> Option<Event> {
+        Some(Event::MouseEvent(self.mouse_info.clone()))
+    }
+}
+
+impl MouseSysMsg {
+    fn is_move(&self) -> bool {
+        self.mouse_info.is_move()
+    }
+
+    fn absorb(&mut self, later: MouseSysMsg) {
+        self.mouse_info.absorb(later.mouse_info);
+    }
+}
+
+/// Event numbering and the counters behind `Listener::metrics`, shared by
+/// the event loop and workers.
+#[derive(Debug, Default)]
+pub(crate) struct MetricsRecorder {
+    /// Last sequence number handed out; see `KeyInfo::seq`.
+    seq: AtomicU64,
+    keyboard_events: AtomicU64,
+    mouse_events: AtomicU64,
+    dropped_events: AtomicU64,
+    latencies: Mutex<VecDeque<Duration>>,
+}
+
+impl MetricsRecorder {
+    /// Counts an input message and numbers it.
+    pub fn received(&self, msg: &mut WorkerMsg) {
+        let (counter, seq) = match msg {
+            WorkerMsg::KeyboardEvent(msg) => (&self.keyboard_events, &mut msg.key_info.seq),
+            WorkerMsg::MouseEvent(msg) => (&self.mouse_events, &mut msg.mouse_info.seq),
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        *seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+    }
+
+    pub fn dropped(&self) {
+        self.dropped_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn dispatched(&self, captured: Instant) {
+        let mut latencies = self.latencies.lock().unwrap();
+        if latencies.len() == consts::METRICS_LATENCY_SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(captured.elapsed());
+    }
+
+    /// Everything but the queue depths, which belong to the workers.
+    pub fn snapshot(&self) -> Metrics {
+        let mut latencies: Vec<Duration> = self.latencies.lock().unwrap().iter().copied().collect();
+        latencies.sort_unstable();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        Metrics {
+            keyboard_events: self.keyboard_events.load(Ordering::Relaxed),
+            mouse_events: self.mouse_events.load(Ordering::Relaxed),
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
+            queue_depth: HashMap::new(),
+            latency: (!latencies.is_empty()).then(|| Latency {
+                p50: percentile(50),
+                p90: percentile(90),
+                p99: percentile(99),
+                max: percentile(100),
+            }),
+        }
+    }
+}
+
+/// Limits how often mouse moves are forwarded to the workers. A move that
+/// comes too soon is held back and folded into the next one; it goes out on
+/// its own from `tick` once the interval is over, so the cursor's last
+/// position always arrives. One per listener, shared by its event loops.
+#[derive(Debug, Default)]
+pub(crate) struct MoveThrottle {
+    last_sent: Option<Instant>,
+    skipped: Option<MouseSysMsg>,
+}
+
+impl MoveThrottle {
+    /// What to send for `msg`: the move held back first if `msg` is not a
+    /// move, then `msg` unless it is held back itself.
+    pub fn filter(
+        &mut self,
+        msg: WorkerMsg,
+        min_interval: Duration,
+        metrics: &MetricsRecorder,
+    ) -> [Option<WorkerMsg>; 2] {
+        let mouse = match msg {
+            WorkerMsg::MouseEvent(mouse) if mouse.is_move() => mouse,
+            msg => return [self.skipped.take().map(WorkerMsg::MouseEvent), Some(msg)],
+        };
+        let mouse = match self.skipped.take() {
+            Some(mut skipped) => {
+                skipped.absorb(mouse);
+                metrics.dropped();
+                skipped
+            }
+            None => mouse,
+        };
+        let now = Instant::now();
+        if self
+            .last_sent
+            .map_or(false, |last| now.duration_since(last) < min_interval)
+        {
+            self.skipped = Some(mouse);
+            return [None, None];
+        }
+        self.last_sent = Some(now);
+        [None, Some(WorkerMsg::MouseEvent(mouse))]
+    }
+
+    /// The move held back, once `min_interval` has passed since the last one
+    /// went out, and when to tick again: until then moves may be held back.
+    pub fn tick(&mut self, min_interval: Duration) -> (Option<WorkerMsg>, Option<Instant>) {
+        let now = Instant::now();
+        let next = self
+            .last_sent
+            .map(|last| last + min_interval)
+            .filter(|&at| at > now);
+        if next.is_some() {
+            return (None, next);
+        }
+        match self.skipped.take() {
+            Some(mouse) => {
+                self.last_sent = Some(now);
+                (Some(WorkerMsg::MouseEvent(mouse)), Some(now + min_interval))
+            }
+            None => (None, None),
+        }
+    }
+}
+
+/// Merges the moves queued right behind `msg` into it. A message that is not
+/// a move ends the run and is handed back through `next`.
+fn coalesce_moves(
+    msg: WorkerMsg,
+    mut try_recv: impl FnMut() -> Option<WorkerMsg>,
+    next: &mut Option<WorkerMsg>,
+    metrics: &MetricsRecorder,
+) -> WorkerMsg {
+    let WorkerMsg::MouseEvent(mut first) = msg else {
+        return msg;
+    };
+    if !first.is_move() {
+        return WorkerMsg::MouseEvent(first);
+    }
+    while let Some(following) = try_recv() {
+        match following {
+            WorkerMsg::MouseEvent(later) if later.is_move() => {
+                first.absorb(later);
+                metrics.dropped();
+            }
+            other => {
+                next.replace(other);
+                break;
+            }
+        }
+    }
+    WorkerMsg::MouseEvent(first)
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Name, priority and CPU affinity of a thread the crate spawns; see the
+/// thread options of `ListenerBuilder`.
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadSpec {
+    pub name: String,
+    pub priority: ThreadPriority,
+    pub cpu_affinity: Option<usize>,
+}
+
+impl ThreadSpec {
+    /// The event loop thread of `listener`, or of a default one if it is
+    /// gone.
+    pub fn event_loop(listener: &Weak<Listener>) -> Self {
+        match listener.upgrade() {
+            Some(listener) => listener.config().event_loop_thread(),
+            None => ListenerConfig::default().event_loop_thread(),
+        }
+    }
+
+    /// The same thread with `suffix` added to its name.
+    pub fn numbered(&self, suffix: usize) -> Self {
+        Self {
+            name: format!("{}-{}", self.name, suffix),
+            ..self.clone()
+        }
+    }
+
+    pub fn spawn<F, T>(self, f: F) -> std::io::Result<thread::JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        thread::Builder::new().name(self.name).spawn(move || {
+            super::configure_thread(self.priority, self.cpu_affinity);
+            f()
+        })
+    }
+}
+
+/// Threads that run callbacks instead of the workers; see
+/// `ListenerBuilder::callback_threads`. They end when the pool is dropped.
+#[derive(Debug)]
+pub(crate) struct CallbackPool {
+    tx: Mutex<Sender<Job>>,
+}
+
+impl CallbackPool {
+    pub fn new(threads: usize, thread: ThreadSpec) -> Result<Self, KmHookError> {
+        let (tx, rx) = std::sync::mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        for i in 0..threads.max(1) {
+            let rx = rx.clone();
+            thread
+                .numbered(i)
+                .spawn(move || loop {
+                    let job = match rx.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    job();
+                })
+                .map_err(|e| KmHookError::Thread(e.to_string()))?;
+        }
+        Ok(Self { tx: Mutex::new(tx) })
+    }
+
+    pub fn execute(&self, job: Job) {
+        let _ = self.tx.lock().unwrap().send(job);
+    }
+}
+
+/// A worker's queue handle that keeps its depth count in step.
+#[derive(Debug, Clone)]
+pub(crate) struct WorkerSender {
+    tx: WorkerTx,
+    queued: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Clone)]
+enum WorkerTx {
+    Unbounded(Sender<WorkerMsg>),
+    /// See `ListenerBuilder::worker_queue_capacity`.
+    Bounded(SyncSender<WorkerMsg>),
+}
+
+impl WorkerSender {
+    /// Returns `false` if the message was dropped because a bounded queue is
+    /// full. `Stop` waits for room instead, so the worker always ends.
+    pub fn send(&self, msg: WorkerMsg) -> bool {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let sent = match &self.tx {
+            WorkerTx::Unbounded(tx) => tx.send(msg).map_err(|_| true),
+            WorkerTx::Bounded(tx) if matches!(msg, WorkerMsg::Stop) => {
+                tx.send(msg).map_err(|_| true)
+            }
+            WorkerTx::Bounded(tx) => tx
+                .try_send(msg)
+                .map_err(|e| matches!(e, TrySendError::Disconnected(_))),
+        };
+        match sent {
+            Ok(()) => true,
+            Err(disconnected) => {
+                self.queued.fetch_sub(1, Ordering::Relaxed);
+                disconnected
+            }
+        }
+    }
+}
+
+/// A snapshot of where an event loop delivers its events. Each loop thread
+/// keeps its own copy, so the hook procs reach the workers without locking.
+#[derive(Debug, Default)]
+pub(crate) struct Routes {
+    senders: Vec<WorkerSender>,
+    throttle: Arc<Mutex<MoveThrottle>>,
+    min_move_interval: Option<Duration>,
+    metrics: Arc<MetricsRecorder>,
+}
+
+impl Routes {
+    pub fn new(
+        senders: Vec<WorkerSender>,
+        throttle: Arc<Mutex<MoveThrottle>>,
+        min_move_interval: Option<Duration>,
+        metrics: Arc<MetricsRecorder>,
+    ) -> Self {
+        Self {
+            senders,
+            throttle,
+            min_move_interval,
+            metrics,
+        }
+    }
+
+    pub fn send(&mut self, mut msg: WorkerMsg) {
+        self.metrics.received(&mut msg);
+        match self.min_move_interval {
+            Some(min_interval) => {
+                let msgs = self
+                    .throttle
+                    .lock()
+                    .unwrap()
+                    .filter(msg, min_interval, &self.metrics);
+                for msg in msgs.into_iter().flatten() {
+                    self.deliver(msg);
+                }
+            }
+            None => self.deliver(msg),
+        }
+    }
+
+    fn deliver(&self, msg: WorkerMsg) {
+        if let Some((last, rest)) = self.senders.split_last() {
+            for tx in rest {
+                if !tx.send(msg.clone()) {
+                    self.metrics.dropped();
+                }
+            }
+            if !last.send(msg) {
+                self.metrics.dropped();
+            }
+        }
     }
 }
 
@@ -51,89 +382,375 @@ impl MouseSysMsg {
 pub(crate) enum WorkerMsg {
     KeyboardEvent(KeyboardSysMsg),
     MouseEvent(MouseSysMsg),
+    /// An event raised by the event loop itself rather than by input.
+    Notify(Event),
+    HidEvent(HidInfo),
+    /// A shortcut served by `RegisterHotKey` was pressed.
+    Hotkey(ID),
     Stop,
 }
 
+/// Records when each key went down and fills in `held_for` on its release.
+fn track_hold(pressed_at: &mut HashMap<KeyId, Instant>, key_info: &mut KeyInfo) {
+    let at = key_info
+        .keyboard_state
+        .as_ref()
+        .map_or_else(Instant::now, |snapshot| snapshot.timestamp);
+    match key_info.state {
+        KeyState::Pressed if !key_info.is_repeat => {
+            pressed_at.insert(key_info.key_id, at);
+        }
+        KeyState::Released => {
+            key_info.held_for = pressed_at
+                .remove(&key_info.key_id)
+                .map(|since| at.saturating_duration_since(since));
+        }
+        _ => {}
+    }
+}
+
+/// The release of the left Ctrl an AltGr layout pressed along with right
+/// Alt. The event loop leaves it out of the snapshot once the right Alt
+/// shows it was AltGr, but listeners already got the press.
+fn alt_gr_release(pressed_at: &HashMap<KeyId, Instant>, key_info: &KeyInfo) -> Option<KeyInfo> {
+    let ctrl = KeyId(VirtualKeyId::ControlLeft);
+    let alt_gr = key_info.key_id == KeyId(VirtualKeyId::AltRight)
+        && key_info.state == KeyState::Pressed
+        && key_info
+            .keyboard_state
+            .as_ref()
+            .map_or(false, |snapshot| snapshot.alt_gr);
+    (alt_gr && pressed_at.contains_key(&ctrl)).then(|| KeyInfo {
+        // VK_LCONTROL and its scan code.
+        vk: 0xA2,
+        scancode: 0x1D,
+        injected: key_info.injected,
+        device: key_info.device,
+        keyboard_state: key_info.keyboard_state.clone(),
+        timestamp: key_info.timestamp,
+        system_time: key_info.system_time,
+        time: key_info.time,
+        ..KeyInfo::new(ctrl, KeyState::Released)
+    })
+}
+
+/// The last press of a run of clicks, for `MouseInfo::click_count`.
+#[derive(Debug)]
+struct LastClick {
+    key: MouseKey,
+    at: Instant,
+    pos: Pos,
+    count: u32,
+}
+
+/// Fills in `click_count` and `MouseStateFlags::DOUBLE` of button events: a
+/// press continues the run of the previous one if it is the same button,
+/// within the double-click time and inside the double-click area around it.
+fn count_clicks(last: &mut Option<LastClick>, mouse_info: &mut MouseInfo) {
+    let Some(button) = &mouse_info.button else {
+        return;
+    };
+    let key = MouseKey::from(button);
+    if button.state() != ClickState::Pressed {
+        mouse_info.click_count = match last {
+            Some(click) if click.key == key => click.count,
+            _ => 1,
+        };
+        mark_double(mouse_info);
+        return;
+    }
+    let interval = Duration::from_millis(super::double_click_interval() as u64);
+    let size = super::double_click_size();
+    let at = mouse_info.timestamp;
+    let count = match last {
+        Some(click)
+            if click.key == key
+                && at.saturating_duration_since(click.at) <= interval
+                && (mouse_info.pos.x - click.pos.x).abs() * 2 <= size.x
+                && (mouse_info.pos.y - click.pos.y).abs() * 2 <= size.y =>
+        {
+            click.count + 1
+        }
+        _ => 1,
+    };
+    mouse_info.click_count = count;
+    mark_double(mouse_info);
+    *last = Some(LastClick {
+        key,
+        at,
+        pos: mouse_info.pos.clone(),
+        count,
+    });
+}
+
+fn mark_double(mouse_info: &mut MouseInfo) {
+    mouse_info
+        .flags
+        .set(MouseStateFlags::DOUBLE, mouse_info.click_count >= 2);
+}
+
+/// Recent cursor positions, for `MouseInfo::velocity`.
+#[derive(Debug, Default)]
+struct MotionTracker {
+    samples: VecDeque<(Instant, Pos)>,
+    velocity: Pos,
+}
+
+impl MotionTracker {
+    /// Fills in `velocity` and `acceleration` of a move from the positions
+    /// seen over the last `window`.
+    fn track(&mut self, window: Duration, mouse_info: &mut MouseInfo) {
+        if !mouse_info.is_move() {
+            return;
+        }
+        let now = mouse_info.timestamp;
+        let previous = self.samples.back().map(|(at, _)| *at);
+        self.samples.push_back((now, mouse_info.pos.clone()));
+        // Keep the last sample older than the window as the baseline.
+        while self.samples.len() > 2 && now.saturating_duration_since(self.samples[1].0) >= window {
+            self.samples.pop_front();
+        }
+        let (since, from) = &self.samples[0];
+        let span = now.saturating_duration_since(*since).as_secs_f64();
+        let velocity = if span > 0.0 {
+            Pos {
+                x: ((mouse_info.pos.x - from.x) as f64 / span).round() as i32,
+                y: ((mouse_info.pos.y - from.y) as f64 / span).round() as i32,
+            }
+        } else {
+            Pos::default()
+        };
+        let step = previous.map_or(0.0, |at| now.saturating_duration_since(at).as_secs_f64());
+        if step > 0.0 {
+            mouse_info.acceleration = Pos {
+                x: ((velocity.x - self.velocity.x) as f64 / step).round() as i32,
+                y: ((velocity.y - self.velocity.y) as f64 / step).round() as i32,
+            };
+        }
+        mouse_info.velocity = velocity.clone();
+        self.velocity = velocity;
+    }
+}
+
 impl WorkerMsg {
-    fn translate_msg(&self) -> Option<EventType> {
+    fn translate_msg(&self) -> Option<Event> {
         match self {
             WorkerMsg::KeyboardEvent(msg) => msg.translate_msg(),
             WorkerMsg::MouseEvent(msg) => msg.translate_msg(),
-            WorkerMsg::Stop => None,
+            WorkerMsg::Notify(event) => Some(event.clone()),
+            WorkerMsg::HidEvent(info) => Some(Event::HidEvent(info.clone())),
+            WorkerMsg::Hotkey(_) | WorkerMsg::Stop => None,
+        }
+    }
+
+    fn captured(&self) -> Option<Instant> {
+        match self {
+            WorkerMsg::KeyboardEvent(msg) => Some(msg.key_info.timestamp),
+            WorkerMsg::MouseEvent(msg) => Some(msg.mouse_info.timestamp),
+            WorkerMsg::HidEvent(info) => Some(info.timestamp),
+            _ => None,
         }
     }
 }
 
 pub(crate) struct Worker {
-    msg_sender: Mutex<Option<Sender<WorkerMsg>>>,
+    thread: ThreadSpec,
+    /// Deliver only the latest of the mouse moves waiting in the queue.
+    coalesce_moves: bool,
+    velocity_window: Duration,
+    /// Messages that fit the queue; unbounded when `None`.
+    capacity: Option<usize>,
+    msg_sender: Mutex<Option<WorkerSender>>,
+    msg_receiver: Mutex<Option<Receiver<WorkerMsg>>>,
+    queued: Arc<AtomicUsize>,
+    metrics: Arc<MetricsRecorder>,
+    /// The thread running the worker loop, while it runs.
+    thread_id: Arc<Mutex<Option<ThreadId>>>,
 }
 
 impl Drop for Worker {
     fn drop(&mut self) {
-        println!("Worker drop");
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Worker drop");
     }
 }
 
 impl Worker {
-    pub fn new() -> Self {
+    pub fn new(
+        thread: ThreadSpec,
+        coalesce_moves: bool,
+        velocity_window: Duration,
+        capacity: Option<usize>,
+        metrics: Arc<MetricsRecorder>,
+    ) -> Self {
         Self {
+            thread,
+            coalesce_moves,
+            velocity_window,
+            capacity,
             msg_sender: Mutex::new(None),
+            msg_receiver: Mutex::new(None),
+            queued: Arc::new(AtomicUsize::new(0)),
+            metrics,
+            thread_id: Arc::new(Mutex::new(None)),
         }
     }
 
-    pub fn run<F>(self: &Arc<Self>, handle: F, with_thread: Option<bool>) -> Option<JoinHandleType>
+    /// Creates the worker's queue ahead of `run` so the event loop can be
+    /// handed its sender before any worker thread exists.
+    pub fn open(&self) {
+        let mut msg_receiver = self.msg_receiver.lock().unwrap();
+        if msg_receiver.is_none() {
+            let (tx, rx) = match self.capacity {
+                Some(capacity) => {
+                    let (tx, rx) = std::sync::mpsc::sync_channel(capacity);
+                    (WorkerTx::Bounded(tx), rx)
+                }
+                None => {
+                    let (tx, rx) = std::sync::mpsc::channel();
+                    (WorkerTx::Unbounded(tx), rx)
+                }
+            };
+            self.queued.store(0, Ordering::Relaxed);
+            *self.msg_sender.lock().unwrap() = Some(WorkerSender {
+                tx,
+                queued: self.queued.clone(),
+            });
+            *msg_receiver = Some(rx);
+        }
+    }
+
+    pub fn sender(&self) -> Option<WorkerSender> {
+        self.msg_sender.lock().unwrap().clone()
+    }
+
+    /// Messages posted to the worker that it has not picked up yet.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.thread.name
+    }
+
+    pub fn thread_id(&self) -> Option<ThreadId> {
+        *self.thread_id.lock().unwrap()
+    }
+
+    /// `on_idle` runs before each message and whenever the time it returned
+    /// comes up while the queue is empty.
+    pub fn run<F, H, I>(
+        self: &Arc<Self>,
+        handle: F,
+        on_hotkey: H,
+        on_idle: I,
+        with_thread: Option<bool>,
+    ) -> Result<Option<JoinHandleType>, KmHookError>
     where
-        F: Fn(EventType) + Send + Sync + 'static,
+        F: Fn(Event) + Send + Sync + 'static,
+        H: Fn(ID) + Send + Sync + 'static,
+        I: Fn() -> Option<Instant> + Send + Sync + 'static,
     {
-        let (tx, rx) = std::sync::mpsc::channel();
-        {
-            let mut msg_sender = self.msg_sender.lock().unwrap();
-            *msg_sender = Some(tx);
-        }
+        self.open();
+        let rx = self.msg_receiver.lock().unwrap().take().unwrap();
         let threading = with_thread.unwrap_or(true);
 
         let handle = Arc::new(handle);
+        let coalesce = self.coalesce_moves;
+        let velocity_window = self.velocity_window;
+        let queued = self.queued.clone();
+        let metrics = self.metrics.clone();
+        let thread_id = self.thread_id.clone();
+        #[cfg(feature = "tracing")]
+        let name = self.thread.name.clone();
         let worker_loop = move || {
-            #[cfg(feature = "Debug")]
-            println!(
-                "Worker loop thread started with ID: {:?}",
-                std::thread::current().id()
-            );
-            while let Ok(msg) = rx.recv() {
-                if let WorkerMsg::Stop = msg {
-                    break;
+            *thread_id.lock().unwrap() = Some(thread::current().id());
+            #[cfg(feature = "tracing")]
+            let _span = tracing::debug_span!("worker", name = name.as_str()).entered();
+            #[cfg(feature = "tracing")]
+            tracing::debug!("worker loop started");
+            let mut pressed_at = HashMap::new();
+            let mut last_click = None;
+            let mut motion = MotionTracker::default();
+            let mut next = None;
+            let mut wake = None;
+            let taken = |msg: WorkerMsg| {
+                queued.fetch_sub(1, Ordering::Relaxed);
+                msg
+            };
+            let receive = |wake: &mut Option<Instant>| loop {
+                let Some(at) = *wake else {
+                    return rx.recv().ok();
+                };
+                match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                    Err(RecvTimeoutError::Timeout) => *wake = on_idle(),
+                    received => return received.ok(),
+                }
+            };
+            while let Some(msg) = next.take().or_else(|| receive(&mut wake).map(taken)) {
+                wake = on_idle();
+                let msg = if coalesce {
+                    coalesce_moves(msg, || rx.try_recv().ok().map(taken), &mut next, &metrics)
+                } else {
+                    msg
+                };
+                match msg {
+                    WorkerMsg::Stop => break,
+                    WorkerMsg::Hotkey(id) => {
+                        on_hotkey(id);
+                        continue;
+                    }
+                    _ => {}
                 }
-                if let Some(event) = msg.translate_msg() {
+                if let Some(mut event) = msg.translate_msg() {
+                    if let Event::KeyboardEvent(key_info) = &event {
+                        if let Some(mut release) = alt_gr_release(&pressed_at, key_info) {
+                            track_hold(&mut pressed_at, &mut release);
+                            handle(Event::KeyboardEvent(release));
+                        }
+                    }
+                    match &mut event {
+                        Event::KeyboardEvent(key_info) => track_hold(&mut pressed_at, key_info),
+                        Event::MouseEvent(mouse_info) => {
+                            count_clicks(&mut last_click, mouse_info);
+                            motion.track(velocity_window, mouse_info);
+                        }
+                        _ => {}
+                    }
+                    if let Some(captured) = msg.captured() {
+                        metrics.dispatched(captured);
+                    }
                     handle(event);
                     // let handle = Arc::clone(&handle);
                     // thread::spawn(move || handle(event));
                 } else {
-                    #[cfg(feature = "Debug")]
-                    println!(
-                        "Worker loop thread({:?}) translate_msg failed. {:?}",
-                        std::thread::current().id(),
-                        msg
-                    );
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(?msg, "translate_msg failed");
                 }
             }
-            #[cfg(feature = "Debug")]
-            println!(
-                "Worker loop thread({:?}) break.",
-                std::thread::current().id()
-            );
+            *thread_id.lock().unwrap() = None;
+            #[cfg(feature = "tracing")]
+            tracing::debug!("worker loop stopped");
         };
 
         if threading {
-            Some(thread::spawn(worker_loop))
+            let handle = self
+                .thread
+                .clone()
+                .spawn(worker_loop)
+                .map_err(|e| KmHookError::Thread(e.to_string()))?;
+            Ok(Some(handle))
         } else {
             worker_loop();
-            None
+            Ok(None)
         }
     }
 
     pub fn post_msg(&self, msg: WorkerMsg) {
         if let Some(tx) = self.msg_sender.lock().unwrap().as_ref() {
-            let _ = tx.send(msg);
+            if !tx.send(msg) {
+                self.metrics.dropped();
+            }
         }
     }
 }
@@ -0,0 +1,158 @@
+//! Delivers hook events through a `winit` event loop as user events, for GUI
+//! applications that handle everything on the event loop thread.
+//!
+//! ```no_run
+//! use kmhook::types::{Event, EventFilter, EventListener};
+//! use kmhook::{winit_ext, Listener};
+//! use winit::event_loop::EventLoop;
+//!
+//! enum UserEvent {
+//!     Hook(Event),
+//!     Toggle,
+//! }
+//!
+//! let event_loop = EventLoop::<UserEvent>::with_user_event().build().unwrap();
+//! let listener = Listener::new();
+//! let _handle = listener.startup(Some(true)).unwrap();
+//! let _keys = winit_ext::forward_events(
+//!     &listener,
+//!     event_loop.create_proxy(),
+//!     Some(EventFilter::Keyboard),
+//!     |event| Some(UserEvent::Hook(event)),
+//! )
+//! .unwrap();
+//! let _toggle = winit_ext::forward_shortcut(
+//!     &listener,
+//!     "Ctrl+Alt+T",
+//!     event_loop.create_proxy(),
+//!     || UserEvent::Toggle,
+//! )
+//! .unwrap();
+//! ```
+
+use crate::types::{Event, EventFilter, KeyId, KeyState, MouseButton, RegisterError, VirtualKeyId};
+use crate::{Listener, Subscription};
+use ::winit::event::{ElementState, MouseButton as WinitMouseButton};
+use ::winit::event_loop::EventLoopProxy;
+use ::winit::keyboard::KeyCode;
+use std::sync::{Arc, Mutex};
+
+/// Sends `map(event)` to `proxy` for events matching `filter`; events mapped
+/// to `None` are skipped. Stops when the subscription is dropped; events
+/// sent after the event loop exited are dropped.
+pub fn forward_events<T, F>(
+    listener: &Arc<Listener>,
+    proxy: EventLoopProxy<T>,
+    filter: Option<EventFilter>,
+    map: F,
+) -> Result<Subscription, String>
+where
+    T: Send + 'static,
+    F: Fn(Event) -> Option<T> + Send + Sync + 'static,
+{
+    let proxy = Mutex::new(proxy);
+    listener.add_event_listener_scoped(
+        move |event| {
+            if let Some(user_event) = map(event) {
+                let _ = proxy.lock().unwrap().send_event(user_event);
+            }
+        },
+        filter,
+    )
+}
+
+/// Sends `make()` to `proxy` each time `shortcut` fires.
+pub fn forward_shortcut<T, F>(
+    listener: &Arc<Listener>,
+    shortcut: &str,
+    proxy: EventLoopProxy<T>,
+    make: F,
+) -> Result<Subscription, RegisterError>
+where
+    T: Send + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let proxy = Mutex::new(proxy);
+    listener.add_global_shortcut_scoped(shortcut, move || {
+        let _ = proxy.lock().unwrap().send_event(make());
+    })
+}
+
+pub fn element_state(state: KeyState) -> ElementState {
+    match state {
+        KeyState::Pressed => ElementState::Pressed,
+        KeyState::Released => ElementState::Released,
+    }
+}
+
+pub fn mouse_button(button: &MouseButton) -> WinitMouseButton {
+    match button {
+        MouseButton::Left(_) => WinitMouseButton::Left,
+        MouseButton::Right(_) => WinitMouseButton::Right,
+        MouseButton::Middle(_) => WinitMouseButton::Middle,
+        MouseButton::X1(_) => WinitMouseButton::Back,
+        MouseButton::X2(_) => WinitMouseButton::Forward,
+    }
+}
+
+/// Both name keys by their position as in the W3C `code` values, so most
+/// map by name.
+macro_rules! same_code {
+    ($key:expr, { $($extra:tt)* }, $($name:ident),* $(,)?) => {
+        match $key {
+            $(VirtualKeyId::$name => Some(KeyCode::$name),)*
+            $($extra)*
+        }
+    };
+}
+
+/// The physical key winit reports for `key`; `None` for keys it has no code
+/// for and for side-less modifiers like `VirtualKeyId::Shift`.
+pub fn key_code(key: KeyId) -> Option<KeyCode> {
+    same_code!(key.0, {
+        VirtualKeyId::UsA => Some(KeyCode::KeyA),
+        VirtualKeyId::UsB => Some(KeyCode::KeyB),
+        VirtualKeyId::UsC => Some(KeyCode::KeyC),
+        VirtualKeyId::UsD => Some(KeyCode::KeyD),
+        VirtualKeyId::UsE => Some(KeyCode::KeyE),
+        VirtualKeyId::UsF => Some(KeyCode::KeyF),
+        VirtualKeyId::UsG => Some(KeyCode::KeyG),
+        VirtualKeyId::UsH => Some(KeyCode::KeyH),
+        VirtualKeyId::UsI => Some(KeyCode::KeyI),
+        VirtualKeyId::UsJ => Some(KeyCode::KeyJ),
+        VirtualKeyId::UsK => Some(KeyCode::KeyK),
+        VirtualKeyId::UsL => Some(KeyCode::KeyL),
+        VirtualKeyId::UsM => Some(KeyCode::KeyM),
+        VirtualKeyId::UsN => Some(KeyCode::KeyN),
+        VirtualKeyId::UsO => Some(KeyCode::KeyO),
+        VirtualKeyId::UsP => Some(KeyCode::KeyP),
+        VirtualKeyId::UsQ => Some(KeyCode::KeyQ),
+        VirtualKeyId::UsR => Some(KeyCode::KeyR),
+        VirtualKeyId::UsS => Some(KeyCode::KeyS),
+        VirtualKeyId::UsT => Some(KeyCode::KeyT),
+        VirtualKeyId::UsU => Some(KeyCode::KeyU),
+        VirtualKeyId::UsV => Some(KeyCode::KeyV),
+        VirtualKeyId::UsW => Some(KeyCode::KeyW),
+        VirtualKeyId::UsX => Some(KeyCode::KeyX),
+        VirtualKeyId::UsY => Some(KeyCode::KeyY),
+        VirtualKeyId::UsZ => Some(KeyCode::KeyZ),
+        VirtualKeyId::MetaLeft => Some(KeyCode::SuperLeft),
+        VirtualKeyId::MetaRight => Some(KeyCode::SuperRight),
+        _ => None,
+    },
+        Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+        Enter, Escape, Backspace, Tab, Space, Minus, Equal, BracketLeft, BracketRight, Backslash,
+        Semicolon, Quote, Backquote, Comma, Period, Slash, IntlBackslash,
+        CapsLock, NumLock, ScrollLock, PrintScreen, Pause, Insert, Delete, Home, End, PageUp,
+        PageDown, ArrowLeft, ArrowRight, ArrowUp, ArrowDown, ContextMenu,
+        ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight,
+        AudioVolumeMute, AudioVolumeDown, AudioVolumeUp, MediaPlayPause, MediaStop,
+        MediaTrackNext, MediaTrackPrevious, MediaSelect, LaunchMail, LaunchApp1, LaunchApp2,
+        BrowserBack, BrowserForward, BrowserRefresh, BrowserStop, BrowserSearch, BrowserFavorites,
+        BrowserHome,
+    )
+}